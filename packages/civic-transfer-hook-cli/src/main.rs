@@ -1,8 +1,7 @@
 use std::rc::Rc;
 use solana_clap_v3_utils::input_parsers::parse_url_or_moniker;
-use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::{system_instruction, system_program};
+use solana_sdk::system_instruction;
 use spl_transfer_hook_interface::get_extra_account_metas_address_and_bump_seed;
 use civic_transfer_hook;
 use {
@@ -26,7 +25,13 @@ use {
     },
     std::process::exit,
 };
-use civic_transfer_hook::instruction::CivicTransferHookInstruction;
+use civic_transfer_hook::extra_account_metas::build_extra_account_metas;
+use civic_transfer_hook::instruction::{initialize_extra_account_metas, migrate_extra_account_metas};
+use civic_transfer_hook::state::{
+    get_config_history_address, get_mint_config_address, get_mint_config_address_and_bump_seed,
+    ConfigHistory, MintConfig, ProgramAccount, TransferPolicy,
+};
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
 
 struct Config {
     commitment_config: CommitmentConfig,
@@ -44,26 +49,22 @@ pub fn get_extra_account_metas_address(mint: &Pubkey) -> Pubkey {
     address
 }
 
-pub fn initialize_extra_account_meta(
-    mint: &Pubkey,
-    mint_authority: &Pubkey,
-    gatekeeper_network: &Pubkey
-) -> Instruction {
-    let extra_account_metas_pubkey = get_extra_account_metas_address(mint);
-    let account_metas = vec![
-        AccountMeta::new(extra_account_metas_pubkey, false),
-        AccountMeta::new_readonly(*mint, false),
-        AccountMeta::new_readonly(*mint_authority, true),
-        AccountMeta::new_readonly(system_program::id(), false),
-    ];
-    let data = CivicTransferHookInstruction::InitializeExtraAccountMetas {
-        gatekeeper_network: *gatekeeper_network
-    }.pack();
-    Instruction::new_with_bytes(
-        civic_transfer_hook::id(),
-        &data,
-        account_metas,
-    )
+/// The config a mint gets when it is initialized or migrated with a single gatekeeper network
+fn initial_mint_config(mint: &Pubkey, authority: &Pubkey, gatekeeper_network: &Pubkey) -> MintConfig {
+    let (_, bump_seed) = get_mint_config_address_and_bump_seed(mint, &civic_transfer_hook::id());
+    MintConfig::new(*mint, bump_seed, *authority, TransferPolicy {
+        gatekeeper_networks: vec![*gatekeeper_network],
+        ..TransferPolicy::default()
+    })
+}
+
+/// Returns the (extra account metas, mint config) account sizes for a config
+fn account_sizes(config: &MintConfig) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let (extra_account_metas, _) = build_extra_account_metas(config)?;
+    Ok((
+        ExtraAccountMetaList::size_of(extra_account_metas.len())?,
+        config.packed_len()?,
+    ))
 }
 
 async fn process_initialize_extra_account_metas(
@@ -72,22 +73,106 @@ async fn process_initialize_extra_account_metas(
     mint: &Pubkey,
     gatekeeper_network: &Pubkey
 ) -> Result<Signature, Box<dyn std::error::Error>> {
-    let rent_lamports = rpc_client.get_minimum_balance_for_rent_exemption(121 /* TODO derive dynamically */).await?;
-    let mut transaction = Transaction::new_unsigned(Message::new(
+    let config = initial_mint_config(mint, &signer.pubkey(), gatekeeper_network);
+    let (extra_account_metas_size, config_size) = account_sizes(&config)?;
+    let extra_account_metas_rent = rpc_client.get_minimum_balance_for_rent_exemption(extra_account_metas_size).await?;
+    let config_rent = rpc_client.get_minimum_balance_for_rent_exemption(config_size).await?;
+    let extra_account_metas_address = get_extra_account_metas_address(mint);
+    let transaction = Transaction::new_unsigned(Message::new(
         &[
             system_instruction::transfer(
                 &signer.pubkey(),
-                &get_extra_account_metas_address(mint),
-                rent_lamports,
+                &extra_account_metas_address,
+                extra_account_metas_rent,
+            ),
+            system_instruction::transfer(
+                &signer.pubkey(),
+                &get_mint_config_address(mint, &civic_transfer_hook::id()),
+                config_rent,
+            ),
+            initialize_extra_account_metas(
+                &civic_transfer_hook::id(),
+                &extra_account_metas_address,
+                mint,
+                &signer.pubkey(),
+                gatekeeper_network,
             ),
-            initialize_extra_account_meta(
-            mint,
-            &signer.pubkey(),
-            gatekeeper_network
-        )],
+        ],
         Some(&signer.pubkey()),
     ));
 
+    send_transaction(rpc_client, signer, transaction).await
+}
+
+async fn process_migrate_extra_account_metas(
+    rpc_client: &RpcClient,
+    signer: &dyn Signer,
+    mint: &Pubkey,
+) -> Result<Signature, Box<dyn std::error::Error>> {
+    let extra_account_metas_address = get_extra_account_metas_address(mint);
+    let extra_account_metas_account = rpc_client.get_account(&extra_account_metas_address).await?;
+    let config_address = get_mint_config_address(mint, &civic_transfer_hook::id());
+
+    let mut instructions = vec![];
+    if rpc_client.get_account(&config_address).await.is_err() {
+        // The original layout stores the gatekeeper network as its first extra account:
+        // a TLV header (8-byte discriminator, 4-byte length), a 4-byte count, then a 1-byte discriminator
+        let gatekeeper_network = extra_account_metas_account
+            .data
+            .get(17..49)
+            .map(Pubkey::try_from)
+            .ok_or("extra account metas are not initialized")??;
+        println!("Migrating gatekeeper network {}", gatekeeper_network);
+
+        let config = initial_mint_config(mint, &signer.pubkey(), &gatekeeper_network);
+        let (extra_account_metas_size, config_size) = account_sizes(&config)?;
+        let config_rent = rpc_client.get_minimum_balance_for_rent_exemption(config_size).await?;
+        instructions.push(system_instruction::transfer(&signer.pubkey(), &config_address, config_rent));
+
+        let extra_account_metas_rent = rpc_client.get_minimum_balance_for_rent_exemption(extra_account_metas_size).await?;
+        if extra_account_metas_rent > extra_account_metas_account.lamports {
+            instructions.push(system_instruction::transfer(
+                &signer.pubkey(),
+                &extra_account_metas_address,
+                extra_account_metas_rent - extra_account_metas_account.lamports,
+            ));
+        }
+    }
+    instructions.push(migrate_extra_account_metas(
+        &civic_transfer_hook::id(),
+        &extra_account_metas_address,
+        mint,
+        &signer.pubkey(),
+        &[],
+    ));
+
+    let transaction = Transaction::new_unsigned(Message::new(&instructions, Some(&signer.pubkey())));
+    send_transaction(rpc_client, signer, transaction).await
+}
+
+async fn process_show_config_history(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let history_address = get_config_history_address(mint, &civic_transfer_hook::id());
+    let history_account = rpc_client.get_account(&history_address).await?;
+    let history = ConfigHistory::unpack(&history_account.data)?;
+    let entries = ConfigHistory::entries(&history_account.data)?;
+    println!("{} changes recorded, showing the last {}", history.total_entries, entries.len());
+    for entry in entries {
+        println!(
+            "slot {} (unix time {}): {:?} by {}, settings {} -> {}",
+            entry.slot, entry.unix_timestamp, entry.kind, entry.actor, entry.old_hash, entry.new_hash
+        );
+    }
+    Ok(())
+}
+
+async fn send_transaction(
+    rpc_client: &RpcClient,
+    signer: &dyn Signer,
+    mut transaction: Transaction,
+) -> Result<Signature, Box<dyn std::error::Error>> {
     let blockhash = rpc_client
         .get_latest_blockhash()
         .await
@@ -176,6 +261,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .help("The gatekeeper network address to associate with the token"),
             ),
         )
+        .subcommand(
+            Command::new("migrate").about("Migrate a permissioned token to the current extra account metas layout").arg(
+                Arg::new("mint")
+                    .validator(|s| is_valid_pubkey(s))
+                    .value_name("MINT")
+                    .takes_value(true)
+                    .index(1)
+                    .help("The token mint"),
+            ),
+        )
+        .subcommand(
+            Command::new("history").about("Show the recorded config changes of a permissioned token").arg(
+                Arg::new("mint")
+                    .validator(|s| is_valid_pubkey(s))
+                    .value_name("MINT")
+                    .takes_value(true)
+                    .index(1)
+                    .help("The token mint"),
+            ),
+        )
         .get_matches();
 
     let (command, matches) = app_matches.subcommand().unwrap();
@@ -240,6 +345,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 });
             println!("Signature: {signature}");
         }
+        ("migrate", arg_matches) => {
+            let mint =
+                pubkey_of(arg_matches, "mint").unwrap_or_else(|| config.default_signer.pubkey());
+            println!("Migrating the extra account metas of {}", mint);
+            let signature = process_migrate_extra_account_metas(&rpc_client, config.default_signer.as_ref(), &mint)
+                .await
+                .unwrap_or_else(|err| {
+                    eprintln!("error: send transaction: {err}");
+                    exit(1);
+                });
+            println!("Signature: {signature}");
+        }
+        ("history", arg_matches) => {
+            let mint =
+                pubkey_of(arg_matches, "mint").unwrap_or_else(|| config.default_signer.pubkey());
+            process_show_config_history(&rpc_client, &mint)
+                .await
+                .unwrap_or_else(|err| {
+                    eprintln!("error: {err}");
+                    exit(1);
+                });
+        }
         _ => unreachable!(),
     };
 
@@ -0,0 +1,246 @@
+//! Off-chain transfer approvals
+//!
+//! The approver of a mint can allow a transfer that would otherwise fail by
+//! signing a [TransferApproval](struct.TransferApproval.html) off-chain.
+//! The signature is verified by an Ed25519 program instruction in the same
+//! transaction, which the hook finds through the instructions sysvar.
+//!
+//! An approval carries the approval nonce of the source state of its source
+//! account, which the hook increments when the approval is used, so each
+//! approval allows a single transfer. Approvals only stand in for the pass
+//! checks of the mint.
+
+use {
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{
+        account_info::AccountInfo, clock::UnixTimestamp, ed25519_program,
+        program_error::ProgramError, pubkey::Pubkey,
+        sysvar::instructions::load_instruction_at_checked,
+    },
+};
+
+/// Size of the header of Ed25519 program instruction data:
+/// the number of signatures and a padding byte
+const ED25519_HEADER_LEN: usize = 2;
+/// Size of the offsets describing one signature in Ed25519 program instruction data
+const ED25519_OFFSETS_LEN: usize = 14;
+/// Instruction index used by Ed25519 offsets to refer to their own instruction
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+/// Size of an Ed25519 public key
+const PUBKEY_LEN: usize = 32;
+
+/// A transfer approved by the approver of a mint. The borsh encoding is
+/// the message signed by the approver.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct TransferApproval {
+    /// The mint
+    pub mint: Pubkey,
+    /// The source token account
+    pub source: Pubkey,
+    /// The destination token account
+    pub destination: Pubkey,
+    /// The exact amount approved
+    pub amount: u64,
+    /// The time after which the approval can no longer be used
+    pub expiry: UnixTimestamp,
+    /// The approval nonce of the source state of `source` when the approval is used
+    pub nonce: u64,
+}
+
+impl TransferApproval {
+    /// The message to sign
+    pub fn message(&self) -> Vec<u8> {
+        self.try_to_vec().unwrap()
+    }
+
+    /// Whether the approval is for this transfer out of a source whose approval
+    /// nonce is `nonce`, and has not expired at `now`
+    pub fn covers(
+        &self,
+        mint: &Pubkey,
+        source: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+        nonce: u64,
+        now: UnixTimestamp,
+    ) -> bool {
+        self.mint == *mint
+            && self.source == *source
+            && self.destination == *destination
+            && self.amount == amount
+            && self.nonce == nonce
+            && now <= self.expiry
+    }
+}
+
+/// Returns the approvals signed by `approver` in the Ed25519 program
+/// instructions of the current transaction
+pub fn find_approvals(
+    instructions_sysvar_info: &AccountInfo,
+    approver: &Pubkey,
+) -> Result<Vec<TransferApproval>, ProgramError> {
+    let mut approvals = vec![];
+    let mut index = 0;
+    while let Ok(instruction) = load_instruction_at_checked(index, instructions_sysvar_info) {
+        index += 1;
+        if instruction.program_id != ed25519_program::id() {
+            continue;
+        }
+        approvals.extend(
+            signed_messages(&instruction.data)
+                .into_iter()
+                .filter(|(signer, _)| signer == approver)
+                .filter_map(|(_, message)| TransferApproval::try_from_slice(message).ok()),
+        );
+    }
+    Ok(approvals)
+}
+
+/// Returns the (public key, message) pairs verified by an Ed25519 program instruction.
+///
+/// Only signatures whose key and message are stored in the instruction itself
+/// are returned. The Ed25519 program has already failed the transaction if
+/// any signature is invalid.
+fn signed_messages(data: &[u8]) -> Vec<(Pubkey, &[u8])> {
+    let read_u16 = |offset: usize| {
+        data.get(offset..offset + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let num_signatures = match data.first() {
+        Some(num_signatures) => *num_signatures as usize,
+        None => return vec![],
+    };
+
+    let mut messages = Vec::with_capacity(num_signatures);
+    for i in 0..num_signatures {
+        let start = ED25519_HEADER_LEN + i * ED25519_OFFSETS_LEN;
+        let (
+            Some(signature_instruction_index),
+            Some(public_key_offset),
+            Some(public_key_instruction_index),
+            Some(message_offset),
+            Some(message_size),
+            Some(message_instruction_index),
+        ) = (
+            read_u16(start + 2),
+            read_u16(start + 4),
+            read_u16(start + 6),
+            read_u16(start + 8),
+            read_u16(start + 10),
+            read_u16(start + 12),
+        )
+        else {
+            break;
+        };
+        if [
+            signature_instruction_index,
+            public_key_instruction_index,
+            message_instruction_index,
+        ]
+        .iter()
+        .any(|index| *index != CURRENT_INSTRUCTION)
+        {
+            continue;
+        }
+        let public_key_offset = public_key_offset as usize;
+        let message_offset = message_offset as usize;
+        let (Some(public_key), Some(message)) = (
+            data.get(public_key_offset..public_key_offset + PUBKEY_LEN),
+            data.get(message_offset..message_offset + message_size as usize),
+        ) else {
+            continue;
+        };
+        messages.push((Pubkey::try_from(public_key).unwrap(), message));
+    }
+    messages
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Lays out Ed25519 instruction data like `new_ed25519_instruction` in the sdk,
+    /// with a zeroed signature since the hook does not verify it itself
+    fn ed25519_instruction_data(
+        public_key: &Pubkey,
+        message: &[u8],
+        instruction_index: u16,
+    ) -> Vec<u8> {
+        let public_key_offset = ED25519_HEADER_LEN + ED25519_OFFSETS_LEN;
+        let signature_offset = public_key_offset + PUBKEY_LEN;
+        let message_offset = signature_offset + 64;
+        let mut data = vec![1, 0];
+        for value in [
+            signature_offset as u16,
+            instruction_index,
+            public_key_offset as u16,
+            instruction_index,
+            message_offset as u16,
+            message.len() as u16,
+            instruction_index,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(public_key.as_ref());
+        data.extend_from_slice(&[0; 64]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn reads_signed_approval() {
+        let approver = Pubkey::new_unique();
+        let approval = TransferApproval {
+            mint: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            amount: 500,
+            expiry: 1_000,
+            nonce: 3,
+        };
+        let message = approval.message();
+        let data = ed25519_instruction_data(&approver, &message, CURRENT_INSTRUCTION);
+        assert_eq!(signed_messages(&data), vec![(approver, message.as_slice())]);
+
+        // keys and messages stored in other instructions are not trusted
+        let data = ed25519_instruction_data(&approver, &message, 0);
+        assert!(signed_messages(&data).is_empty());
+        assert!(signed_messages(&[]).is_empty());
+        assert!(signed_messages(&[1, 0, 4]).is_empty());
+    }
+
+    #[test]
+    fn approval_coverage() {
+        let approval = TransferApproval {
+            mint: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            amount: 500,
+            expiry: 1_000,
+            nonce: 3,
+        };
+        let covers = |amount, nonce, now| {
+            approval.covers(
+                &approval.mint,
+                &approval.source,
+                &approval.destination,
+                amount,
+                nonce,
+                now,
+            )
+        };
+        assert!(covers(500, 3, 1_000));
+        assert!(!covers(499, 3, 1_000));
+        assert!(!covers(500, 3, 1_001));
+        // once used, the nonce of the source has moved on
+        assert!(!covers(500, 4, 1_000));
+        assert!(!approval.covers(
+            &approval.mint,
+            &approval.destination,
+            &approval.source,
+            500,
+            3,
+            1_000
+        ));
+    }
+}
@@ -0,0 +1,69 @@
+//! CPI helpers for programs integrating tokens that use this hook
+
+use {
+    crate::instruction::{check_eligibility as check_eligibility_instruction, EligibilityVerdict},
+    borsh::BorshDeserialize,
+    solana_program::{
+        account_info::AccountInfo,
+        program::{get_return_data, invoke},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+    spl_tlv_account_resolution::state::ExtraAccountMetaList,
+    spl_transfer_hook_interface::{
+        error::TransferHookError, get_extra_account_metas_address, instruction::ExecuteInstruction,
+    },
+};
+
+/// Asks the hook whether a transfer would be allowed, without transferring.
+///
+/// As with `spl_transfer_hook_interface::onchain::invoke_execute`, the extra
+/// accounts of the mint are looked up in `additional_accounts`, which must also
+/// contain the validation account.
+pub fn check_eligibility<'a>(
+    program_id: &Pubkey,
+    source_info: AccountInfo<'a>,
+    mint_info: AccountInfo<'a>,
+    destination_info: AccountInfo<'a>,
+    authority_info: AccountInfo<'a>,
+    additional_accounts: &[AccountInfo<'a>],
+    amount: u64,
+) -> Result<EligibilityVerdict, ProgramError> {
+    let validation_pubkey = get_extra_account_metas_address(mint_info.key, program_id);
+    let validation_info = additional_accounts
+        .iter()
+        .find(|&x| *x.key == validation_pubkey)
+        .ok_or(TransferHookError::IncorrectAccount)?;
+    let mut cpi_instruction = check_eligibility_instruction(
+        program_id,
+        source_info.key,
+        mint_info.key,
+        destination_info.key,
+        authority_info.key,
+        &validation_pubkey,
+        amount,
+    );
+
+    let mut cpi_account_infos = vec![
+        source_info,
+        mint_info,
+        destination_info,
+        authority_info,
+        validation_info.clone(),
+    ];
+    ExtraAccountMetaList::add_to_cpi_instruction::<ExecuteInstruction>(
+        &mut cpi_instruction,
+        &mut cpi_account_infos,
+        &validation_info.try_borrow_data()?,
+        additional_accounts,
+    )?;
+    invoke(&cpi_instruction, &cpi_account_infos)?;
+
+    match get_return_data() {
+        Some((returning_program_id, data)) if returning_program_id == *program_id => {
+            EligibilityVerdict::try_from_slice(&data)
+                .map_err(|_| ProgramError::InvalidInstructionData)
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
@@ -1,10 +1,15 @@
 //! Program entrypoint
 
 use {
-    crate::processor,
+    crate::{error::CivicTransferHookError, processor},
+    num_traits::FromPrimitive,
     solana_program::{
-        account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
-        program_error::PrintProgramError, pubkey::Pubkey,
+        account_info::AccountInfo,
+        entrypoint,
+        entrypoint::ProgramResult,
+        msg,
+        program_error::{PrintProgramError, ProgramError},
+        pubkey::Pubkey,
     },
     spl_transfer_hook_interface::error::TransferHookError,
 };
@@ -17,8 +22,24 @@ fn process_instruction(
 ) -> ProgramResult {
     if let Err(error) = processor::process(program_id, accounts, instruction_data) {
         // catch the error so we can print it
-        error.print::<TransferHookError>();
+        print_error(&error);
         return Err(error);
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Prints an error, decoding custom codes with the error type that defines them.
+/// Codes defined by neither this program nor the interface, e.g. those of the
+/// gateway program, are printed raw.
+fn print_error(error: &ProgramError) {
+    match error {
+        ProgramError::Custom(code) if CivicTransferHookError::from_u32(*code).is_some() => {
+            error.print::<CivicTransferHookError>()
+        }
+        ProgramError::Custom(code) if TransferHookError::from_u32(*code).is_some() => {
+            error.print::<TransferHookError>()
+        }
+        ProgramError::Custom(code) => msg!("Error: custom program error {:#x}", code),
+        _ => error.print::<CivicTransferHookError>(),
+    }
+}
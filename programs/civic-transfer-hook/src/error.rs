@@ -0,0 +1,239 @@
+//! Error types
+
+use {
+    num_derive::FromPrimitive,
+    num_traits::FromPrimitive as FromPrimitiveTrait,
+    solana_program::{
+        decode_error::DecodeError,
+        msg,
+        program_error::{PrintProgramError, ProgramError},
+    },
+    thiserror::Error,
+};
+
+/// Errors that may be returned by the Civic transfer hook program.
+///
+/// Codes start at 0x4354_0000 ("CT") so that they do not overlap with
+/// errors returned by the gateway program or the transfer hook interface.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum CivicTransferHookError {
+    /// The mint config account is not the expected PDA, or does not belong to the mint
+    #[error("Incorrect mint config account")]
+    IncorrectMintConfig = 0x4354_0000,
+    /// The mint config was written by a newer version of the program
+    #[error("Unsupported mint config version")]
+    UnsupportedMintConfigVersion,
+    /// The list of gatekeeper networks is empty or too long
+    #[error("Invalid gatekeeper networks")]
+    InvalidGatekeeperNetworks,
+    /// The extra account metas do not match a layout known to this program
+    #[error("Unsupported extra account metas layout")]
+    UnsupportedExtraAccountMetas,
+    /// The signer does not hold the role required by the instruction
+    #[error("Incorrect config authority")]
+    IncorrectConfigAuthority,
+    /// No gateway token exists for the owner in any accepted network
+    #[error("Pass missing")]
+    PassMissing,
+    /// The gateway token has been revoked
+    #[error("Pass revoked")]
+    PassRevoked,
+    /// The gateway token is frozen
+    #[error("Pass frozen")]
+    PassFrozen,
+    /// The gateway token has expired
+    #[error("Pass expired")]
+    PassExpired,
+    /// The mint config does not allow transfers by the permanent delegate
+    #[error("Permanent delegate transfer rejected")]
+    PermanentDelegateTransferRejected,
+    /// The transfer permit account is not the expected PDA
+    #[error("Incorrect transfer permit")]
+    IncorrectTransferPermit,
+    /// The permit expiry is in the past
+    #[error("Transfer permit expired")]
+    TransferPermitExpired,
+    /// The source state account is not the expected PDA
+    #[error("Incorrect source state")]
+    IncorrectSourceState,
+    /// The mint has a transfer cooldown, but the source state has not been initialized
+    #[error("Source state not initialized")]
+    SourceStateNotInitialized,
+    /// The source account sent a transfer too recently
+    #[error("Transfer cooldown active")]
+    TransferCooldownActive,
+    /// The transfer would add a holder beyond the holder cap of the mint
+    #[error("Holder cap reached")]
+    HolderCapReached,
+    /// The transfer is above the travel rule threshold, but the mint has no travel rule buffer
+    #[error("Travel rule buffer missing")]
+    TravelRuleBufferMissing,
+    /// The account is not a travel rule buffer of the mint, or is still in use
+    #[error("Incorrect travel rule buffer")]
+    IncorrectTravelRuleBuffer,
+    /// The transfer matrix refers to unknown networks, or is too long
+    #[error("Invalid transfer matrix")]
+    InvalidTransferMatrix,
+    /// The transfer matrix does not allow transfers between the networks of the passes presented
+    #[error("Transfer not allowed between these jurisdictions")]
+    TransferRouteNotAllowed,
+    /// The list of issuer accounts is too long
+    #[error("Too many issuer accounts")]
+    TooManyIssuerAccounts,
+    /// The token is soulbound, and the source is not an issuer account
+    #[error("Source is not an issuer account")]
+    SourceNotIssuer,
+    /// The destination is not the associated token account of its owner
+    #[error("Destination is not an associated token account")]
+    DestinationNotAssociatedTokenAccount,
+    /// Fewer accounts were passed than listed in the extra account metas
+    #[error("Not enough extra accounts")]
+    NotEnoughExtraAccounts,
+    /// The mint or a token account is not owned by the Token-2022 program
+    #[error("Incorrect token program")]
+    IncorrectTokenProgram,
+    /// A token account does not belong to the mint being transferred
+    #[error("Token account mint mismatch")]
+    TokenAccountMintMismatch,
+    /// A gatekeeper network account does not match the stored configuration
+    #[error("Gatekeeper network mismatch")]
+    GatekeeperNetworkMismatch,
+    /// The multisig threshold or number of signers is out of range
+    #[error("Invalid multisig")]
+    InvalidMultisig,
+    /// Fewer multisig signers than required signed the instruction
+    #[error("Not enough multisig signers")]
+    NotEnoughMultisigSigners,
+    /// The mint config is timelocked, so the update must be queued
+    #[error("Config update must be queued")]
+    TimelockRequired,
+    /// The queued config update is not executable yet
+    #[error("Timelock not expired")]
+    TimelockNotExpired,
+    /// The pending config update account is not the expected PDA
+    #[error("Incorrect pending config update")]
+    IncorrectPendingConfigUpdate,
+    /// Transfers of the mint are paused
+    #[error("Transfers paused")]
+    TransfersPaused,
+    /// The allow list or the deny list is too long
+    #[error("List too long")]
+    ListTooLong,
+    /// The source or destination is on the deny list of the mint
+    #[error("Account denied")]
+    AccountDenied,
+    /// The config history account is not the PDA for the mint
+    #[error("Incorrect config history")]
+    IncorrectConfigHistory,
+    /// The hook authority account is not the PDA for the mint
+    #[error("Incorrect hook authority")]
+    IncorrectHookAuthority,
+    /// The hook authority is not the freeze authority of the mint
+    #[error("Hook authority is not the freeze authority")]
+    HookAuthorityNotFreezeAuthority,
+    /// The token account cannot be frozen, as it holds a valid pass or does not need one
+    #[error("Pass still valid")]
+    PassStillValid,
+    /// A gateway token account is not the one of the token account in the expected network
+    #[error("Incorrect gateway token")]
+    IncorrectGatewayToken,
+    /// The permanent delegate may only move tokens to the quarantine account
+    #[error("Recovery not allowed")]
+    RecoveryNotAllowed,
+    /// No revocation has been recorded for the source token account
+    #[error("Revocation not recorded")]
+    RevocationNotRecorded,
+    /// The pass of the source has not been revoked for the recovery period yet
+    #[error("Recovery period not elapsed")]
+    RecoveryPeriodNotElapsed,
+    /// The revocation record account is not the PDA for the token account
+    #[error("Incorrect revocation record")]
+    IncorrectRevocationRecord,
+    /// The best pass presented for the token account is not revoked
+    #[error("Pass not revoked")]
+    PassNotRevoked,
+    /// The revocation record cannot be closed while the token account holds
+    /// tokens without a valid pass
+    #[error("Revocation still in effect")]
+    RevocationStillInEffect,
+    /// The mint does not issue token passes, as it has no base pass network
+    #[error("Base pass network not set")]
+    BasePassNetworkNotSet,
+    /// The gatekeeper authority account is not the PDA for the network
+    #[error("Incorrect gatekeeper authority")]
+    IncorrectGatekeeperAuthority,
+    /// The owner of the token account does not hold an active base pass
+    #[error("Base pass not active")]
+    BasePassNotActive,
+    /// The signer is not the owner of the token account
+    #[error("Token account owner mismatch")]
+    TokenAccountOwnerMismatch,
+    /// The update changes more than the transfer policy
+    #[error("Not a policy update")]
+    NotAPolicyUpdate,
+    /// The shared policy account is not the PDA for its creator and id,
+    /// or not the shared policy the mint is linked to
+    #[error("Incorrect shared policy")]
+    IncorrectSharedPolicy,
+    /// The policy of the mint is managed through its shared policy
+    #[error("Mint linked to a shared policy")]
+    MintLinkedToSharedPolicy,
+    /// The mint is not linked to a shared policy
+    #[error("Mint not linked to a shared policy")]
+    MintNotLinkedToSharedPolicy,
+    /// The shared policy cannot be closed, nor withdraw its approval from a
+    /// mint, while mints are linked to it
+    #[error("Shared policy in use")]
+    SharedPolicyInUse,
+    /// The rules refer to missing nodes, lists or networks, are too large,
+    /// or use an unsupported version
+    #[error("Invalid rules")]
+    InvalidRules,
+    /// The transfer does not satisfy the rules of the policy
+    #[error("Rules not satisfied")]
+    RulesNotSatisfied,
+    /// The transfer is above the travel rule threshold, but the travel rule
+    /// buffer of the mint is full and must be rotated
+    #[error("Travel rule buffer full")]
+    TravelRuleBufferFull,
+    /// The token account is the quarantine account, or its tokens await recovery
+    /// by the permanent delegate, which freezing would block
+    #[error("Account awaiting recovery")]
+    AccountAwaitingRecovery,
+    /// The shared policy has a shorter timelock than the mint, so its updates
+    /// would bypass the timelock of the mint
+    #[error("Shared policy timelock too short")]
+    SharedPolicyTimelockTooShort,
+    /// The policy of the mint has rules, under which a holder without a pass
+    /// may still receive tokens, so holders are not frozen for lacking one
+    #[error("Freeze not supported with rules")]
+    FreezeNotSupportedWithRules,
+    /// The authority of the shared policy has not approved the mint to link to it
+    #[error("Mint not approved for shared policy")]
+    MintNotApprovedForSharedPolicy,
+    /// The holder count snapshot is from a future slot, or older than the
+    /// holder count checkpoints kept in the mint config
+    #[error("Invalid holder count snapshot")]
+    InvalidHolderCountSnapshot,
+}
+
+impl From<CivicTransferHookError> for ProgramError {
+    fn from(e: CivicTransferHookError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for CivicTransferHookError {
+    fn type_of() -> &'static str {
+        "CivicTransferHookError"
+    }
+}
+
+impl PrintProgramError for CivicTransferHookError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitiveTrait,
+    {
+        msg!("Error: {}", self);
+    }
+}
@@ -0,0 +1,220 @@
+//! Structured events emitted by the program
+//!
+//! Events are borsh-encoded and logged with `sol_log_data`, so they show up
+//! base64-encoded as `Program data:` lines in the transaction logs.
+
+use {
+    crate::state::{ConfigUpdate, Role},
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{
+        clock::UnixTimestamp,
+        log::sol_log_data,
+        pubkey::Pubkey,
+    },
+};
+
+/// An event emitted by the program
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A transfer failed the checks of its mint, but was allowed because
+    /// the mint is in monitor mode
+    TransferViolation {
+        /// The mint
+        mint: Pubkey,
+        /// The source token account
+        source: Pubkey,
+        /// The destination token account
+        destination: Pubkey,
+        /// The transfer authority
+        authority: Pubkey,
+        /// The amount transferred
+        amount: u64,
+        /// The error the transfer would have failed with, as a `u64` program error code
+        error: u64,
+    },
+    /// An administrative instruction was approved
+    AdminAction {
+        /// The mint
+        mint: Pubkey,
+        /// The key holding the role, a wallet or a multisig
+        actor: Pubkey,
+        /// The role the action required
+        role: Role,
+        /// What was done
+        action: AdminAction,
+    },
+    /// A token account was thawed by the hook authority on presenting a valid pass
+    AccountThawed {
+        /// The mint
+        mint: Pubkey,
+        /// The thawed token account
+        account: Pubkey,
+        /// The gateway token presented
+        gateway_token: Pubkey,
+    },
+    /// A token account without a valid pass was frozen by the hook authority
+    AccountFrozen {
+        /// The mint
+        mint: Pubkey,
+        /// The frozen token account
+        account: Pubkey,
+    },
+    /// The permanent delegate moved tokens out of a token account whose pass was revoked
+    Recovered {
+        /// The mint
+        mint: Pubkey,
+        /// The token account the tokens were recovered from
+        source: Pubkey,
+        /// The quarantine token account
+        quarantine: Pubkey,
+        /// The permanent delegate
+        authority: Pubkey,
+        /// The amount recovered
+        amount: u64,
+        /// The revoked pass of the source, or the default pubkey if it had none
+        source_gateway_token: Pubkey,
+        /// The time the revocation was recorded
+        revoked_since: UnixTimestamp,
+    },
+    /// The program issued a token pass to the holder of a base pass
+    TokenPassIssued {
+        /// The mint
+        mint: Pubkey,
+        /// The token account the pass was issued to
+        account: Pubkey,
+        /// The owner of the token account
+        owner: Pubkey,
+        /// The base pass presented by the owner
+        base_pass: Pubkey,
+        /// The issued gateway token
+        gateway_token: Pubkey,
+        /// The network of the issued gateway token
+        network: Pubkey,
+    },
+    /// A shared policy was created, updated or closed by its authority
+    SharedPolicyAction {
+        /// The shared policy
+        policy: Pubkey,
+        /// The authority of the shared policy, a wallet or a multisig
+        actor: Pubkey,
+        /// What was done
+        action: SharedPolicyAction,
+    },
+}
+
+/// An administrative action, as reported by [Event::AdminAction](enum.Event.html)
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum AdminAction {
+    /// The extra account metas were migrated or resynchronized
+    MigrateExtraAccountMetas,
+    /// A config update was applied
+    UpdateConfig(ConfigUpdate),
+    /// A config update was queued
+    QueueConfigUpdate {
+        /// The queued update
+        update: ConfigUpdate,
+        /// The time from which it can be applied
+        executable_at: UnixTimestamp,
+    },
+    /// A queued config update was applied
+    ExecuteConfigUpdate(ConfigUpdate),
+    /// A queued config update was cancelled
+    CancelConfigUpdate(ConfigUpdate),
+    /// A transfer permit was created or replaced
+    CreateTransferPermit {
+        /// The source token account
+        source: Pubkey,
+        /// The destination token account
+        destination: Pubkey,
+        /// The permitted amount
+        amount: u64,
+        /// The expiry of the permit
+        expiry: UnixTimestamp,
+    },
+    /// A transfer permit was closed
+    CloseTransferPermit {
+        /// The permit account
+        permit: Pubkey,
+    },
+    /// Travel rule records now go to a new buffer
+    RotateTravelRuleBuffer {
+        /// The new buffer
+        buffer: Pubkey,
+    },
+    /// A travel rule buffer was closed
+    CloseTravelRuleBuffer {
+        /// The closed buffer
+        buffer: Pubkey,
+    },
+    /// The config history of the mint was started
+    InitializeConfigHistory,
+    /// A token account was thawed without presenting a pass
+    ThawAccount {
+        /// The thawed token account
+        account: Pubkey,
+    },
+}
+
+/// An action on a shared policy, as reported by [Event::SharedPolicyAction](enum.Event.html)
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum SharedPolicyAction {
+    /// The shared policy was created
+    Create,
+    /// An update was applied to the shared policy and all linked mints
+    Update(ConfigUpdate),
+    /// The shared policy was closed
+    Close,
+    /// An update to the shared policy was queued
+    QueueUpdate {
+        /// The queued update
+        update: ConfigUpdate,
+        /// The time from which it can be applied
+        executable_at: UnixTimestamp,
+    },
+    /// A queued update was applied to the shared policy and all linked mints
+    ExecuteUpdate(ConfigUpdate),
+    /// A queued update to the shared policy was cancelled
+    CancelUpdate(ConfigUpdate),
+    /// The history of the shared policy was started
+    InitializeHistory,
+}
+
+impl Event {
+    /// Logs the event
+    pub fn emit(&self) {
+        if let Ok(data) = self.try_to_vec() {
+            sol_log_data(&[&data]);
+        }
+    }
+
+    /// Decodes an event from the data of a `Program data:` log line
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        Self::try_from_slice(data).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn event_round_trip() {
+        let event = Event::TransferViolation {
+            mint: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            amount: 42,
+            error: 6,
+        };
+        assert_eq!(Event::decode(&event.try_to_vec().unwrap()), Some(event));
+
+        let event = Event::AdminAction {
+            mint: Pubkey::new_unique(),
+            actor: Pubkey::new_unique(),
+            role: Role::Guardian,
+            action: AdminAction::UpdateConfig(ConfigUpdate::SetPaused(true)),
+        };
+        assert_eq!(Event::decode(&event.try_to_vec().unwrap()), Some(event));
+    }
+}
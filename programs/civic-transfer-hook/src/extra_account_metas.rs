@@ -0,0 +1,488 @@
+//! Layout of the extra accounts required by the `Execute` instruction
+//!
+//! The extra account metas of a mint are derived from its config, so that the
+//! hook only asks wallets for the accounts that the policy actually needs.
+//! The same layout is used by the processor to locate those accounts.
+
+use {
+    crate::{
+        processor::{GATEWAY_PROGRAM_ID, GATEWAY_TOKEN_ADDRESS_SEED},
+        rules::RuleSet,
+        state::{
+            MintConfig, PermanentDelegatePolicy, FLAG_TRANSFER_PERMITS, FLAG_VERIFY_DELEGATE,
+            MINT_CONFIG_SEED, REVOCATION_RECORD_SEED, SOURCE_STATE_SEED, TRANSFER_PERMIT_SEED,
+        },
+    },
+    solana_program::{program_error::ProgramError, pubkey::Pubkey, sysvar},
+    spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed},
+};
+
+/// Index of the source token account in the `Execute` instruction
+pub const SOURCE_INDEX: u8 = 0;
+/// Index of the mint in the `Execute` instruction
+pub const MINT_INDEX: u8 = 1;
+/// Index of the destination token account in the `Execute` instruction
+pub const DESTINATION_INDEX: u8 = 2;
+/// Index of the transfer authority in the `Execute` instruction
+pub const AUTHORITY_INDEX: u8 = 3;
+/// Index of the first extra account in the `Execute` instruction
+pub const FIRST_EXTRA_ACCOUNT_INDEX: u8 = 5;
+/// Index of the shared policy in the `Execute` instruction, right after the
+/// mint config, for mints linked to a shared policy
+pub const SHARED_POLICY_INDEX: u8 = FIRST_EXTRA_ACCOUNT_INDEX + 1;
+
+/// Number of extra accounts in the layout used before mint configs existed:
+/// the gatekeeper network, the gateway program and the recipient's gateway token.
+pub const LEGACY_EXTRA_ACCOUNT_METAS_LEN: usize = 3;
+
+/// The accounts used to check a pass in one gatekeeper network
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkAccounts {
+    /// Index of the gatekeeper network account
+    pub network: u8,
+    /// Index of the destination's gateway token in this network
+    pub destination_gateway_token: u8,
+    /// Index of the transfer authority's gateway token in this network,
+    /// present if delegates must hold a pass
+    pub authority_gateway_token: Option<u8>,
+    /// Index of the source's gateway token in this network,
+    /// present if the source pass is recorded or checked, including by the rules
+    pub source_gateway_token: Option<u8>,
+}
+
+/// Positions of the extra accounts in the `Execute` instruction for a given config
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecuteAccountsLayout {
+    /// Index of the mint config account, writable if the policy has a holder cap
+    pub config: u8,
+    /// Index of the gateway program
+    pub gateway_program: u8,
+    /// One entry per gatekeeper network, in config order
+    pub networks: Vec<NetworkAccounts>,
+    /// Index of the transfer permit for the source and destination, if permits are enabled
+    pub transfer_permit: Option<u8>,
+    /// Index of the instructions sysvar, if the config has an approver
+    pub instructions_sysvar: Option<u8>,
+    /// Index of the source state, if the mint has a transfer cooldown or an approver
+    pub source_state: Option<u8>,
+    /// Index of the travel rule buffer, if the policy has a travel rule threshold
+    /// and the mint has a buffer
+    pub travel_rule_buffer: Option<u8>,
+    /// Index of the revocation record of the source, if the permanent delegate
+    /// may only recover tokens
+    pub revocation_record: Option<u8>,
+}
+
+/// Builds the extra account metas for a config, along with the layout
+/// the processor uses to find them.
+///
+/// For a mint linked to a shared policy, the policy of `config` must be
+/// the shared policy, as the metas follow the policy the hook enforces.
+pub fn build_extra_account_metas(
+    config: &MintConfig,
+) -> Result<(Vec<ExtraAccountMeta>, ExecuteAccountsLayout), ProgramError> {
+    let mut builder = Builder::default();
+    let is_recovery_only = matches!(
+        config.policy.permanent_delegate,
+        PermanentDelegatePolicy::RecoveryOnly { .. }
+    );
+    let uses_source_passes = config
+        .policy
+        .rules
+        .as_ref()
+        .map_or(false, RuleSet::uses_source_passes);
+
+    let config_index = builder.push(ExtraAccountMeta::new_with_seeds(
+        &[
+            Seed::Literal {
+                bytes: MINT_CONFIG_SEED.to_vec(),
+            },
+            Seed::AccountKey { index: MINT_INDEX },
+        ],
+        false,
+        // the hook keeps the holder count in the config
+        config.policy.holder_cap.is_some(),
+    )?);
+    if let Some(shared_policy) = config.shared_policy {
+        builder.push(ExtraAccountMeta::new_with_pubkey(&shared_policy, false, false)?);
+    }
+    let gateway_program = builder.push(ExtraAccountMeta::new_with_pubkey(
+        &GATEWAY_PROGRAM_ID,
+        false,
+        false,
+    )?);
+
+    let mut networks = Vec::with_capacity(config.policy.gatekeeper_networks.len());
+    for gatekeeper_network in &config.policy.gatekeeper_networks {
+        let network = builder.push(ExtraAccountMeta::new_with_pubkey(
+            gatekeeper_network,
+            false,
+            false,
+        )?);
+        let destination_gateway_token = builder.push(gateway_token_meta(
+            gateway_program,
+            DESTINATION_INDEX,
+            network,
+        )?);
+        let authority_gateway_token = if config.policy.has_flag(FLAG_VERIFY_DELEGATE) {
+            Some(builder.push(gateway_token_meta(
+                gateway_program,
+                AUTHORITY_INDEX,
+                network,
+            )?))
+        } else {
+            None
+        };
+        let source_gateway_token = if config.policy.travel_rule_threshold.is_some()
+            || !config.policy.transfer_matrix.is_empty()
+            || is_recovery_only
+            || uses_source_passes
+        {
+            Some(builder.push(gateway_token_meta(gateway_program, SOURCE_INDEX, network)?))
+        } else {
+            None
+        };
+        networks.push(NetworkAccounts {
+            network,
+            destination_gateway_token,
+            authority_gateway_token,
+            source_gateway_token,
+        });
+    }
+
+    let transfer_permit = if config.policy.has_flag(FLAG_TRANSFER_PERMITS) {
+        Some(builder.push(ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: TRANSFER_PERMIT_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: MINT_INDEX },
+                Seed::AccountKey {
+                    index: SOURCE_INDEX,
+                },
+                Seed::AccountKey {
+                    index: DESTINATION_INDEX,
+                },
+            ],
+            false,
+            true,
+        )?))
+    } else {
+        None
+    };
+
+    let instructions_sysvar = if config.policy.approver.is_some() {
+        Some(builder.push(ExtraAccountMeta::new_with_pubkey(
+            &sysvar::instructions::id(),
+            false,
+            false,
+        )?))
+    } else {
+        None
+    };
+
+    // the source state holds the cooldown, and the nonce that approvals must match
+    let source_state = if config.policy.transfer_cooldown_seconds > 0
+        || config.policy.approver.is_some()
+    {
+        Some(builder.push(ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: SOURCE_STATE_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: MINT_INDEX },
+                Seed::AccountKey {
+                    index: SOURCE_INDEX,
+                },
+            ],
+            false,
+            true,
+        )?))
+    } else {
+        None
+    };
+
+    let travel_rule_buffer = match (
+        config.policy.travel_rule_threshold,
+        config.travel_rule_buffer,
+    ) {
+        (Some(_), Some(buffer)) => Some(builder.push(ExtraAccountMeta::new_with_pubkey(
+            &buffer, false, true,
+        )?)),
+        _ => None,
+    };
+
+    let revocation_record = if is_recovery_only {
+        Some(builder.push(ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: REVOCATION_RECORD_SEED.to_vec(),
+                },
+                Seed::AccountKey { index: MINT_INDEX },
+                Seed::AccountKey {
+                    index: SOURCE_INDEX,
+                },
+            ],
+            false,
+            false,
+        )?))
+    } else {
+        None
+    };
+
+    Ok((
+        builder.metas,
+        ExecuteAccountsLayout {
+            config: config_index,
+            gateway_program,
+            networks,
+            transfer_permit,
+            instructions_sysvar,
+            source_state,
+            travel_rule_buffer,
+            revocation_record,
+        },
+    ))
+}
+
+/// Builds the extra account metas used before mint configs existed.
+/// Only kept to describe mints that have not been migrated yet.
+pub fn build_legacy_extra_account_metas(
+    gatekeeper_network: &Pubkey,
+) -> Result<Vec<ExtraAccountMeta>, ProgramError> {
+    Ok(vec![
+        ExtraAccountMeta::new_with_pubkey(gatekeeper_network, false, false)?,
+        ExtraAccountMeta::new_with_pubkey(&GATEWAY_PROGRAM_ID, false, false)?,
+        gateway_token_meta(
+            FIRST_EXTRA_ACCOUNT_INDEX + 1,
+            DESTINATION_INDEX,
+            FIRST_EXTRA_ACCOUNT_INDEX,
+        )?,
+    ])
+}
+
+/// The gateway token of the owner at `owner_index`, in the network at `network_index`.
+///
+/// Gateway tokens are PDAs of the gateway program. Since the token owner is not
+/// present in the transfer instruction, recipient passes are issued to token accounts
+/// rather than wallets. A separate service (on- or off-chain) is needed to
+/// associate a gateway token with a token account, if the owner has one.
+/// The transfer authority is a wallet, so its pass is the usual wallet pass.
+fn gateway_token_meta(
+    gateway_program_index: u8,
+    owner_index: u8,
+    network_index: u8,
+) -> Result<ExtraAccountMeta, ProgramError> {
+    ExtraAccountMeta::new_external_pda_with_seeds(
+        gateway_program_index,
+        &[
+            Seed::AccountKey { index: owner_index },
+            Seed::Literal {
+                bytes: GATEWAY_TOKEN_ADDRESS_SEED.to_vec(),
+            },
+            // configurable seed (we assume 0 here for simplicity)
+            Seed::Literal { bytes: [0; 8].to_vec() },
+            Seed::AccountKey {
+                index: network_index,
+            },
+        ],
+        false,
+        false,
+    )
+}
+
+/// Collects metas while tracking their index in the `Execute` instruction
+#[derive(Default)]
+struct Builder {
+    metas: Vec<ExtraAccountMeta>,
+}
+
+impl Builder {
+    fn push(&mut self, meta: ExtraAccountMeta) -> u8 {
+        let index = FIRST_EXTRA_ACCOUNT_INDEX + self.metas.len() as u8;
+        self.metas.push(meta);
+        index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            rules::RuleBuilder,
+            state::{MintConfig, TransferPolicy},
+        },
+    };
+
+    #[test]
+    fn layout_indices() {
+        let config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+                ..TransferPolicy::default()
+            },
+        );
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), 6);
+        assert_eq!(layout.config, 5);
+        assert_eq!(layout.gateway_program, 6);
+        assert_eq!(
+            layout.networks,
+            vec![
+                NetworkAccounts {
+                    network: 7,
+                    destination_gateway_token: 8,
+                    authority_gateway_token: None,
+                    source_gateway_token: None,
+                },
+                NetworkAccounts {
+                    network: 9,
+                    destination_gateway_token: 10,
+                    authority_gateway_token: None,
+                    source_gateway_token: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_with_optional_accounts() {
+        let config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                flags: FLAG_VERIFY_DELEGATE | FLAG_TRANSFER_PERMITS,
+                approver: Some(Pubkey::new_unique()),
+                transfer_cooldown_seconds: 60,
+                holder_cap: Some(2_000),
+                ..TransferPolicy::default()
+            },
+        );
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), 8);
+        assert_eq!(layout.networks[0].authority_gateway_token, Some(9));
+        assert_eq!(layout.transfer_permit, Some(10));
+        assert_eq!(layout.instructions_sysvar, Some(11));
+        assert_eq!(layout.source_state, Some(12));
+        assert!(bool::from(metas[7].is_writable));
+        assert!(bool::from(metas[0].is_writable));
+        assert!(bool::from(metas[5].is_writable));
+    }
+
+    #[test]
+    fn layout_with_approver() {
+        let config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                approver: Some(Pubkey::new_unique()),
+                ..TransferPolicy::default()
+            },
+        );
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), 6);
+        assert_eq!(layout.instructions_sysvar, Some(9));
+        assert_eq!(layout.source_state, Some(10));
+        assert!(bool::from(metas[5].is_writable));
+    }
+
+    #[test]
+    fn layout_with_travel_rule() {
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                travel_rule_threshold: Some(1_000_000),
+                ..TransferPolicy::default()
+            },
+        );
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), 5);
+        assert_eq!(layout.networks[0].source_gateway_token, Some(9));
+        assert_eq!(layout.travel_rule_buffer, None);
+
+        config.travel_rule_buffer = Some(Pubkey::new_unique());
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(layout.travel_rule_buffer, Some(10));
+        assert!(bool::from(metas[5].is_writable));
+    }
+
+    #[test]
+    fn layout_with_recovery() {
+        let config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                permanent_delegate: PermanentDelegatePolicy::RecoveryOnly {
+                    quarantine: Pubkey::new_unique(),
+                    recovery_period_seconds: 86_400,
+                },
+                ..TransferPolicy::default()
+            },
+        );
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), 6);
+        assert_eq!(layout.networks[0].source_gateway_token, Some(9));
+        assert_eq!(layout.revocation_record, Some(10));
+    }
+
+    #[test]
+    fn layout_with_source_pass_rules() {
+        let mut builder = RuleBuilder::default();
+        let root = builder.source_has_pass(0);
+        let config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                rules: Some(builder.build(root, 1).unwrap()),
+                ..TransferPolicy::default()
+            },
+        );
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), 5);
+        assert_eq!(layout.networks[0].source_gateway_token, Some(9));
+    }
+
+    #[test]
+    fn layout_with_shared_policy() {
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                ..TransferPolicy::default()
+            },
+        );
+        let shared_policy = Pubkey::new_unique();
+        config.shared_policy = Some(shared_policy);
+        let (metas, layout) = build_extra_account_metas(&config).unwrap();
+        assert_eq!(metas.len(), 5);
+        assert_eq!(
+            metas[(SHARED_POLICY_INDEX - FIRST_EXTRA_ACCOUNT_INDEX) as usize].address_config,
+            shared_policy.to_bytes()
+        );
+        assert_eq!(layout.gateway_program, 7);
+        assert_eq!(layout.networks[0].network, 8);
+    }
+
+    #[test]
+    fn legacy_layout_matches_original() {
+        let metas = build_legacy_extra_account_metas(&Pubkey::new_unique()).unwrap();
+        assert_eq!(metas.len(), LEGACY_EXTRA_ACCOUNT_METAS_LEN);
+    }
+}
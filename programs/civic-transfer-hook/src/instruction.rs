@@ -1,16 +1,35 @@
 //! Instruction types
 
 use {
+    crate::{
+        pass::get_gateway_token_address,
+        processor::GATEWAY_PROGRAM_ID,
+        state::{
+            get_config_history_address, get_gatekeeper_authority_address,
+            get_hook_authority_address, get_mint_config_address,
+            get_pending_config_update_address, get_revocation_record_address,
+            get_shared_policy_address, get_source_state_address, get_transfer_permit_address,
+            ConfigUpdate, TransferPolicy,
+        },
+    },
+    borsh::{BorshDeserialize, BorshSerialize},
     solana_program::{
         instruction::{AccountMeta, Instruction},
+        clock::UnixTimestamp,
         program_error::ProgramError,
         pubkey::Pubkey,
-        system_program,
+        system_program, sysvar,
     },
     spl_discriminator::{ArrayDiscriminator, SplDiscriminate},
 };
 
 /// Instructions supported by the transfer hook interface.
+///
+/// Admin instructions are approved by the holder of a role in the mint config:
+/// the admin (the config authority), the compliance officer or the guardian.
+/// Roles that are not set are held by the admin. Any role can be held by a
+/// multisig: the multisig account is passed in place of the role holder,
+/// without signing, followed by the multisig signers that sign.
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq)]
 pub enum CivicTransferHookInstruction {
@@ -23,13 +42,472 @@ pub enum CivicTransferHookInstruction {
     ///   1. `[]` Mint
     ///   2. `[s]` Mint authority
     ///   3. `[]` System program
-    ///   4..4+M `[]` `M` additional accounts, to be written to validation data
+    ///   4. `[w]` Mint config
     ///
+    /// Both the extra account metas and the mint config must be funded
+    /// for rent before calling this instruction.
     InitializeExtraAccountMetas {
         /// The Gatekeeper Network that this token uses.
         /// Recipients of this token are required to have a gateway token from this network.
         gatekeeper_network: Pubkey
     },
+    /// Upgrades a mint initialized with the original three-account layout
+    /// by creating its mint config from the stored gatekeeper network, then
+    /// rewrites the extra account metas to the layout required by the config.
+    ///
+    /// Transfers keep working with the original layout until this runs,
+    /// and running it again on a migrated mint only rewrites the metas.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Account with extra account metas
+    ///   1. `[]` Mint
+    ///   2. `[s]` Mint authority, or config authority for a migrated mint
+    ///   3. `[]` System program
+    ///   4. `[w]` Mint config
+    ///
+    /// The mint config, and the extra account metas if they grow, must be
+    /// funded for rent before calling this instruction.
+    MigrateExtraAccountMetas,
+    /// Applies a change to the mint config and rewrites the extra account
+    /// metas to match it.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Account with extra account metas
+    ///   1. `[]` Mint
+    ///   2. `[s]` Holder of the role required by the update
+    ///   3. `[w]` Mint config
+    ///   4. `[w]` Config history, recorded to if initialized
+    ///
+    /// For a mint linked to a shared policy, or linked to one by the update,
+    /// the shared policy follows the config history.
+    ///
+    /// Any of these accounts must be funded for rent beforehand if the change
+    /// makes it grow.
+    ///
+    /// If the config has a timelock, only updates exempt from it (pausing)
+    /// can be applied directly, others must go through `QueueConfigUpdate`.
+    UpdateConfig {
+        /// The change to apply
+        update: ConfigUpdate,
+    },
+    /// Checks whether a transfer would be allowed by the hook, without
+    /// transferring. Runs the same checks as `Execute`, except that the
+    /// token accounts do not need to be in the middle of a transfer.
+    ///
+    /// The verdict is returned as a borsh-encoded
+    /// [EligibilityVerdict](struct.EligibilityVerdict.html) via return data.
+    ///
+    /// Accounts expected by this instruction are the same as for `Execute`:
+    ///
+    ///   0. `[]` Source token account
+    ///   1. `[]` Mint
+    ///   2. `[]` Destination token account
+    ///   3. `[]` Source token account authority
+    ///   4. `[]` Validation account
+    ///   5..5+M `[]` `M` extra accounts, as listed in the validation account
+    CheckEligibility {
+        /// Amount of tokens that would be transferred
+        amount: u64,
+    },
+    /// Creates a transfer permit, or replaces an existing one, allowing
+    /// transfers from `source` to `destination` of up to `amount` tokens in
+    /// total until `expiry`, even if the recipient or delegate lacks a valid pass.
+    /// Every other check of the mint still applies.
+    ///
+    /// Permits are only used if the mint config has `FLAG_TRANSFER_PERMITS` set.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Transfer permit
+    ///   1. `[]` Mint
+    ///   2. `[s]` Compliance officer
+    ///   3. `[]` System program
+    ///   4. `[]` Mint config
+    ///
+    /// A new transfer permit must be funded for rent before calling this instruction.
+    CreateTransferPermit {
+        /// The source token account
+        source: Pubkey,
+        /// The destination token account
+        destination: Pubkey,
+        /// The total amount that may be transferred
+        amount: u64,
+        /// The time after which the permit can no longer be used
+        expiry: UnixTimestamp,
+    },
+    /// Closes a transfer permit, returning its lamports.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Transfer permit
+    ///   1. `[]` Mint
+    ///   2. `[s]` Compliance officer
+    ///   3. `[]` Mint config
+    ///   4. `[w]` Destination for the lamports
+    CloseTransferPermit,
+    /// Creates the source state of a token account, which records its
+    /// outgoing transfers. Required before a token account can send tokens
+    /// of a mint with a transfer cooldown, or send tokens under an approval.
+    /// Anyone may call this instruction.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Source state
+    ///   1. `[]` Mint
+    ///   2. `[]` Token account
+    ///   3. `[]` System program
+    ///
+    /// The source state must be funded for rent before calling this instruction.
+    InitializeSourceState,
+    /// Starts writing travel rule records to a new buffer. The previous
+    /// buffer, if any, is kept until closed with `CloseTravelRuleBuffer`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Account with extra account metas
+    ///   1. `[]` Mint
+    ///   2. `[s]` Config authority
+    ///   3. `[w]` Mint config
+    ///   4. `[w]` New travel rule buffer
+    ///   5. `[w]` Config history, recorded to if initialized
+    ///
+    /// For a mint linked to a shared policy, the shared policy follows the
+    /// config history, and the extra account metas are rewritten with it.
+    ///
+    /// The new buffer must be created beforehand with the system program,
+    /// zeroed and owned by this program. Its size sets the number of records it holds.
+    RotateTravelRuleBuffer,
+    /// Closes a travel rule buffer that is no longer in use, returning its lamports.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Travel rule buffer
+    ///   1. `[]` Mint
+    ///   2. `[s]` Config authority
+    ///   3. `[]` Mint config
+    ///   4. `[w]` Destination for the lamports
+    CloseTravelRuleBuffer,
+    /// Initializes an M-of-N multisig, which can then be made the config
+    /// authority of mints with `ConfigUpdate::SetAuthority`. Multisigs are
+    /// standalone: they are not tied to a mint, and gain authority over one
+    /// only once its current authority hands it over.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w, s]` Multisig account
+    ///
+    /// The multisig account must be created beforehand with the system program,
+    /// zeroed and owned by this program. It signs this instruction as well, so
+    /// that no one else can initialize it in between.
+    InitializeMultisig {
+        /// Number of signers required to approve an instruction
+        m: u8,
+        /// The keys that may approve instructions
+        signers: Vec<Pubkey>,
+    },
+    /// Queues a config update, to be applied with `ExecuteConfigUpdate` once
+    /// the timelock of the config has passed. Only one update can be queued per mint.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Pending config update
+    ///   1. `[]` Mint
+    ///   2. `[s]` Holder of the role required by the update
+    ///   3. `[]` System program
+    ///   4. `[]` Mint config
+    ///
+    /// For a mint linked to a shared policy, or linked to one by the update,
+    /// the shared policy follows the mint config.
+    ///
+    /// The pending config update must be funded for rent before calling this instruction.
+    QueueConfigUpdate {
+        /// The change to apply
+        update: ConfigUpdate,
+    },
+    /// Applies a queued config update whose timelock has passed, and rewrites
+    /// the extra account metas to match.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Account with extra account metas
+    ///   1. `[]` Mint
+    ///   2. `[s]` Holder of the role required by the update
+    ///   3. `[w]` Mint config
+    ///   4. `[w]` Pending config update
+    ///   5. `[w]` Destination for the lamports of the pending config update
+    ///   6. `[w]` Config history, recorded to if initialized
+    ///
+    /// For a mint linked to a shared policy, or linked to one by the update,
+    /// the shared policy follows the config history.
+    ExecuteConfigUpdate,
+    /// Cancels a queued config update.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Pending config update
+    ///   1. `[]` Mint
+    ///   2. `[s]` Holder of the role required by the update
+    ///   3. `[]` Mint config
+    ///   4. `[w]` Destination for the lamports of the pending config update
+    CancelConfigUpdate,
+    /// Starts the config history of a mint, to which config updates and
+    /// travel rule buffer rotations are then recorded.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Config history
+    ///   1. `[]` Mint
+    ///   2. `[s]` Config authority
+    ///   3. `[]` System program
+    ///   4. `[]` Mint config
+    ///
+    /// The config history must be funded for rent beforehand, for
+    /// `ConfigHistory::size_of(1)` bytes, then topped up as it grows.
+    InitializeConfigHistory,
+    /// Thaws a token account that presents a valid pass in one of the
+    /// accepted networks. Anyone may call this instruction.
+    ///
+    /// Requires the hook authority PDA of the mint to be its freeze authority,
+    /// e.g. for mints whose token accounts start frozen with `DefaultAccountState`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Token account
+    ///   1. `[]` Mint
+    ///   2. `[]` Hook authority
+    ///   3. `[]` Mint config
+    ///   4. `[]` Gateway token of the token account
+    ///   5. `[]` Token program
+    ///
+    /// For a mint linked to a shared policy, the shared policy follows the mint config.
+    ThawAccount,
+    /// Freezes a token account whose pass is missing, or in a state the policy
+    /// rejects, e.g. revoked or expired beyond the grace period. Anyone may call
+    /// this instruction, so that revocations can be enforced before the next transfer.
+    ///
+    /// Requires the hook authority PDA of the mint to be its freeze authority.
+    /// Token accounts on the allow list and issuer accounts are never frozen.
+    /// Mints whose policy has rules are not supported, as the rules decide per
+    /// transfer rather than per pass. Frozen accounts are thawed with `ThawAccount` once they have a valid pass,
+    /// or by the compliance officer with `AuthorityThawAccount`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Token account
+    ///   1. `[]` Mint
+    ///   2. `[]` Hook authority
+    ///   3. `[]` Mint config
+    ///   4. `[]` Token program
+    ///   5. `[]` Revocation record of the token account
+    ///   6..6+N `[]` Gateway token of the token account in each accepted network, in order
+    ///
+    /// For a mint linked to a shared policy, the shared policy follows the mint config.
+    ///
+    /// Under `PermanentDelegatePolicy::RecoveryOnly`, the quarantine account and
+    /// token accounts with a revocation record are not frozen, as the permanent
+    /// delegate cannot move tokens out of or into a frozen account.
+    FreezeAccount,
+    /// Records that the pass of a token account is revoked, starting its
+    /// recovery period under `PermanentDelegatePolicy::RecoveryOnly`.
+    /// Anyone may call this instruction.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Revocation record
+    ///   1. `[]` Mint
+    ///   2. `[]` Token account
+    ///   3. `[]` Mint config
+    ///   4. `[s]` Recorder, refunded when the record is closed
+    ///   5. `[]` System program
+    ///   6..6+N `[]` Gateway token of the token account in each accepted network, in order
+    ///
+    /// The revocation record must be funded for rent before calling this instruction.
+    ///
+    /// For a mint linked to a shared policy, the shared policy follows the mint config.
+    RecordRevokedPass,
+    /// Closes a revocation record once the token account is empty or has a
+    /// valid pass again, refunding the recorder. Anyone may call this instruction.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Revocation record
+    ///   1. `[]` Mint
+    ///   2. `[]` Token account
+    ///   3. `[]` Mint config
+    ///   4. `[w]` Recorder
+    ///   5..5+N `[]` Gateway token of the token account in each accepted network,
+    ///      in order, if the token account is not empty
+    ///
+    /// For a mint linked to a shared policy, the shared policy follows the mint config.
+    CloseRevocationRecord,
+    /// Issues a token pass for a token account, in one of the accepted networks
+    /// of the mint, to an owner holding an active pass in the base pass network
+    /// of the mint. The token pass expires with the base pass.
+    ///
+    /// The program signs as the gatekeeper authority PDA of the mint in the
+    /// token pass network, which must be registered as a gatekeeper in that
+    /// network. Each mint has its own PDA, so that a network only issues
+    /// passes for the mints it registered.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[ws]` Payer of the gateway token
+    ///   1. `[w]` Gateway token to issue
+    ///   2. `[]` Token account
+    ///   3. `[]` Mint
+    ///   4. `[]` Mint config
+    ///   5. `[s]` Owner of the token account
+    ///   6. `[]` Base pass of the owner
+    ///   7. `[]` Gatekeeper authority
+    ///   8. `[]` Gatekeeper account of the gatekeeper authority in the network
+    ///   9. `[]` Token pass network
+    ///   10. `[]` Rent sysvar
+    ///   11. `[]` System program
+    ///   12. `[]` Gateway program
+    ///
+    /// For a mint linked to a shared policy, the shared policy follows the mint config.
+    IssueTokenPass,
+    /// Creates a shared policy, which mints can link to in order to enforce
+    /// the same rules. The creator becomes its authority.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Shared policy
+    ///   1. `[s]` Creator
+    ///   2. `[]` System program
+    ///
+    /// The shared policy must be funded for rent before calling this instruction.
+    CreateSharedPolicy {
+        /// Id of the policy among those of the creator, part of its address
+        id: u64,
+        /// The transfer policy
+        policy: TransferPolicy,
+    },
+    /// Applies an update to a shared policy, and so to every mint linked to it.
+    /// Only policy updates, `SetAuthority` to transfer the shared policy,
+    /// `SetTimelock` and `SetApprovedMints`, to choose the mints that may link
+    /// to it, are accepted.
+    ///
+    /// After updates that change the accounts required by transfers, e.g.
+    /// adding a network, the extra account metas of each linked mint must be
+    /// rewritten with `SyncExtraAccountMetas`. Transfers of a linked mint fail
+    /// until then. The linked mints are those marked as linked among the
+    /// approved mints of the shared policy.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Shared policy
+    ///   1. `[s]` Shared policy authority
+    ///   2. `[w]` Shared policy history, recorded to if initialized
+    ///
+    /// The shared policy and its history must be funded for rent beforehand
+    /// if they grow.
+    ///
+    /// If the shared policy has a timelock, updates must go through
+    /// `QueueSharedPolicyUpdate`.
+    UpdateSharedPolicy {
+        /// The change to apply
+        update: ConfigUpdate,
+    },
+    /// Closes a shared policy that no mint is linked to.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Shared policy
+    ///   1. `[s]` Shared policy authority
+    ///   2. `[w]` Destination for the lamports of the shared policy
+    CloseSharedPolicy,
+    /// Rewrites the extra account metas of a mint to match its config and
+    /// shared policy, after an update of the shared policy changed the
+    /// accounts transfers need. Anyone may call this instruction.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Account with extra account metas
+    ///   1. `[]` Mint
+    ///   2. `[]` Mint config
+    ///   3. `[]` Shared policy, if the mint is linked to one
+    ///
+    /// The extra account metas must be funded for rent beforehand if they grow.
+    SyncExtraAccountMetas,
+    /// Thaws a token account without requiring a pass, e.g. one frozen by
+    /// `FreezeAccount` whose pass was revoked by mistake, or whose holder is
+    /// cleared off-chain. Approved by the compliance officer of the mint.
+    ///
+    /// Requires the hook authority PDA of the mint to be its freeze authority.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Token account
+    ///   1. `[]` Mint
+    ///   2. `[]` Hook authority
+    ///   3. `[]` Mint config
+    ///   4. `[s]` Compliance officer
+    ///   5. `[]` Token program
+    AuthorityThawAccount,
+    /// Queues an update to a timelocked shared policy, to be applied with
+    /// `ExecuteSharedPolicyUpdate` once the timelock of the shared policy has
+    /// passed. Only one update can be queued per shared policy.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Pending config update of the shared policy
+    ///   1. `[]` Shared policy
+    ///   2. `[s]` Shared policy authority
+    ///   3. `[]` System program
+    ///
+    /// The pending config update must be funded for rent before calling this instruction.
+    QueueSharedPolicyUpdate {
+        /// The change to apply
+        update: ConfigUpdate,
+    },
+    /// Applies a queued update to a shared policy whose timelock has passed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Shared policy
+    ///   1. `[s]` Shared policy authority
+    ///   2. `[w]` Pending config update of the shared policy
+    ///   3. `[w]` Destination for the lamports of the pending config update
+    ///   4. `[w]` Shared policy history, recorded to if initialized
+    ///
+    /// The shared policy and its history must be funded for rent beforehand
+    /// if they grow.
+    ExecuteSharedPolicyUpdate,
+    /// Cancels a queued update to a shared policy.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Pending config update of the shared policy
+    ///   1. `[]` Shared policy
+    ///   2. `[s]` Shared policy authority
+    ///   3. `[w]` Destination for the lamports of the pending config update
+    CancelSharedPolicyUpdate,
+    /// Starts the history of a shared policy, to which its updates are then
+    /// recorded.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[w]` Shared policy history
+    ///   1. `[]` Shared policy
+    ///   2. `[s]` Shared policy authority
+    ///   3. `[]` System program
+    ///
+    /// The history must be funded for rent beforehand, for
+    /// `ConfigHistory::size_of(1)` bytes, then topped up as it grows.
+    InitializeSharedPolicyHistory,
+}
+
+/// The result of a `CheckEligibility` instruction
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct EligibilityVerdict {
+    /// Whether the transfer would be allowed
+    pub eligible: bool,
+    /// The error the transfer would fail with, as a `u64` program error code,
+    /// or 0 if it is eligible
+    pub error: u64,
 }
 /// TLV instruction type used to initialize extra account metas
 /// for the transfer hook
@@ -37,6 +515,141 @@ pub enum CivicTransferHookInstruction {
 #[discriminator_hash_input("spl-transfer-hook-interface:initialize-extra-account-metas")]
 pub struct InitializeExtraAccountMetasInstruction;
 
+/// TLV instruction type used to migrate the extra account metas of a mint
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:migrate-extra-account-metas")]
+pub struct MigrateExtraAccountMetasInstruction;
+
+/// TLV instruction type used to update a mint config
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:update-config")]
+pub struct UpdateConfigInstruction;
+
+/// TLV instruction type used to check whether a transfer would be allowed
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:check-eligibility")]
+pub struct CheckEligibilityInstruction;
+
+/// TLV instruction type used to create a transfer permit
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:create-transfer-permit")]
+pub struct CreateTransferPermitInstruction;
+
+/// TLV instruction type used to close a transfer permit
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:close-transfer-permit")]
+pub struct CloseTransferPermitInstruction;
+
+/// TLV instruction type used to initialize the source state of a token account
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:initialize-source-state")]
+pub struct InitializeSourceStateInstruction;
+
+/// TLV instruction type used to rotate the travel rule buffer of a mint
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:rotate-travel-rule-buffer")]
+pub struct RotateTravelRuleBufferInstruction;
+
+/// TLV instruction type used to close a travel rule buffer
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:close-travel-rule-buffer")]
+pub struct CloseTravelRuleBufferInstruction;
+
+/// TLV instruction type used to initialize a multisig authority
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:initialize-multisig")]
+pub struct InitializeMultisigInstruction;
+
+/// TLV instruction type used to queue a config update
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:queue-config-update")]
+pub struct QueueConfigUpdateInstruction;
+
+/// TLV instruction type used to apply a queued config update
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:execute-config-update")]
+pub struct ExecuteConfigUpdateInstruction;
+
+/// TLV instruction type used to cancel a queued config update
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:cancel-config-update")]
+pub struct CancelConfigUpdateInstruction;
+
+/// TLV instruction type used to start the config history of a mint
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:initialize-config-history")]
+pub struct InitializeConfigHistoryInstruction;
+
+/// TLV instruction type used to thaw a token account with a valid pass
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:thaw-account")]
+pub struct ThawAccountInstruction;
+
+/// TLV instruction type used to freeze a token account without a valid pass
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:freeze-account")]
+pub struct FreezeAccountInstruction;
+
+/// TLV instruction type used to record a revoked pass
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:record-revoked-pass")]
+pub struct RecordRevokedPassInstruction;
+
+/// TLV instruction type used to close a revocation record
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:close-revocation-record")]
+pub struct CloseRevocationRecordInstruction;
+
+/// TLV instruction type used to issue a token pass to the holder of a base pass
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:issue-token-pass")]
+pub struct IssueTokenPassInstruction;
+
+/// TLV instruction type used to create a shared policy
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:create-shared-policy")]
+pub struct CreateSharedPolicyInstruction;
+
+/// TLV instruction type used to update a shared policy
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:update-shared-policy")]
+pub struct UpdateSharedPolicyInstruction;
+
+/// TLV instruction type used to close a shared policy
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:close-shared-policy")]
+pub struct CloseSharedPolicyInstruction;
+
+/// TLV instruction type used to resynchronize the extra account metas of a mint
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:sync-extra-account-metas")]
+pub struct SyncExtraAccountMetasInstruction;
+
+/// TLV instruction type used to thaw a token account on behalf of the compliance officer
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:authority-thaw-account")]
+pub struct AuthorityThawAccountInstruction;
+
+/// TLV instruction type used to queue an update to a shared policy
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:queue-shared-policy-update")]
+pub struct QueueSharedPolicyUpdateInstruction;
+
+/// TLV instruction type used to apply a queued update to a shared policy
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:execute-shared-policy-update")]
+pub struct ExecuteSharedPolicyUpdateInstruction;
+
+/// TLV instruction type used to cancel a queued update to a shared policy
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:cancel-shared-policy-update")]
+pub struct CancelSharedPolicyUpdateInstruction;
+
+/// TLV instruction type used to start the history of a shared policy
+#[derive(SplDiscriminate)]
+#[discriminator_hash_input("civic-transfer-hook:initialize-shared-policy-history")]
+pub struct InitializeSharedPolicyHistoryInstruction;
+
 impl CivicTransferHookInstruction {
     /// Unpacks a byte buffer into a [TransferHookInstruction](enum.TransferHookInstruction.html).
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
@@ -55,6 +668,96 @@ impl CivicTransferHookInstruction {
                     gatekeeper_network
                 }
             }
+            MigrateExtraAccountMetasInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::MigrateExtraAccountMetas
+            }
+            UpdateConfigInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let update = ConfigUpdate::try_from_slice(rest)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::UpdateConfig { update }
+            }
+            CheckEligibilityInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let amount = rest
+                    .get(..8)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or(ProgramError::InvalidInstructionData)?;
+                Self::CheckEligibility { amount }
+            }
+            CreateTransferPermitInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let (source, destination, amount, expiry) =
+                    <(Pubkey, Pubkey, u64, UnixTimestamp)>::try_from_slice(rest)
+                        .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::CreateTransferPermit {
+                    source,
+                    destination,
+                    amount,
+                    expiry,
+                }
+            }
+            CloseTransferPermitInstruction::SPL_DISCRIMINATOR_SLICE => Self::CloseTransferPermit,
+            InitializeSourceStateInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::InitializeSourceState
+            }
+            RotateTravelRuleBufferInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::RotateTravelRuleBuffer
+            }
+            CloseTravelRuleBufferInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::CloseTravelRuleBuffer
+            }
+            InitializeMultisigInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let (m, signers) = <(u8, Vec<Pubkey>)>::try_from_slice(rest)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::InitializeMultisig { m, signers }
+            }
+            QueueConfigUpdateInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let update = ConfigUpdate::try_from_slice(rest)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::QueueConfigUpdate { update }
+            }
+            ExecuteConfigUpdateInstruction::SPL_DISCRIMINATOR_SLICE => Self::ExecuteConfigUpdate,
+            CancelConfigUpdateInstruction::SPL_DISCRIMINATOR_SLICE => Self::CancelConfigUpdate,
+            InitializeConfigHistoryInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::InitializeConfigHistory
+            }
+            ThawAccountInstruction::SPL_DISCRIMINATOR_SLICE => Self::ThawAccount,
+            FreezeAccountInstruction::SPL_DISCRIMINATOR_SLICE => Self::FreezeAccount,
+            RecordRevokedPassInstruction::SPL_DISCRIMINATOR_SLICE => Self::RecordRevokedPass,
+            CloseRevocationRecordInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::CloseRevocationRecord
+            }
+            IssueTokenPassInstruction::SPL_DISCRIMINATOR_SLICE => Self::IssueTokenPass,
+            CreateSharedPolicyInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let (id, policy) = <(u64, TransferPolicy)>::try_from_slice(rest)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::CreateSharedPolicy { id, policy }
+            }
+            UpdateSharedPolicyInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let update = ConfigUpdate::try_from_slice(rest)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::UpdateSharedPolicy { update }
+            }
+            CloseSharedPolicyInstruction::SPL_DISCRIMINATOR_SLICE => Self::CloseSharedPolicy,
+            SyncExtraAccountMetasInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::SyncExtraAccountMetas
+            }
+            AuthorityThawAccountInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::AuthorityThawAccount
+            }
+            QueueSharedPolicyUpdateInstruction::SPL_DISCRIMINATOR_SLICE => {
+                let update = ConfigUpdate::try_from_slice(rest)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::QueueSharedPolicyUpdate { update }
+            }
+            ExecuteSharedPolicyUpdateInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::ExecuteSharedPolicyUpdate
+            }
+            CancelSharedPolicyUpdateInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::CancelSharedPolicyUpdate
+            }
+            InitializeSharedPolicyHistoryInstruction::SPL_DISCRIMINATOR_SLICE => {
+                Self::InitializeSharedPolicyHistory
+            }
             _ => return Err(ProgramError::InvalidInstructionData),
         })
     }
@@ -69,26 +772,760 @@ impl CivicTransferHookInstruction {
                 );
                 buf.extend_from_slice(&gatekeeper_network.to_bytes())
             }
-        };
-        buf
-    }
-}
-
-/// Creates a `InitializeExtraAccountMetas` instruction.
-pub fn initialize_extra_account_metas(
-    program_id: &Pubkey,
-    extra_account_metas_pubkey: &Pubkey,
-    mint_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    gatekeeper_network: &Pubkey,
-) -> Instruction {
-    let data = CivicTransferHookInstruction::InitializeExtraAccountMetas { gatekeeper_network: *gatekeeper_network }.pack();
-
-    let accounts = vec![
-        AccountMeta::new(*extra_account_metas_pubkey, false),
-        AccountMeta::new_readonly(*mint_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, true),
-        AccountMeta::new_readonly(system_program::id(), false),
+            Self::MigrateExtraAccountMetas => {
+                buf.extend_from_slice(
+                    MigrateExtraAccountMetasInstruction::SPL_DISCRIMINATOR_SLICE,
+                );
+            }
+            Self::UpdateConfig { update } => {
+                buf.extend_from_slice(UpdateConfigInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.append(&mut update.try_to_vec().unwrap());
+            }
+            Self::CheckEligibility { amount } => {
+                buf.extend_from_slice(CheckEligibilityInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::CreateTransferPermit {
+                source,
+                destination,
+                amount,
+                expiry,
+            } => {
+                buf.extend_from_slice(CreateTransferPermitInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.append(
+                    &mut (source, destination, amount, expiry)
+                        .try_to_vec()
+                        .unwrap(),
+                );
+            }
+            Self::CloseTransferPermit => {
+                buf.extend_from_slice(CloseTransferPermitInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::InitializeSourceState => {
+                buf.extend_from_slice(InitializeSourceStateInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::RotateTravelRuleBuffer => {
+                buf.extend_from_slice(RotateTravelRuleBufferInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::CloseTravelRuleBuffer => {
+                buf.extend_from_slice(CloseTravelRuleBufferInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::InitializeMultisig { m, signers } => {
+                buf.extend_from_slice(InitializeMultisigInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.push(*m);
+                buf.append(&mut signers.try_to_vec().unwrap());
+            }
+            Self::QueueConfigUpdate { update } => {
+                buf.extend_from_slice(QueueConfigUpdateInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.append(&mut update.try_to_vec().unwrap());
+            }
+            Self::ExecuteConfigUpdate => {
+                buf.extend_from_slice(ExecuteConfigUpdateInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::CancelConfigUpdate => {
+                buf.extend_from_slice(CancelConfigUpdateInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::InitializeConfigHistory => {
+                buf.extend_from_slice(InitializeConfigHistoryInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::ThawAccount => {
+                buf.extend_from_slice(ThawAccountInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::FreezeAccount => {
+                buf.extend_from_slice(FreezeAccountInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::RecordRevokedPass => {
+                buf.extend_from_slice(RecordRevokedPassInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::CloseRevocationRecord => {
+                buf.extend_from_slice(CloseRevocationRecordInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::IssueTokenPass => {
+                buf.extend_from_slice(IssueTokenPassInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::CreateSharedPolicy { id, policy } => {
+                buf.extend_from_slice(CreateSharedPolicyInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.append(&mut (id, policy).try_to_vec().unwrap());
+            }
+            Self::UpdateSharedPolicy { update } => {
+                buf.extend_from_slice(UpdateSharedPolicyInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.append(&mut update.try_to_vec().unwrap());
+            }
+            Self::CloseSharedPolicy => {
+                buf.extend_from_slice(CloseSharedPolicyInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::SyncExtraAccountMetas => {
+                buf.extend_from_slice(SyncExtraAccountMetasInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::AuthorityThawAccount => {
+                buf.extend_from_slice(AuthorityThawAccountInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::QueueSharedPolicyUpdate { update } => {
+                buf.extend_from_slice(QueueSharedPolicyUpdateInstruction::SPL_DISCRIMINATOR_SLICE);
+                buf.append(&mut update.try_to_vec().unwrap());
+            }
+            Self::ExecuteSharedPolicyUpdate => {
+                buf.extend_from_slice(ExecuteSharedPolicyUpdateInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::CancelSharedPolicyUpdate => {
+                buf.extend_from_slice(CancelSharedPolicyUpdateInstruction::SPL_DISCRIMINATOR_SLICE);
+            }
+            Self::InitializeSharedPolicyHistory => {
+                buf.extend_from_slice(
+                    InitializeSharedPolicyHistoryInstruction::SPL_DISCRIMINATOR_SLICE,
+                );
+            }
+        };
+        buf
+    }
+}
+
+/// Creates a `InitializeExtraAccountMetas` instruction.
+pub fn initialize_extra_account_metas(
+    program_id: &Pubkey,
+    extra_account_metas_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    gatekeeper_network: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::InitializeExtraAccountMetas { gatekeeper_network: *gatekeeper_network }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*extra_account_metas_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `MigrateExtraAccountMetas` instruction.
+pub fn migrate_extra_account_metas(
+    program_id: &Pubkey,
+    extra_account_metas_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Instruction {
+    let data = CivicTransferHookInstruction::MigrateExtraAccountMetas.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*extra_account_metas_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `UpdateConfig` instruction. `shared_policy_pubkey` is the shared
+/// policy the mint is linked to, or the one it is being linked to.
+pub fn update_config(
+    program_id: &Pubkey,
+    extra_account_metas_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    update: ConfigUpdate,
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::UpdateConfig { update }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*extra_account_metas_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new(get_mint_config_address(mint_pubkey, program_id), false),
+        AccountMeta::new(get_config_history_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(linked_shared_policy_meta(shared_policy_pubkey));
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CheckEligibility` instruction, without the extra accounts.
+///
+/// The extra accounts are the same as for `Execute`, and can be added with
+/// `ExtraAccountMetaList::add_to_instruction::<ExecuteInstruction>`.
+#[allow(clippy::too_many_arguments)]
+pub fn check_eligibility(
+    program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    validation_pubkey: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CheckEligibility { amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*source_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*destination_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*validation_pubkey, false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CreateTransferPermit` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn create_transfer_permit(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    amount: u64,
+    expiry: UnixTimestamp,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CreateTransferPermit {
+        source: *source_pubkey,
+        destination: *destination_pubkey,
+        amount,
+        expiry,
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(
+            get_transfer_permit_address(mint_pubkey, source_pubkey, destination_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CloseTransferPermit` instruction.
+pub fn close_transfer_permit(
+    program_id: &Pubkey,
+    permit_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports_destination_pubkey: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CloseTransferPermit.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*permit_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+        AccountMeta::new(*lamports_destination_pubkey, false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `InitializeSourceState` instruction.
+pub fn initialize_source_state(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::InitializeSourceState.pack();
+
+    let accounts = vec![
+        AccountMeta::new(
+            get_source_state_address(mint_pubkey, source_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*source_pubkey, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `RotateTravelRuleBuffer` instruction. `shared_policy_pubkey` is
+/// the shared policy the mint is linked to, if any.
+pub fn rotate_travel_rule_buffer(
+    program_id: &Pubkey,
+    extra_account_metas_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    buffer_pubkey: &Pubkey,
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::RotateTravelRuleBuffer.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*extra_account_metas_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new(get_mint_config_address(mint_pubkey, program_id), false),
+        AccountMeta::new(*buffer_pubkey, false),
+        AccountMeta::new(get_config_history_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CloseTravelRuleBuffer` instruction.
+pub fn close_travel_rule_buffer(
+    program_id: &Pubkey,
+    buffer_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports_destination_pubkey: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CloseTravelRuleBuffer.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*buffer_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+        AccountMeta::new(*lamports_destination_pubkey, false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `InitializeMultisig` instruction for a multisig with `signers`.
+pub fn initialize_multisig(
+    program_id: &Pubkey,
+    multisig_pubkey: &Pubkey,
+    m: u8,
+    signers: Vec<Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::InitializeMultisig { m, signers }.pack();
+
+    let accounts = vec![AccountMeta::new(*multisig_pubkey, true)];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `QueueConfigUpdate` instruction. `shared_policy_pubkey` is the
+/// shared policy the mint is linked to, or the one it is being linked to.
+pub fn queue_config_update(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    update: ConfigUpdate,
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::QueueConfigUpdate { update }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(get_pending_config_update_address(mint_pubkey, program_id), false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `ExecuteConfigUpdate` instruction. `shared_policy_pubkey` is the
+/// shared policy the mint is linked to, or the one it is being linked to.
+pub fn execute_config_update(
+    program_id: &Pubkey,
+    extra_account_metas_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports_destination_pubkey: &Pubkey,
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::ExecuteConfigUpdate.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*extra_account_metas_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new(get_mint_config_address(mint_pubkey, program_id), false),
+        AccountMeta::new(get_pending_config_update_address(mint_pubkey, program_id), false),
+        AccountMeta::new(*lamports_destination_pubkey, false),
+        AccountMeta::new(get_config_history_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(linked_shared_policy_meta(shared_policy_pubkey));
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CancelConfigUpdate` instruction.
+pub fn cancel_config_update(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports_destination_pubkey: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CancelConfigUpdate.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(get_pending_config_update_address(mint_pubkey, program_id), false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+        AccountMeta::new(*lamports_destination_pubkey, false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `InitializeConfigHistory` instruction.
+pub fn initialize_config_history(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Instruction {
+    let data = CivicTransferHookInstruction::InitializeConfigHistory.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(get_config_history_address(mint_pubkey, program_id), false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `ThawAccount` instruction.
+pub fn thaw_account(
+    program_id: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    gateway_token_pubkey: &Pubkey,
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::ThawAccount.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*token_account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(get_hook_authority_address(mint_pubkey, program_id), false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+    accounts.extend([
+        AccountMeta::new_readonly(*gateway_token_pubkey, false),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+    ]);
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `FreezeAccount` instruction, presenting the gateway tokens of
+/// the token account in each of the accepted `gatekeeper_networks` of the mint.
+pub fn freeze_account(
+    program_id: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    gatekeeper_networks: &[Pubkey],
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::FreezeAccount.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*token_account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(get_hook_authority_address(mint_pubkey, program_id), false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+    accounts.extend([
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(
+            get_revocation_record_address(mint_pubkey, token_account_pubkey, program_id),
+            false,
+        ),
+    ]);
+    accounts.extend(gatekeeper_networks.iter().map(|gatekeeper_network| {
+        AccountMeta::new_readonly(
+            get_gateway_token_address(token_account_pubkey, gatekeeper_network),
+            false,
+        )
+    }));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `RecordRevokedPass` instruction, presenting the gateway tokens of
+/// the token account in each of the accepted `gatekeeper_networks` of the mint.
+pub fn record_revoked_pass(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    recorder_pubkey: &Pubkey,
+    gatekeeper_networks: &[Pubkey],
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::RecordRevokedPass.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(
+            get_revocation_record_address(mint_pubkey, token_account_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*token_account_pubkey, false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+    accounts.extend([
+        AccountMeta::new_readonly(*recorder_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    accounts.extend(gatekeeper_networks.iter().map(|gatekeeper_network| {
+        AccountMeta::new_readonly(
+            get_gateway_token_address(token_account_pubkey, gatekeeper_network),
+            false,
+        )
+    }));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CloseRevocationRecord` instruction, presenting the gateway tokens of
+/// the token account in each of the accepted `gatekeeper_networks` of the mint.
+pub fn close_revocation_record(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    recorder_pubkey: &Pubkey,
+    gatekeeper_networks: &[Pubkey],
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CloseRevocationRecord.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(
+            get_revocation_record_address(mint_pubkey, token_account_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*token_account_pubkey, false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+    accounts.push(AccountMeta::new(*recorder_pubkey, false));
+    accounts.extend(gatekeeper_networks.iter().map(|gatekeeper_network| {
+        AccountMeta::new_readonly(
+            get_gateway_token_address(token_account_pubkey, gatekeeper_network),
+            false,
+        )
+    }));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `IssueTokenPass` instruction.
+pub fn issue_token_pass(
+    program_id: &Pubkey,
+    payer_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    base_pass_network: &Pubkey,
+    network: &Pubkey,
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::IssueTokenPass.pack();
+
+    let gatekeeper_authority = get_gatekeeper_authority_address(mint_pubkey, network, program_id);
+    let (gatekeeper_account, _) =
+        solana_gateway::state::get_gatekeeper_address_with_seed(&gatekeeper_authority, network);
+    let mut accounts = vec![
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new(get_gateway_token_address(token_account_pubkey, network), false),
+        AccountMeta::new_readonly(*token_account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+    accounts.extend([
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new_readonly(
+            get_gateway_token_address(owner_pubkey, base_pass_network),
+            false,
+        ),
+        AccountMeta::new_readonly(gatekeeper_authority, false),
+        AccountMeta::new_readonly(gatekeeper_account, false),
+        AccountMeta::new_readonly(*network, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(GATEWAY_PROGRAM_ID, false),
+    ]);
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// The shared policy account of a mint, if the mint is linked to one
+fn shared_policy_meta(shared_policy_pubkey: Option<&Pubkey>) -> Option<AccountMeta> {
+    shared_policy_pubkey
+        .map(|shared_policy_pubkey| AccountMeta::new_readonly(*shared_policy_pubkey, false))
+}
+
+/// The shared policy account of a config update, writable as linking and
+/// unlinking mark the mint as linked or unlinked in it
+fn linked_shared_policy_meta(shared_policy_pubkey: Option<&Pubkey>) -> Option<AccountMeta> {
+    shared_policy_pubkey.map(|shared_policy_pubkey| AccountMeta::new(*shared_policy_pubkey, false))
+}
+
+/// Creates a `CreateSharedPolicy` instruction.
+pub fn create_shared_policy(
+    program_id: &Pubkey,
+    creator_pubkey: &Pubkey,
+    id: u64,
+    policy: TransferPolicy,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CreateSharedPolicy { id, policy }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(get_shared_policy_address(creator_pubkey, id, program_id), false),
+        AccountMeta::new_readonly(*creator_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
 
     Instruction {
@@ -98,13 +1535,259 @@ pub fn initialize_extra_account_metas(
     }
 }
 
+/// Creates an `UpdateSharedPolicy` instruction.
+pub fn update_shared_policy(
+    program_id: &Pubkey,
+    shared_policy_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    update: ConfigUpdate,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::UpdateSharedPolicy { update }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*shared_policy_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new(
+            get_config_history_address(shared_policy_pubkey, program_id),
+            false,
+        ),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CloseSharedPolicy` instruction.
+pub fn close_shared_policy(
+    program_id: &Pubkey,
+    shared_policy_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports_destination_pubkey: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CloseSharedPolicy.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*shared_policy_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new(*lamports_destination_pubkey, false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `SyncExtraAccountMetas` instruction.
+pub fn sync_extra_account_metas(
+    program_id: &Pubkey,
+    extra_account_metas_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    shared_policy_pubkey: Option<&Pubkey>,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::SyncExtraAccountMetas.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*extra_account_metas_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+    ];
+    accounts.extend(shared_policy_meta(shared_policy_pubkey));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+
+/// Creates an `AuthorityThawAccount` instruction.
+pub fn authority_thaw_account(
+    program_id: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Instruction {
+    let data = CivicTransferHookInstruction::AuthorityThawAccount.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*token_account_pubkey, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(get_hook_authority_address(mint_pubkey, program_id), false),
+        AccountMeta::new_readonly(get_mint_config_address(mint_pubkey, program_id), false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `QueueSharedPolicyUpdate` instruction.
+pub fn queue_shared_policy_update(
+    program_id: &Pubkey,
+    shared_policy_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    update: ConfigUpdate,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::QueueSharedPolicyUpdate { update }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(
+            get_pending_config_update_address(shared_policy_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*shared_policy_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `ExecuteSharedPolicyUpdate` instruction.
+pub fn execute_shared_policy_update(
+    program_id: &Pubkey,
+    shared_policy_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports_destination_pubkey: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::ExecuteSharedPolicyUpdate.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*shared_policy_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new(
+            get_pending_config_update_address(shared_policy_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new(*lamports_destination_pubkey, false),
+        AccountMeta::new(
+            get_config_history_address(shared_policy_pubkey, program_id),
+            false,
+        ),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates a `CancelSharedPolicyUpdate` instruction.
+pub fn cancel_shared_policy_update(
+    program_id: &Pubkey,
+    shared_policy_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    lamports_destination_pubkey: &Pubkey,
+) -> Instruction {
+    let data = CivicTransferHookInstruction::CancelSharedPolicyUpdate.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(
+            get_pending_config_update_address(shared_policy_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*shared_policy_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new(*lamports_destination_pubkey, false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Creates an `InitializeSharedPolicyHistory` instruction.
+pub fn initialize_shared_policy_history(
+    program_id: &Pubkey,
+    shared_policy_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Instruction {
+    let data = CivicTransferHookInstruction::InitializeSharedPolicyHistory.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(
+            get_config_history_address(shared_policy_pubkey, program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*shared_policy_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, signer_pubkeys.is_empty()),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(
+        signer_pubkeys
+            .iter()
+            .map(|signer_pubkey| AccountMeta::new_readonly(**signer_pubkey, true)),
+    );
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use {super::*, spl_transfer_hook_interface::NAMESPACE, solana_program::hash};
 
     #[test]
     fn initialize_validation_pubkeys_packing() {
-        let check = CivicTransferHookInstruction::InitializeExtraAccountMetas;
+        let gatekeeper_network = Pubkey::new_unique();
+        let check = CivicTransferHookInstruction::InitializeExtraAccountMetas { gatekeeper_network };
         let packed = check.pack();
         // Please use INITIALIZE_EXTRA_ACCOUNT_METAS_DISCRIMINATOR in your program,
         // the following is just for test purposes
@@ -113,8 +1796,71 @@ mod test {
         let discriminator = &preimage.as_ref()[..ArrayDiscriminator::LENGTH];
         let mut expect = vec![];
         expect.extend_from_slice(discriminator.as_ref());
+        expect.extend_from_slice(gatekeeper_network.as_ref());
         assert_eq!(packed, expect);
         let unpacked = CivicTransferHookInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
     }
+
+    #[test]
+    fn config_instructions_packing() {
+        for check in [
+            CivicTransferHookInstruction::MigrateExtraAccountMetas,
+            CivicTransferHookInstruction::UpdateConfig {
+                update: ConfigUpdate::SetGatekeeperNetworks(vec![
+                    Pubkey::new_unique(),
+                    Pubkey::new_unique(),
+                ]),
+            },
+            CivicTransferHookInstruction::CheckEligibility { amount: 1_000 },
+            CivicTransferHookInstruction::CreateTransferPermit {
+                source: Pubkey::new_unique(),
+                destination: Pubkey::new_unique(),
+                amount: 1_000,
+                expiry: 1_700_000_000,
+            },
+            CivicTransferHookInstruction::CloseTransferPermit,
+            CivicTransferHookInstruction::InitializeSourceState,
+            CivicTransferHookInstruction::RotateTravelRuleBuffer,
+            CivicTransferHookInstruction::CloseTravelRuleBuffer,
+            CivicTransferHookInstruction::InitializeMultisig {
+                m: 2,
+                signers: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            },
+            CivicTransferHookInstruction::QueueConfigUpdate {
+                update: ConfigUpdate::SetTimelock(86_400),
+            },
+            CivicTransferHookInstruction::ExecuteConfigUpdate,
+            CivicTransferHookInstruction::CancelConfigUpdate,
+            CivicTransferHookInstruction::InitializeConfigHistory,
+            CivicTransferHookInstruction::ThawAccount,
+            CivicTransferHookInstruction::FreezeAccount,
+            CivicTransferHookInstruction::RecordRevokedPass,
+            CivicTransferHookInstruction::CloseRevocationRecord,
+            CivicTransferHookInstruction::IssueTokenPass,
+            CivicTransferHookInstruction::CreateSharedPolicy {
+                id: 7,
+                policy: TransferPolicy {
+                    gatekeeper_networks: vec![Pubkey::new_unique()],
+                    ..TransferPolicy::default()
+                },
+            },
+            CivicTransferHookInstruction::UpdateSharedPolicy {
+                update: ConfigUpdate::SetDenyList(vec![Pubkey::new_unique()]),
+            },
+            CivicTransferHookInstruction::CloseSharedPolicy,
+            CivicTransferHookInstruction::SyncExtraAccountMetas,
+            CivicTransferHookInstruction::AuthorityThawAccount,
+            CivicTransferHookInstruction::QueueSharedPolicyUpdate {
+                update: ConfigUpdate::SetAllowList(vec![Pubkey::new_unique()]),
+            },
+            CivicTransferHookInstruction::ExecuteSharedPolicyUpdate,
+            CivicTransferHookInstruction::CancelSharedPolicyUpdate,
+            CivicTransferHookInstruction::InitializeSharedPolicyHistory,
+        ] {
+            let packed = check.pack();
+            let unpacked = CivicTransferHookInstruction::unpack(&packed).unwrap();
+            assert_eq!(unpacked, check);
+        }
+    }
 }
@@ -7,8 +7,17 @@
 #![deny(missing_docs)]
 #![cfg_attr(not(test), forbid(unsafe_code))]
 
-pub mod processor;
+pub mod approval;
+#[cfg(feature = "cpi")]
+pub mod cpi;
+pub mod error;
+pub mod event;
+pub mod extra_account_metas;
 pub mod instruction;
+pub mod pass;
+pub mod processor;
+pub mod rules;
+pub mod state;
 
 #[cfg(not(feature = "no-entrypoint"))]
 mod entrypoint;
@@ -0,0 +1,185 @@
+//! Evaluation of gateway tokens (passes) presented to the hook
+
+use {
+    crate::{
+        error::CivicTransferHookError,
+        processor::{GATEWAY_PROGRAM_ID, GATEWAY_TOKEN_ADDRESS_SEED},
+        state::{PassStateAction, PassStatePolicy},
+    },
+    solana_gateway::state::{GatewayToken, GatewayTokenState},
+    solana_program::{
+        account_info::AccountInfo, borsh0_10::try_from_slice_unchecked, clock::UnixTimestamp,
+        program_error::ProgramError, pubkey::Pubkey,
+    },
+};
+
+/// Get the address of the gateway token of `owner` in `gatekeeper_network`,
+/// assuming the default (zero) seed offset
+pub fn get_gateway_token_address(owner: &Pubkey, gatekeeper_network: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            owner.as_ref(),
+            GATEWAY_TOKEN_ADDRESS_SEED,
+            &[0; 8],
+            gatekeeper_network.as_ref(),
+        ],
+        &GATEWAY_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Reads the expiry time of the gateway token at `gateway_token_info`, if it has one
+pub fn gateway_token_expiry(gateway_token_info: &AccountInfo) -> Option<UnixTimestamp> {
+    if *gateway_token_info.owner != GATEWAY_PROGRAM_ID {
+        return None;
+    }
+    let data = gateway_token_info.try_borrow_data().ok()?;
+    try_from_slice_unchecked::<GatewayToken>(&data)
+        .ok()?
+        .expire_time
+}
+
+/// The state of a pass, as seen by the hook
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PassState {
+    /// No gateway token exists for the owner in the network
+    Missing,
+    /// The gateway token has been revoked by its gatekeeper
+    Revoked,
+    /// The gateway token has been frozen by its gatekeeper
+    Frozen,
+    /// The gateway token is active, but expired
+    Expired,
+    /// The gateway token is active and has not expired
+    Active,
+}
+
+impl PassState {
+    /// Reads the state of the gateway token at `gateway_token_info`, which must
+    /// belong to `owner` and `gatekeeper_network` to count as present.
+    ///
+    /// `expiry_grace_seconds` is added to the expiry time before comparing it to `now`.
+    pub fn from_account_info(
+        gateway_token_info: &AccountInfo,
+        owner: &Pubkey,
+        gatekeeper_network: &Pubkey,
+        now: UnixTimestamp,
+        expiry_grace_seconds: u32,
+    ) -> Self {
+        if *gateway_token_info.owner != GATEWAY_PROGRAM_ID || gateway_token_info.data_is_empty() {
+            return Self::Missing;
+        }
+        let gateway_token = match gateway_token_info
+            .try_borrow_data()
+            .ok()
+            .and_then(|data| try_from_slice_unchecked::<GatewayToken>(&data).ok())
+        {
+            Some(gateway_token) => gateway_token,
+            None => return Self::Missing,
+        };
+        if gateway_token.owner_wallet != *owner
+            || gateway_token.gatekeeper_network != *gatekeeper_network
+        {
+            return Self::Missing;
+        }
+        match gateway_token.state {
+            GatewayTokenState::Revoked => Self::Revoked,
+            GatewayTokenState::Frozen => Self::Frozen,
+            GatewayTokenState::Active => match gateway_token.expire_time {
+                Some(expire_time)
+                    if expire_time.saturating_add(expiry_grace_seconds as UnixTimestamp) < now =>
+                {
+                    Self::Expired
+                }
+                _ => Self::Active,
+            },
+        }
+    }
+
+    /// Reads the state of a gateway token that may belong to any of `gatekeeper_networks`,
+    /// returning the best state found
+    pub fn for_networks(
+        gateway_token_info: &AccountInfo,
+        owner: &Pubkey,
+        gatekeeper_networks: &[Pubkey],
+        now: UnixTimestamp,
+        expiry_grace_seconds: u32,
+    ) -> Self {
+        gatekeeper_networks
+            .iter()
+            .map(|gatekeeper_network| {
+                Self::from_account_info(
+                    gateway_token_info,
+                    owner,
+                    gatekeeper_network,
+                    now,
+                    expiry_grace_seconds,
+                )
+            })
+            .fold(Self::Missing, Self::best)
+    }
+
+    /// Ranks states from least to most usable, so that the best pass across
+    /// several networks decides the outcome
+    fn rank(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Picks the most usable of two states
+    pub fn best(self, other: Self) -> Self {
+        if other.rank() > self.rank() {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Checks the state against the policy of the mint
+    pub fn check(&self, policy: &PassStatePolicy) -> Result<(), ProgramError> {
+        let (action, error) = match self {
+            Self::Active => return Ok(()),
+            Self::Missing => return Err(CivicTransferHookError::PassMissing.into()),
+            Self::Revoked => (policy.revoked, CivicTransferHookError::PassRevoked),
+            Self::Frozen => (policy.frozen, CivicTransferHookError::PassFrozen),
+            Self::Expired => (policy.expired, CivicTransferHookError::PassExpired),
+        };
+        match action {
+            PassStateAction::Allow => Ok(()),
+            PassStateAction::Reject => Err(error.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn best_state_wins() {
+        assert_eq!(PassState::Missing.best(PassState::Frozen), PassState::Frozen);
+        assert_eq!(PassState::Active.best(PassState::Expired), PassState::Active);
+        assert_eq!(PassState::Revoked.best(PassState::Missing), PassState::Revoked);
+    }
+
+    #[test]
+    fn policy_decides_inactive_states() {
+        let policy = PassStatePolicy {
+            frozen: PassStateAction::Allow,
+            ..PassStatePolicy::default()
+        };
+        assert_eq!(PassState::Active.check(&policy), Ok(()));
+        assert_eq!(PassState::Frozen.check(&policy), Ok(()));
+        assert_eq!(
+            PassState::Revoked.check(&policy),
+            Err(CivicTransferHookError::PassRevoked.into())
+        );
+        assert_eq!(
+            PassState::Expired.check(&policy),
+            Err(CivicTransferHookError::PassExpired.into())
+        );
+        assert_eq!(
+            PassState::Missing.check(&policy),
+            Err(CivicTransferHookError::PassMissing.into())
+        );
+    }
+}
@@ -1,22 +1,29 @@
 //! Program state processor
+use borsh::BorshSerialize;
 use solana_gateway::Gateway;
 use spl_tlv_account_resolution::account::ExtraAccountMeta;
-use spl_tlv_account_resolution::seeds::Seed;
 use spl_tlv_account_resolution::state::ExtraAccountMetaList;
+use spl_type_length_value::state::TlvStateBorrowed;
 use {
     solana_program::{
         account_info::{next_account_info, AccountInfo},
+        clock::{Clock, UnixTimestamp},
         entrypoint::ProgramResult,
+        hash::Hash,
         msg,
-        program::invoke_signed,
+        program::{invoke_signed, set_return_data},
         program_error::ProgramError,
+        program_option::COption,
         pubkey::Pubkey,
         system_instruction,
+        sysvar::Sysvar,
     },
     spl_token_2022::{
         extension::{
+            permanent_delegate::PermanentDelegate, transfer_fee::TransferFeeConfig,
             transfer_hook::TransferHookAccount, BaseStateWithExtensions, StateWithExtensions,
         },
+        instruction::{freeze_account, thaw_account},
         state::{Account, Mint},
     },
     spl_transfer_hook_interface::{
@@ -26,7 +33,34 @@ use {
         instruction::{ExecuteInstruction, TransferHookInstruction},
     },
 };
-use crate::instruction::CivicTransferHookInstruction;
+use crate::approval::find_approvals;
+use crate::error::CivicTransferHookError;
+use crate::event::{AdminAction, Event, SharedPolicyAction};
+use crate::extra_account_metas::{
+    build_extra_account_metas, build_legacy_extra_account_metas, ExecuteAccountsLayout,
+    NetworkAccounts, LEGACY_EXTRA_ACCOUNT_METAS_LEN, SHARED_POLICY_INDEX,
+};
+use crate::instruction::{CivicTransferHookInstruction, EligibilityVerdict};
+use crate::pass::{gateway_token_expiry, get_gateway_token_address, PassState};
+use crate::rules::{RuleContext, RuleSet};
+use crate::state::{
+    collect_config_history_signer_seeds, collect_gatekeeper_authority_signer_seeds,
+    collect_hook_authority_signer_seeds,
+    collect_mint_config_signer_seeds, collect_pending_config_update_signer_seeds,
+    collect_source_state_signer_seeds, collect_transfer_permit_signer_seeds,
+    collect_revocation_record_signer_seeds, collect_shared_policy_signer_seeds,
+    get_config_history_address_and_bump_seed,
+    get_gatekeeper_authority_address_and_bump_seed, get_hook_authority_address_and_bump_seed,
+    get_mint_config_address_and_bump_seed,
+    get_revocation_record_address_and_bump_seed,
+    get_pending_config_update_address_and_bump_seed, get_shared_policy_address_and_bump_seed,
+    get_source_state_address_and_bump_seed, get_transfer_permit_address_and_bump_seed,
+    ConfigChangeKind, ConfigHistory, ConfigHistoryEntry, ConfigUpdate, EnforcementMode, MintConfig, Multisig, PendingConfigUpdate,
+    PermanentDelegatePolicy, ProgramAccount, RevocationRecord, Role, SharedPolicy, SourceState,
+    TransferPermit, TransferPolicy,
+    TravelRuleBuffer, TravelRuleRecord, FLAG_REQUIRE_ASSOCIATED_DESTINATION,
+    FLAG_VERIFY_DELEGATE,
+};
 
 /// The seed literal for deriving the gateway token account address.
 /// Defined here: https://github.com/identity-com/on-chain-identity-gateway/blob/develop/solana/program/src/state.rs#L20
@@ -42,6 +76,15 @@ pub const GATEWAY_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
     62, 157,  73,  19, 220, 196, 171,  94
 ]);
 
+/// The associated token account program, used to derive canonical token accounts.
+/// ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    140, 151,  37, 143,  78,  36, 137, 241,
+    187,  61,  16,  41,  20, 142,  13, 131,
+     11,  90,  19, 153, 218, 255,  16, 132,
+      4, 142, 123, 216, 219, 233, 248,  89
+]);
+
 fn check_token_account_is_transferring(account_info: &AccountInfo) -> Result<(), ProgramError> {
     let account_data = account_info.try_borrow_data()?;
     let token_account = StateWithExtensions::<Account>::unpack(&account_data)?;
@@ -53,23 +96,441 @@ fn check_token_account_is_transferring(account_info: &AccountInfo) -> Result<(),
     }
 }
 
+/// Checks that the mint and token accounts belong to Token-2022,
+/// and that both token accounts hold tokens of the mint
+fn check_token_accounts(
+    mint_info: &AccountInfo,
+    source_account_info: &AccountInfo,
+    destination_account_info: &AccountInfo,
+) -> ProgramResult {
+    for account_info in [mint_info, source_account_info, destination_account_info] {
+        if *account_info.owner != spl_token_2022::id() {
+            return Err(CivicTransferHookError::IncorrectTokenProgram.into());
+        }
+    }
+    for account_info in [source_account_info, destination_account_info] {
+        check_token_account_mint(mint_info, account_info)?;
+    }
+    Ok(())
+}
+
+/// Checks that a token account holds tokens of the mint
+fn check_token_account_mint(mint_info: &AccountInfo, account_info: &AccountInfo) -> ProgramResult {
+    let account_data = account_info.try_borrow_data()?;
+    let token_account = StateWithExtensions::<Account>::unpack(&account_data)?;
+    if token_account.base.mint != *mint_info.key {
+        return Err(CivicTransferHookError::TokenAccountMintMismatch.into());
+    }
+    Ok(())
+}
+
+/// Reads the token balance of a token account
+fn token_account_amount(account_info: &AccountInfo) -> Result<u64, ProgramError> {
+    let account_data = account_info.try_borrow_data()?;
+    let token_account = StateWithExtensions::<Account>::unpack(&account_data)?;
+    Ok(token_account.base.amount)
+}
+
+/// Reads the extra account metas stored in a validation account
+fn stored_extra_account_metas(data: &[u8]) -> Result<Vec<ExtraAccountMeta>, ProgramError> {
+    let state = TlvStateBorrowed::unpack(data)?;
+    let extra_account_metas =
+        ExtraAccountMetaList::unpack_with_tlv_state::<ExecuteInstruction>(&state)?;
+    Ok(extra_account_metas.data().to_vec())
+}
+
+/// Returns the account at `index`, or an error if the account list is too short
+fn account_at<'a, 'b>(
+    accounts: &'a [AccountInfo<'b>],
+    index: u8,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    accounts
+        .get(index as usize)
+        .ok_or(ProgramError::NotEnoughAccountKeys)
+}
+
+/// Allocates a PDA owned by this program. The account must already hold
+/// enough lamports to be rent-exempt at `size`.
+fn allocate_and_assign(
+    program_id: &Pubkey,
+    account_info: &AccountInfo,
+    size: usize,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    msg!("Allocating {} bytes", size);
+    invoke_signed(
+        &system_instruction::allocate(account_info.key, size as u64),
+        &[account_info.clone()],
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account_info.key, program_id),
+        &[account_info.clone()],
+        &[signer_seeds],
+    )
+}
+
+/// Writes a mint config, resizing the account to fit
+fn write_mint_config(config_info: &AccountInfo, config: &MintConfig) -> ProgramResult {
+    let packed = config.pack()?;
+    config_info.realloc(packed.len(), false)?;
+    let mut data = config_info.try_borrow_mut_data()?;
+    data.copy_from_slice(&packed);
+    Ok(())
+}
+
+/// Writes a shared policy, resizing the account to fit
+fn write_shared_policy(shared_policy_info: &AccountInfo, shared_policy: &SharedPolicy) -> ProgramResult {
+    let packed = shared_policy.pack()?;
+    shared_policy_info.realloc(packed.len(), false)?;
+    let mut data = shared_policy_info.try_borrow_mut_data()?;
+    data.copy_from_slice(&packed);
+    Ok(())
+}
+
+/// (Re)writes the extra account metas, resizing the account to fit
+fn write_extra_account_metas(
+    extra_account_metas_info: &AccountInfo,
+    extra_account_metas: &[ExtraAccountMeta],
+) -> ProgramResult {
+    let account_size = ExtraAccountMetaList::size_of(extra_account_metas.len())?;
+    extra_account_metas_info.realloc(account_size, false)?;
+    let mut data = extra_account_metas_info.try_borrow_mut_data()?;
+    data.fill(0);
+    ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, extra_account_metas)
+}
+
+/// Closes an account owned by this program, moving its lamports to `lamports_destination_info`
+fn close_account(
+    account_info: &AccountInfo,
+    lamports_destination_info: &AccountInfo,
+) -> ProgramResult {
+    let lamports = account_info.lamports();
+    let destination_lamports = lamports_destination_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **account_info.try_borrow_mut_lamports()? = 0;
+    **lamports_destination_info.try_borrow_mut_lamports()? = destination_lamports;
+    account_info.realloc(0, false)
+}
+
+/// Checks that the mint config account is the PDA for the mint,
+/// returning its bump seed
+fn check_mint_config_address(
+    program_id: &Pubkey,
+    mint_info: &AccountInfo,
+    config_info: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (expected_config_address, bump_seed) =
+        get_mint_config_address_and_bump_seed(mint_info.key, program_id);
+    if expected_config_address != *config_info.key {
+        return Err(CivicTransferHookError::IncorrectMintConfig.into());
+    }
+    Ok(bump_seed)
+}
+
+/// Replaces the policy of a config linked to a shared policy with the
+/// shared policy, checking that the account is the one the config links to
+fn apply_shared_policy(
+    program_id: &Pubkey,
+    config: &mut MintConfig,
+    shared_policy_info: &AccountInfo,
+) -> ProgramResult {
+    if config.shared_policy != Some(*shared_policy_info.key) {
+        return Err(CivicTransferHookError::IncorrectSharedPolicy.into());
+    }
+    if shared_policy_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    config.policy = SharedPolicy::unpack(&shared_policy_info.try_borrow_data()?)?.policy;
+    Ok(())
+}
+
+/// Unpacks the shared policy of a config linked to one, which is the next
+/// account, checking that it is the one the config links to
+fn next_linked_shared_policy<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    config: &MintConfig,
+    account_info_iter: &mut I,
+) -> Result<Option<SharedPolicy>, ProgramError> {
+    if config.shared_policy.is_none() {
+        return Ok(None);
+    }
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    if config.shared_policy != Some(*shared_policy_info.key) {
+        return Err(CivicTransferHookError::IncorrectSharedPolicy.into());
+    }
+    if shared_policy_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    SharedPolicy::unpack(&shared_policy_info.try_borrow_data()?).map(Some)
+}
+
+/// Unpacks the config of a mint, checking its address. For a mint linked to a
+/// shared policy, the shared policy is the next account, and its policy
+/// replaces the policy of the config.
+fn next_mint_config<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    mint_info: &AccountInfo,
+    config_info: &AccountInfo,
+    account_info_iter: &mut I,
+) -> Result<MintConfig, ProgramError> {
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let mut config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    let shared_policy = next_linked_shared_policy(program_id, &config, account_info_iter)?;
+    if let Some(shared_policy) = shared_policy {
+        config.policy = shared_policy.policy;
+    }
+    Ok(config)
+}
+
+/// Checks that the hook authority account is the PDA for the mint, and
+/// the freeze authority of the mint, returning its bump seed
+fn check_hook_freeze_authority(
+    program_id: &Pubkey,
+    mint_info: &AccountInfo,
+    hook_authority_info: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (expected_hook_authority_address, bump_seed) =
+        get_hook_authority_address_and_bump_seed(mint_info.key, program_id);
+    if expected_hook_authority_address != *hook_authority_info.key {
+        return Err(CivicTransferHookError::IncorrectHookAuthority.into());
+    }
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    if mint.base.freeze_authority != COption::Some(*hook_authority_info.key) {
+        return Err(CivicTransferHookError::HookAuthorityNotFreezeAuthority.into());
+    }
+    Ok(bump_seed)
+}
+
+/// Checks that the config history account is the PDA for the mint,
+/// returning it if the history has been initialized
+fn check_config_history<'a, 'b>(
+    program_id: &Pubkey,
+    mint_info: &AccountInfo,
+    history_info: &'a AccountInfo<'b>,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    let (expected_history_address, _) =
+        get_config_history_address_and_bump_seed(mint_info.key, program_id);
+    if expected_history_address != *history_info.key {
+        return Err(CivicTransferHookError::IncorrectConfigHistory.into());
+    }
+    Ok((history_info.owner == program_id).then_some(history_info))
+}
+
+/// Appends an entry to a config history, growing the account until it
+/// holds the maximum number of entries
+fn record_config_change(
+    history_info: &AccountInfo,
+    actor: &Pubkey,
+    kind: ConfigChangeKind,
+    old_hash: Hash,
+    new_hash: Hash,
+) -> ProgramResult {
+    let mut history = ConfigHistory::unpack(&history_info.try_borrow_data()?)?;
+    let clock = Clock::get()?;
+    let entry = ConfigHistoryEntry {
+        slot: clock.slot,
+        unix_timestamp: clock.unix_timestamp,
+        actor: *actor,
+        kind,
+        old_hash,
+        new_hash,
+    };
+    if history.next_size() > history_info.data_len() {
+        history_info.realloc(history.next_size(), false)?;
+    }
+    history.push(&mut history_info.try_borrow_mut_data()?, &entry)
+}
+
+/// Writes a changed config and its extra account metas, recording the change
+/// in the config history if the mint has one. The metas of a mint linked to
+/// a shared policy are built with the policy of `shared_policy`.
+fn write_config_change(
+    config_info: &AccountInfo,
+    extra_account_metas_info: &AccountInfo,
+    history_info: Option<&AccountInfo>,
+    actor: &Pubkey,
+    kind: ConfigChangeKind,
+    old_hash: Hash,
+    config: &MintConfig,
+    shared_policy: Option<&SharedPolicy>,
+) -> ProgramResult {
+    write_mint_config(config_info, config)?;
+    let mut enforced_config = config.clone();
+    if let (Some(_), Some(shared_policy)) = (config.shared_policy, shared_policy) {
+        enforced_config.policy = shared_policy.policy.clone();
+    }
+    let (extra_account_metas, _) = build_extra_account_metas(&enforced_config)?;
+    write_extra_account_metas(extra_account_metas_info, &extra_account_metas)?;
+
+    if let Some(history_info) = history_info {
+        record_config_change(history_info, actor, kind, old_hash, config.settings_hash()?)?;
+    }
+    Ok(())
+}
+
+/// Checks that the authority has signed and is the mint authority,
+/// returning the mint authority
+fn check_mint_authority(
+    mint_info: &AccountInfo,
+    authority_info: &AccountInfo,
+) -> Result<Pubkey, ProgramError> {
+    // check that the mint authority is valid without fully deserializing
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    let mint_authority = mint
+        .base
+        .mint_authority
+        .ok_or(TransferHookError::MintHasNoMintAuthority)?;
+
+    // Check signers
+    if !authority_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *authority_info.key != mint_authority {
+        return Err(TransferHookError::IncorrectMintAuthority.into());
+    }
+    Ok(mint_authority)
+}
+
+/// Checks that the holder of `role` approved the instruction: either the holder
+/// signed, or it is a [Multisig](../state/struct.Multisig.html) and enough of its
+/// signers are among `signer_infos` and signed
+fn check_role(
+    program_id: &Pubkey,
+    config: &MintConfig,
+    role: Role,
+    authority_info: &AccountInfo,
+    signer_infos: &[AccountInfo],
+) -> ProgramResult {
+    if *authority_info.key != config.role_holder(role) {
+        msg!("{:?} role required", role);
+        return Err(CivicTransferHookError::IncorrectConfigAuthority.into());
+    }
+    check_approval(program_id, authority_info, signer_infos)
+}
+
+/// Checks that an authority approved the instruction, by signing or through
+/// its multisig signers, as for [check_role](fn.check_role.html)
+fn check_approval(
+    program_id: &Pubkey,
+    authority_info: &AccountInfo,
+    signer_infos: &[AccountInfo],
+) -> ProgramResult {
+    if authority_info.owner == program_id && !authority_info.data_is_empty() {
+        let multisig = Multisig::unpack(&authority_info.try_borrow_data()?)?;
+        let approvals = multisig.count_approvals(
+            signer_infos
+                .iter()
+                .filter(|signer_info| signer_info.is_signer)
+                .map(|signer_info| signer_info.key),
+        );
+        if approvals < multisig.m as usize {
+            msg!("{} of {} multisig signers signed", approvals, multisig.m);
+            return Err(CivicTransferHookError::NotEnoughMultisigSigners.into());
+        }
+        return Ok(());
+    }
+    if !authority_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// Logs an [AdminAction](../event/enum.Event.html) event naming the holder of `role`
+fn emit_admin_action(
+    mint_info: &AccountInfo,
+    authority_info: &AccountInfo,
+    role: Role,
+    action: AdminAction,
+) {
+    Event::AdminAction {
+        mint: *mint_info.key,
+        actor: *authority_info.key,
+        role,
+        action,
+    }
+    .emit();
+}
+
+/// Why a transfer is being checked
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CheckKind {
+    /// The token program is executing the transfer
+    Execute,
+    /// A caller wants to know whether the transfer would be allowed
+    Eligibility,
+}
+
 /// Processes an [Execute](enum.TransferHookInstruction.html) instruction.
 pub fn process_execute(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     amount: u64,
+) -> ProgramResult {
+    check_transfer(program_id, accounts, amount, CheckKind::Execute)?;
+
+    msg!("checked gateway token - hook complete");
+
+    Ok(())
+}
+
+/// Processes a [CheckEligibility](enum.CivicTransferHookInstruction.html) instruction.
+///
+/// The verdict is returned via return data rather than as an error,
+/// so that callers can act on it.
+pub fn process_check_eligibility(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let verdict = match check_transfer(program_id, accounts, amount, CheckKind::Eligibility) {
+        Ok(()) => EligibilityVerdict {
+            eligible: true,
+            error: 0,
+        },
+        Err(error) => {
+            msg!("transfer would fail: {}", error);
+            EligibilityVerdict {
+                eligible: false,
+                error: error.into(),
+            }
+        }
+    };
+    set_return_data(
+        &verdict
+            .try_to_vec()
+            .map_err(|e| ProgramError::BorshIoError(e.to_string()))?,
+    );
+    Ok(())
+}
+
+/// Validates a transfer against the extra account metas and config of its mint
+fn check_transfer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    kind: CheckKind,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
 
     let source_account_info = next_account_info(account_info_iter)?;
     let mint_info = next_account_info(account_info_iter)?;
     let destination_account_info = next_account_info(account_info_iter)?;
-    let _authority_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
     let extra_account_metas_info = next_account_info(account_info_iter)?;
 
     // Check that the accounts are properly in "transferring" mode
-    check_token_account_is_transferring(source_account_info)?;
-    check_token_account_is_transferring(destination_account_info)?;
+    if kind == CheckKind::Execute {
+        check_token_account_is_transferring(source_account_info)?;
+        check_token_account_is_transferring(destination_account_info)?;
+    }
 
     // check that the correct extra account metas pda and validation pubkeys are provided
     let expected_validation_address = get_extra_account_metas_address(mint_info.key, program_id);
@@ -77,7 +538,14 @@ pub fn process_execute(
         return Err(ProgramError::InvalidSeeds);
     }
 
+    check_token_accounts(mint_info, source_account_info, destination_account_info)?;
+
     let data = extra_account_metas_info.try_borrow_data()?;
+    let stored_extra_account_metas = stored_extra_account_metas(&data)?;
+    let extra_account_infos = account_info_iter.as_slice();
+    if extra_account_infos.len() < stored_extra_account_metas.len() {
+        return Err(CivicTransferHookError::NotEnoughExtraAccounts.into());
+    }
 
     msg!("Checking each extra account meta");
 
@@ -90,139 +558,2883 @@ pub fn process_execute(
 
     msg!("Checked extra account metas");
 
-    let extra_account_infos = account_info_iter.as_slice();
-    // the gatekeeper network that the gateway token must belong to (specified by the Token2022 token extra account metas)
-    let gatekeeper_network = extra_account_infos[0].key;
-    // the gateway token account
-    let gateway_token = &extra_account_infos[2];
-    // the owner of the gateway token account. Usually this would be the recipient owner.
-    // But in this case, it is the token account (see more details below)
-    let owner = destination_account_info;
-
-    msg!("checking gateway token: {}", gateway_token.key);
+    // Mints set up before mint configs existed have the gatekeeper network as their first extra account.
+    // Only this program can own the mint config, so the owner tells the two layouts apart.
+    match extra_account_infos.first() {
+        Some(config_info) if config_info.owner == program_id => {
+            let mut config =
+                MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+            let config_bump_seed = [config.bump_seed];
+            let config_address = Pubkey::create_program_address(
+                &collect_mint_config_signer_seeds(mint_info.key, &config_bump_seed),
+                program_id,
+            )?;
+            if config_address != *config_info.key {
+                return Err(CivicTransferHookError::IncorrectMintConfig.into());
+            }
+            if config.shared_policy.is_some() {
+                apply_shared_policy(
+                    program_id,
+                    &mut config,
+                    account_at(accounts, SHARED_POLICY_INDEX)?,
+                )?;
+            }
+            let (extra_account_metas, layout) = build_extra_account_metas(&config)?;
+            if stored_extra_account_metas != extra_account_metas {
+                return Err(CivicTransferHookError::UnsupportedExtraAccountMetas.into());
+            }
+            for (network, gatekeeper_network) in layout
+                .networks
+                .iter()
+                .zip(&config.policy.gatekeeper_networks)
+            {
+                if account_at(accounts, network.network)?.key != gatekeeper_network {
+                    return Err(CivicTransferHookError::GatekeeperNetworkMismatch.into());
+                }
+            }
 
-    Gateway::verify_gateway_token_account_info(
-        gateway_token,
-        &owner.key,
-        &gatekeeper_network,
-        None
-    )?;
+            // pausing overrides monitor mode, permits and approvals
+            if config.paused {
+                return Err(CivicTransferHookError::TransfersPaused.into());
+            }
 
-    msg!("checked gateway token - hook complete");
+            let transfer = Transfer {
+                program_id,
+                kind,
+                amount,
+                accounts,
+                source: source_account_info,
+                mint: mint_info,
+                destination: destination_account_info,
+                authority: authority_info,
+                config,
+                layout,
+                now: Clock::get()?.unix_timestamp,
+            };
+            if let Err(error) = transfer.check() {
+                if transfer.config.policy.enforcement != EnforcementMode::Monitor {
+                    return Err(error);
+                }
+                msg!("monitor mode: allowing transfer that failed with {}", error);
+                if kind == CheckKind::Execute {
+                    Event::TransferViolation {
+                        mint: *mint_info.key,
+                        source: *source_account_info.key,
+                        destination: *destination_account_info.key,
+                        authority: *authority_info.key,
+                        amount,
+                        error: error.into(),
+                    }
+                    .emit();
+                }
+            }
+            if kind == CheckKind::Execute {
+                transfer.record_outgoing_transfer()?;
+                transfer.update_holder_count()?;
+                transfer.record_travel_rule()?;
+                transfer.record_recovery()?;
+            }
+        }
+        _ => execute_legacy(
+            destination_account_info,
+            &stored_extra_account_metas,
+            extra_account_infos,
+        )?,
+    }
 
     Ok(())
 }
 
-/// Processes a [InitializeExtraAccountMetas](enum.TransferHookInstruction.html) instruction.
-pub fn process_initialize_extra_account_metas(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    gatekeeper_network: &Pubkey
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
+/// A transfer being checked against the config of its mint
+struct Transfer<'a, 'b> {
+    program_id: &'a Pubkey,
+    kind: CheckKind,
+    amount: u64,
+    accounts: &'a [AccountInfo<'b>],
+    source: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    destination: &'a AccountInfo<'b>,
+    authority: &'a AccountInfo<'b>,
+    config: MintConfig,
+    layout: ExecuteAccountsLayout,
+    now: UnixTimestamp,
+}
 
-    let extra_account_metas_info = next_account_info(account_info_iter)?;
-    let mint_info = next_account_info(account_info_iter)?;
-    let authority_info = next_account_info(account_info_iter)?;
-    let _system_program_info = next_account_info(account_info_iter)?;
+impl<'a, 'b> Transfer<'a, 'b> {
+    /// Runs every check required by the config
+    fn check(&self) -> ProgramResult {
+        if self.is_travel_rule_transfer() {
+            let (_, buffer) = self
+                .travel_rule_buffer()?
+                .ok_or(CivicTransferHookError::TravelRuleBufferMissing)?;
+            if buffer.is_full() {
+                return Err(CivicTransferHookError::TravelRuleBufferFull.into());
+            }
+        }
 
-    // check that the mint authority is valid without fully deserializing
-    let mint_data = mint_info.try_borrow_data()?;
-    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
-    let mint_authority = mint
-        .base
-        .mint_authority
-        .ok_or(TransferHookError::MintHasNoMintAuthority)?;
+        if let Some(holder_cap) = self.config.policy.holder_cap {
+            let (adds_holder, removes_holder) = self.holder_changes()?;
+            if adds_holder && !removes_holder && self.config.holder_count >= holder_cap {
+                msg!("{} holders, cap is {}", self.config.holder_count, holder_cap);
+                return Err(CivicTransferHookError::HolderCapReached.into());
+            }
+        }
 
-    // Check signers
-    if !authority_info.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    if *authority_info.key != mint_authority {
-        return Err(TransferHookError::IncorrectMintAuthority.into());
-    }
+        let is_permanent_delegate_transfer = self.is_permanent_delegate_transfer()?;
+        if is_permanent_delegate_transfer {
+            msg!("transfer by permanent delegate {}", self.authority.key);
+            match self.config.policy.permanent_delegate {
+                PermanentDelegatePolicy::Enforce => {}
+                PermanentDelegatePolicy::SkipPassChecks => return Ok(()),
+                PermanentDelegatePolicy::Reject => {
+                    return Err(CivicTransferHookError::PermanentDelegateTransferRejected.into())
+                }
+                PermanentDelegatePolicy::RecoveryOnly {
+                    quarantine,
+                    recovery_period_seconds,
+                } => return self.check_recovery(&quarantine, recovery_period_seconds),
+            }
+        }
 
-    // Check validation account
-    let (expected_validation_address, bump_seed) =
-        get_extra_account_metas_address_and_bump_seed(mint_info.key, program_id);
-    if expected_validation_address != *extra_account_metas_info.key {
-        return Err(ProgramError::InvalidSeeds);
-    }
+        let policy = &self.config.policy;
+        if policy.deny_list.contains(self.source.key)
+            || policy.deny_list.contains(self.destination.key)
+        {
+            return Err(CivicTransferHookError::AccountDenied.into());
+        }
+        let is_allowed_destination = policy.allow_list.contains(self.destination.key);
 
-    // The first extra account is the gatekeeper network.
-    // This is a fixed key i.e. the execute function requires literally this account for all transactions
-    let gkn_pod_account_meta = ExtraAccountMeta::new_with_pubkey(gatekeeper_network, false, false)?;
-    let gateway_program_extra_account_meta = ExtraAccountMeta::new_with_pubkey(&GATEWAY_PROGRAM_ID, false, false)?;
-    // The second extra account is the token recipient's gateway token. This is a PDA, derived from the recipient address and the gatekeeper network
-    let gt_extra_account_meta = ExtraAccountMeta::new_external_pda_with_seeds(6, &[
-        // gateway token owner - usually this is the user address, in this case, the recipient.
-        // however, since the token owner is not present in the transfer instruction, we will instead use the
-        // recipient token account.
-        // This means that the gateway token must be associated with the token account, rather than the user.
-        // A separate service (can be on- or off-chain) is needed to associate a gateway token with a token account,
-        // if the owner has one.
-        Seed::AccountKey { index: 2 },
-        // literal seed
-        Seed::Literal { bytes: GATEWAY_TOKEN_ADDRESS_SEED.into() },
-        // configurable seed (we assume 0 here for simplicity)
-        Seed::Literal { bytes: [0; 8].into() },
-        // gkn
-        Seed::AccountKey { index: 5 }
-    ], false, false)?;
-
-    let extra_account_metas = &[
-        gkn_pod_account_meta,
-        gateway_program_extra_account_meta,
-        gt_extra_account_meta
-    ];
+        // in issuer-only mode, tokens only move out of issuer accounts,
+        // or back to the issuer through the permanent delegate
+        if !self.config.policy.issuer_accounts.is_empty()
+            && !is_permanent_delegate_transfer
+            && !self.config.policy.issuer_accounts.contains(self.source.key)
+        {
+            return Err(CivicTransferHookError::SourceNotIssuer.into());
+        }
 
-    // Create the account
-    let bump_seed = [bump_seed];
-    let signer_seeds = collect_extra_account_metas_signer_seeds(mint_info.key, &bump_seed);
-    let length = extra_account_metas.len();
-    let account_size = ExtraAccountMetaList::size_of(length)?;
-    msg!("Allocating {} bytes", account_size);
-    invoke_signed(
-        &system_instruction::allocate(extra_account_metas_info.key, account_size as u64),
-        &[extra_account_metas_info.clone()],
-        &[&signer_seeds],
-    )?;
-    invoke_signed(
-        &system_instruction::assign(extra_account_metas_info.key, program_id),
-        &[extra_account_metas_info.clone()],
-        &[&signer_seeds],
-    )?;
+        if self.config.policy.has_flag(FLAG_REQUIRE_ASSOCIATED_DESTINATION)
+            && !self.is_associated_destination()?
+        {
+            return Err(CivicTransferHookError::DestinationNotAssociatedTokenAccount.into());
+        }
 
-    // copy the pod_account_metas into the account
-    let mut data = extra_account_metas_info.try_borrow_mut_data()?;
-    ExtraAccountMetaList::init::<ExecuteInstruction>(
-        &mut data,
-        extra_account_metas,
-    )?;
+        // permits and approvals only stand in for the passes of the parties
+        self.check_passes(is_allowed_destination)
+            .or_else(|error| self.use_permit(error))
+            .or_else(|error| self.use_approval(error))?;
 
-    Ok(())
-}
+        if self.config.policy.transfer_cooldown_seconds > 0 {
+            let source_state = self
+                .source_state()?
+                .ok_or(CivicTransferHookError::SourceStateNotInitialized)?;
+            if !source_state.cooldown_elapsed(self.config.policy.transfer_cooldown_seconds, self.now)
+            {
+                msg!("last transfer at {}", source_state.last_transfer_time);
+                return Err(CivicTransferHookError::TransferCooldownActive.into());
+            }
+        }
 
-/// Processes an [Instruction](enum.Instruction.html).
-pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
-    let transfer_hook_instruction_result = TransferHookInstruction::unpack(input);
+        Ok(())
+    }
 
-    if let Ok(instruction) = transfer_hook_instruction_result {
-        match instruction {
-            TransferHookInstruction::Execute { amount } => {
-                msg!("Instruction: Execute");
-                return process_execute(program_id, accounts, amount);
-            }
-            _ => {}
+    /// The source state of the source, if the layout has one and it has been created
+    fn source_state(&self) -> Result<Option<SourceState>, ProgramError> {
+        let source_state_info = match self.layout.source_state {
+            Some(index) => account_at(self.accounts, index)?,
+            None => return Ok(None),
+        };
+        // the address was checked against the extra account metas
+        if source_state_info.owner != self.program_id {
+            return Ok(None);
         }
+        SourceState::unpack(&source_state_info.try_borrow_data()?).map(Some)
     }
 
-    let instruction = CivicTransferHookInstruction::unpack(input)?;
+    /// Checks the passes of the recipient and delegate, and the route between
+    /// the networks of the sender and recipient
+    fn check_passes(&self, is_allowed_destination: bool) -> ProgramResult {
+        // the recipient must hold a valid gateway token in at least one of the accepted networks,
+        // or satisfy the rules of the policy, unless the compliance officer allowed the destination
+        if !is_allowed_destination {
+            if let Some(rules) = &self.config.policy.rules {
+                self.check_rules(rules)?;
+            } else {
+                let pass_state = self.best_pass_state(self.destination.key, |network| {
+                    Some(network.destination_gateway_token)
+                })?;
+                if pass_state != PassState::Active {
+                    msg!("recipient pass is {:?}", pass_state);
+                }
+                pass_state.check(&self.config.policy.pass_states)?;
+            }
+        }
 
-    match instruction {
-        CivicTransferHookInstruction::InitializeExtraAccountMetas { gatekeeper_network } => {
-            msg!("Instruction: InitializeExtraAccountMetas");
-            process_initialize_extra_account_metas(program_id, accounts, &gatekeeper_network)
+        if self.config.policy.has_flag(FLAG_VERIFY_DELEGATE) && self.is_delegate_transfer()? {
+            let pass_state = self.best_pass_state(self.authority.key, |network| {
+                network.authority_gateway_token
+            })?;
+            if pass_state != PassState::Active {
+                msg!("delegate pass is {:?}", pass_state);
+            }
+            pass_state.check(&self.config.policy.pass_states)?;
         }
-    }
-}
\ No newline at end of file
+
+        if !self.config.policy.transfer_matrix.is_empty() && !is_allowed_destination {
+            let sender_networks =
+                self.valid_networks(self.source.key, |network| network.source_gateway_token)?;
+            let recipient_networks = self.valid_networks(self.destination.key, |network| {
+                Some(network.destination_gateway_token)
+            })?;
+            if !self
+                .config
+                .policy
+                .allows_route(sender_networks, recipient_networks)
+            {
+                msg!(
+                    "sender networks {:#b}, recipient networks {:#b}",
+                    sender_networks,
+                    recipient_networks
+                );
+                return Err(CivicTransferHookError::TransferRouteNotAllowed.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the rules of the policy, which replace the recipient pass check
+    fn check_rules(&self, rules: &RuleSet) -> ProgramResult {
+        let source_networks = if rules.uses_source_passes() {
+            self.valid_networks(self.source.key, |network| network.source_gateway_token)?
+        } else {
+            0
+        };
+        let context = RuleContext {
+            source: self.source.key,
+            destination: self.destination.key,
+            authority: self.authority.key,
+            amount: self.amount,
+            now: self.now,
+            source_networks,
+            destination_networks: self.valid_networks(self.destination.key, |network| {
+                Some(network.destination_gateway_token)
+            })?,
+        };
+        if !rules.evaluate(&context) {
+            msg!(
+                "sender networks {:#b}, recipient networks {:#b}",
+                context.source_networks,
+                context.destination_networks
+            );
+            return Err(CivicTransferHookError::RulesNotSatisfied.into());
+        }
+        Ok(())
+    }
+
+    /// Records the time of the transfer in the source state, if the layout has one.
+    /// Transfers let through without a source state, e.g. by a permit, are not recorded.
+    fn record_outgoing_transfer(&self) -> ProgramResult {
+        let source_state_info = match self.layout.source_state {
+            Some(index) => account_at(self.accounts, index)?,
+            None => return Ok(()),
+        };
+        if source_state_info.owner != self.program_id {
+            return Ok(());
+        }
+        let mut data = source_state_info.try_borrow_mut_data()?;
+        let mut source_state = SourceState::unpack(&data)?;
+        source_state.last_transfer_time = self.now;
+        source_state.pack_into_slice(&mut data)
+    }
+
+    /// The networks in which `owner` holds a pass accepted by the policy, as a
+    /// bitmask over network indices, reading the gateway token selected by
+    /// `select_gateway_token` in each network
+    fn valid_networks(
+        &self,
+        owner: &Pubkey,
+        select_gateway_token: fn(&NetworkAccounts) -> Option<u8>,
+    ) -> Result<u8, ProgramError> {
+        let mut networks = 0;
+        for (i, network) in self.layout.networks.iter().enumerate() {
+            let gateway_token = match select_gateway_token(network) {
+                Some(index) => account_at(self.accounts, index)?,
+                None => continue,
+            };
+            let gatekeeper_network = account_at(self.accounts, network.network)?;
+            let pass_state = PassState::from_account_info(
+                gateway_token,
+                owner,
+                gatekeeper_network.key,
+                self.now,
+                self.config.policy.pass_states.expiry_grace_seconds,
+            );
+            if pass_state.check(&self.config.policy.pass_states).is_ok() {
+                networks |= 1 << i;
+            }
+        }
+        Ok(networks)
+    }
+
+    /// Whether the transfer must be recorded for the travel rule
+    fn is_travel_rule_transfer(&self) -> bool {
+        matches!(self.config.policy.travel_rule_threshold, Some(threshold) if self.amount >= threshold)
+    }
+
+    /// The travel rule buffer of the mint and its header, if the layout has one
+    fn travel_rule_buffer(
+        &self,
+    ) -> Result<Option<(&'a AccountInfo<'b>, TravelRuleBuffer)>, ProgramError> {
+        let buffer_info = match self.layout.travel_rule_buffer {
+            Some(index) => account_at(self.accounts, index)?,
+            None => return Ok(None),
+        };
+        if buffer_info.owner != self.program_id {
+            return Err(CivicTransferHookError::IncorrectTravelRuleBuffer.into());
+        }
+        let buffer = TravelRuleBuffer::unpack(&buffer_info.try_borrow_data()?)?;
+        if buffer.mint != *self.mint.key {
+            return Err(CivicTransferHookError::IncorrectTravelRuleBuffer.into());
+        }
+        Ok(Some((buffer_info, buffer)))
+    }
+
+    /// Writes a travel rule record for the transfer, if it is above the threshold.
+    /// In monitor mode, a transfer that could not be recorded has already been
+    /// logged as a violation and goes through unrecorded.
+    fn record_travel_rule(&self) -> ProgramResult {
+        if !self.is_travel_rule_transfer() {
+            return Ok(());
+        }
+        let result = match self.travel_rule_buffer()? {
+            Some((_, buffer)) if buffer.is_full() => {
+                Err(CivicTransferHookError::TravelRuleBufferFull)
+            }
+            Some(buffer) => Ok(buffer),
+            None => Err(CivicTransferHookError::TravelRuleBufferMissing),
+        };
+        let (buffer_info, mut buffer) = match result {
+            Ok(buffer) => buffer,
+            Err(error) if self.config.policy.enforcement == EnforcementMode::Monitor => {
+                msg!("monitor mode: transfer not recorded: {}", error);
+                return Ok(());
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let record = TravelRuleRecord {
+            slot: Clock::get()?.slot,
+            source: *self.source.key,
+            destination: *self.destination.key,
+            amount: self.amount,
+            source_gateway_token: self
+                .present_gateway_token(|network| network.source_gateway_token)?,
+            destination_gateway_token: self
+                .present_gateway_token(|network| Some(network.destination_gateway_token))?,
+        };
+        buffer.push(&mut buffer_info.try_borrow_mut_data()?, &record)
+    }
+
+    /// The first gateway token selected by `select_gateway_token` that exists,
+    /// or the default pubkey if there is none
+    fn present_gateway_token(
+        &self,
+        select_gateway_token: fn(&NetworkAccounts) -> Option<u8>,
+    ) -> Result<Pubkey, ProgramError> {
+        for index in self.layout.networks.iter().filter_map(select_gateway_token) {
+            let gateway_token = account_at(self.accounts, index)?;
+            if *gateway_token.owner == GATEWAY_PROGRAM_ID && !gateway_token.data_is_empty() {
+                return Ok(*gateway_token.key);
+            }
+        }
+        Ok(Pubkey::default())
+    }
+
+    /// Whether the transfer gives the destination its first tokens, and whether
+    /// it takes the last tokens of the source
+    fn holder_changes(&self) -> Result<(bool, bool), ProgramError> {
+        if self.source.key == self.destination.key {
+            return Ok((false, false));
+        }
+        Ok(holder_changes(
+            self.kind,
+            self.amount,
+            self.received_amount()?,
+            token_account_amount(self.source)?,
+            token_account_amount(self.destination)?,
+        ))
+    }
+
+    /// The amount credited to the destination, net of the transfer fee of the mint
+    fn received_amount(&self) -> Result<u64, ProgramError> {
+        let mint_data = self.mint.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        let fee = match mint.get_extension::<TransferFeeConfig>() {
+            Ok(transfer_fee_config) => transfer_fee_config
+                .calculate_epoch_fee(Clock::get()?.epoch, self.amount)
+                .ok_or(ProgramError::InvalidArgument)?,
+            Err(_) => 0,
+        };
+        Ok(self.amount.saturating_sub(fee))
+    }
+
+    /// Updates the holder count in the config, if the policy has a holder cap
+    fn update_holder_count(&self) -> ProgramResult {
+        if self.config.policy.holder_cap.is_none() {
+            return Ok(());
+        }
+        let (adds_holder, removes_holder) = self.holder_changes()?;
+        if adds_holder == removes_holder {
+            return Ok(());
+        }
+        // only the count is written back, as the policy of a linked mint
+        // comes from its shared policy
+        let config_info = account_at(self.accounts, self.layout.config)?;
+        let mut config = MintConfig::unpack(&config_info.try_borrow_data()?)?;
+        let holder_count = if adds_holder {
+            self.config.holder_count.saturating_add(1)
+        } else {
+            self.config.holder_count.saturating_sub(1)
+        };
+        config.track_holder_count(holder_count, Clock::get()?.slot);
+        // configs from before the checkpoints grow on their first write
+        write_mint_config(config_info, &config)
+    }
+
+    /// Lets a transfer whose pass checks failed with `error` through if a transfer
+    /// permit covers it, using up the permitted amount. Returns `error` otherwise.
+    fn use_permit(&self, error: ProgramError) -> ProgramResult {
+        if !is_pass_error(&error) {
+            return Err(error);
+        }
+        let permit_info = match self.layout.transfer_permit {
+            Some(index) => account_at(self.accounts, index)?,
+            None => return Err(error),
+        };
+        // the address was checked against the extra account metas
+        if permit_info.owner != self.program_id || permit_info.data_is_empty() {
+            return Err(error);
+        }
+        let mut permit = TransferPermit::unpack(&permit_info.try_borrow_data()?)?;
+        if !permit.covers(self.amount, self.now) {
+            msg!(
+                "transfer permit {} does not cover the transfer",
+                permit_info.key
+            );
+            return Err(error);
+        }
+
+        msg!("transfer allowed by permit {}", permit_info.key);
+        if self.kind == CheckKind::Execute {
+            permit.remaining_amount -= self.amount;
+            permit.pack_into_slice(&mut permit_info.try_borrow_mut_data()?)?;
+        }
+        Ok(())
+    }
+
+    /// Lets a transfer whose pass checks failed with `error` through if the
+    /// transaction carries an unexpired approval for it, signed by the approver
+    /// of the mint, using up the approval nonce of the source. Returns `error` otherwise.
+    fn use_approval(&self, error: ProgramError) -> ProgramResult {
+        if !is_pass_error(&error) {
+            return Err(error);
+        }
+        let (approver, instructions_sysvar, source_state_index) = match (
+            &self.config.policy.approver,
+            self.layout.instructions_sysvar,
+            self.layout.source_state,
+        ) {
+            (Some(approver), Some(index), Some(source_state_index)) => {
+                (approver, account_at(self.accounts, index)?, source_state_index)
+            }
+            _ => return Err(error),
+        };
+        let Some(mut source_state) = self.source_state()? else {
+            msg!("approvals require a source state");
+            return Err(error);
+        };
+        let approved = find_approvals(instructions_sysvar, approver)?
+            .iter()
+            .any(|approval| {
+                approval.covers(
+                    self.mint.key,
+                    self.source.key,
+                    self.destination.key,
+                    self.amount,
+                    source_state.approval_nonce,
+                    self.now,
+                )
+            });
+        if !approved {
+            return Err(error);
+        }
+
+        msg!("transfer allowed by approver {}", approver);
+        if self.kind == CheckKind::Execute {
+            source_state.approval_nonce += 1;
+            let source_state_info = account_at(self.accounts, source_state_index)?;
+            source_state.pack_into_slice(&mut source_state_info.try_borrow_mut_data()?)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the destination is the associated token account of its owner for the mint
+    fn is_associated_destination(&self) -> Result<bool, ProgramError> {
+        let destination_data = self.destination.try_borrow_data()?;
+        let destination = StateWithExtensions::<Account>::unpack(&destination_data)?;
+        let (associated_address, _) = Pubkey::find_program_address(
+            &[
+                destination.base.owner.as_ref(),
+                self.destination.owner.as_ref(),
+                self.mint.key.as_ref(),
+            ],
+            &ASSOCIATED_TOKEN_PROGRAM_ID,
+        );
+        Ok(associated_address == *self.destination.key)
+    }
+
+    /// Whether the transfer authority is a delegate rather than the owner of the source account
+    fn is_delegate_transfer(&self) -> Result<bool, ProgramError> {
+        let source_data = self.source.try_borrow_data()?;
+        let source = StateWithExtensions::<Account>::unpack(&source_data)?;
+        Ok(source.base.owner != *self.authority.key)
+    }
+
+    /// Checks a transfer by a recovery-only permanent delegate: the tokens must go
+    /// to the quarantine account, out of a token account that has had no valid
+    /// pass since its revocation was recorded, at least the recovery period ago
+    fn check_recovery(&self, quarantine: &Pubkey, recovery_period_seconds: u32) -> ProgramResult {
+        if self.destination.key != quarantine {
+            return Err(CivicTransferHookError::RecoveryNotAllowed.into());
+        }
+        let revocation_record = self
+            .revocation_record()?
+            .ok_or(CivicTransferHookError::RevocationNotRecorded)?;
+        if !revocation_record.recovery_period_elapsed(recovery_period_seconds, self.now) {
+            msg!("revoked since {}", revocation_record.revoked_since);
+            return Err(CivicTransferHookError::RecoveryPeriodNotElapsed.into());
+        }
+        let pass_state =
+            self.best_pass_state(self.source.key, |network| network.source_gateway_token)?;
+        if pass_state.check(&self.config.policy.pass_states).is_ok() {
+            return Err(CivicTransferHookError::PassStillValid.into());
+        }
+        Ok(())
+    }
+
+    /// The revocation record of the source, if the layout has one and it has been created
+    fn revocation_record(&self) -> Result<Option<RevocationRecord>, ProgramError> {
+        let record_info = match self.layout.revocation_record {
+            Some(index) => account_at(self.accounts, index)?,
+            None => return Ok(None),
+        };
+        if record_info.owner != self.program_id || record_info.data_is_empty() {
+            return Ok(None);
+        }
+        let record = RevocationRecord::unpack(&record_info.try_borrow_data()?)?;
+        if record.mint != *self.mint.key || record.account != *self.source.key {
+            return Err(CivicTransferHookError::IncorrectRevocationRecord.into());
+        }
+        Ok(Some(record))
+    }
+
+    /// Logs a recovery event if the transfer was made by a recovery-only permanent delegate
+    fn record_recovery(&self) -> ProgramResult {
+        let PermanentDelegatePolicy::RecoveryOnly { quarantine, .. } =
+            self.config.policy.permanent_delegate
+        else {
+            return Ok(());
+        };
+        if !self.is_permanent_delegate_transfer()? {
+            return Ok(());
+        }
+        Event::Recovered {
+            mint: *self.mint.key,
+            source: *self.source.key,
+            quarantine,
+            authority: *self.authority.key,
+            amount: self.amount,
+            source_gateway_token: self.present_gateway_token(|network| {
+                network.source_gateway_token
+            })?,
+            revoked_since: self
+                .revocation_record()?
+                .map(|record| record.revoked_since)
+                .unwrap_or_default(),
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Whether the transfer authority is the permanent delegate of the mint,
+    /// as used by issuers to claw back tokens
+    fn is_permanent_delegate_transfer(&self) -> Result<bool, ProgramError> {
+        let mint_data = self.mint.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        let permanent_delegate = mint
+            .get_extension::<PermanentDelegate>()
+            .ok()
+            .and_then(|extension| Option::<Pubkey>::from(extension.delegate));
+        Ok(permanent_delegate == Some(*self.authority.key))
+    }
+
+    /// The most usable state among the passes of `owner` in the accepted networks,
+    /// reading the gateway token selected by `select_gateway_token` in each network
+    fn best_pass_state(
+        &self,
+        owner: &Pubkey,
+        select_gateway_token: fn(&NetworkAccounts) -> Option<u8>,
+    ) -> Result<PassState, ProgramError> {
+        let mut pass_state = PassState::Missing;
+        for network in &self.layout.networks {
+            let gatekeeper_network = account_at(self.accounts, network.network)?;
+            let gateway_token = match select_gateway_token(network) {
+                Some(index) => account_at(self.accounts, index)?,
+                None => continue,
+            };
+
+            msg!("checking gateway token: {}", gateway_token.key);
+
+            pass_state = pass_state.best(PassState::from_account_info(
+                gateway_token,
+                owner,
+                gatekeeper_network.key,
+                self.now,
+                self.config.policy.pass_states.expiry_grace_seconds,
+            ));
+            if pass_state == PassState::Active {
+                break;
+            }
+        }
+        Ok(pass_state)
+    }
+}
+
+/// Whether a transfer of `amount`, of which the destination receives
+/// `received_amount` after fees, gives the destination its first tokens, and
+/// whether it takes the last tokens of the source, given the balances of the
+/// token accounts when the transfer is checked
+fn holder_changes(
+    kind: CheckKind,
+    amount: u64,
+    received_amount: u64,
+    source_amount: u64,
+    destination_amount: u64,
+) -> (bool, bool) {
+    let (source_before, destination_before) = match kind {
+        // the token program has already moved the tokens when executing
+        CheckKind::Execute => (
+            source_amount.saturating_add(amount),
+            destination_amount.saturating_sub(received_amount),
+        ),
+        CheckKind::Eligibility => (source_amount, destination_amount),
+    };
+    (
+        received_amount > 0 && destination_before == 0,
+        amount > 0 && source_before == amount,
+    )
+}
+
+/// Whether `error` was raised by the pass checks of a transfer, the only
+/// failures that permits and approvals can override
+fn is_pass_error(error: &ProgramError) -> bool {
+    [
+        CivicTransferHookError::PassMissing,
+        CivicTransferHookError::PassRevoked,
+        CivicTransferHookError::PassFrozen,
+        CivicTransferHookError::PassExpired,
+        CivicTransferHookError::RulesNotSatisfied,
+        CivicTransferHookError::TransferRouteNotAllowed,
+    ]
+    .into_iter()
+    .any(|pass_error| *error == pass_error.into())
+}
+
+/// Checks a transfer for a mint that still uses the original three-account layout
+fn execute_legacy(
+    destination_account_info: &AccountInfo,
+    stored_extra_account_metas: &[ExtraAccountMeta],
+    extra_account_infos: &[AccountInfo],
+) -> ProgramResult {
+    if stored_extra_account_metas.len() != LEGACY_EXTRA_ACCOUNT_METAS_LEN
+        || extra_account_infos.len() < LEGACY_EXTRA_ACCOUNT_METAS_LEN
+    {
+        return Err(CivicTransferHookError::UnsupportedExtraAccountMetas.into());
+    }
+    // the gatekeeper network that the gateway token must belong to (specified by the Token2022 token extra account metas)
+    let gatekeeper_network = extra_account_infos[0].key;
+    if gatekeeper_network.to_bytes() != stored_extra_account_metas[0].address_config {
+        return Err(CivicTransferHookError::GatekeeperNetworkMismatch.into());
+    }
+    // the gateway token account
+    let gateway_token = &extra_account_infos[2];
+    // the owner of the gateway token account. Usually this would be the recipient owner.
+    // But in this case, it is the token account (see more details in extra_account_metas.rs)
+    let owner = destination_account_info;
+
+    msg!("checking gateway token: {}", gateway_token.key);
+
+    Gateway::verify_gateway_token_account_info(
+        gateway_token,
+        &owner.key,
+        &gatekeeper_network,
+        None
+    )?;
+
+    Ok(())
+}
+
+/// Processes a [InitializeExtraAccountMetas](enum.TransferHookInstruction.html) instruction.
+pub fn process_initialize_extra_account_metas(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    gatekeeper_network: &Pubkey
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let extra_account_metas_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    let mint_authority = check_mint_authority(mint_info, authority_info)?;
+
+    // Check validation account
+    let (expected_validation_address, bump_seed) =
+        get_extra_account_metas_address_and_bump_seed(mint_info.key, program_id);
+    if expected_validation_address != *extra_account_metas_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let config_bump_seed = check_mint_config_address(program_id, mint_info, config_info)?;
+
+    // Create the mint config. The mint authority is the initial config authority.
+    let config = MintConfig::new(
+        *mint_info.key,
+        config_bump_seed,
+        mint_authority,
+        TransferPolicy {
+            gatekeeper_networks: vec![*gatekeeper_network],
+            ..TransferPolicy::default()
+        },
+    );
+    config.policy.validate()?;
+    let config_bump_seed = [config_bump_seed];
+    let config_signer_seeds = collect_mint_config_signer_seeds(mint_info.key, &config_bump_seed);
+    allocate_and_assign(program_id, config_info, config.packed_len()?, &config_signer_seeds)?;
+    write_mint_config(config_info, &config)?;
+
+    let (extra_account_metas, _) = build_extra_account_metas(&config)?;
+
+    // Create the account
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_extra_account_metas_signer_seeds(mint_info.key, &bump_seed);
+    let account_size = ExtraAccountMetaList::size_of(extra_account_metas.len())?;
+    allocate_and_assign(program_id, extra_account_metas_info, account_size, &signer_seeds)?;
+
+    // copy the pod_account_metas into the account
+    write_extra_account_metas(extra_account_metas_info, &extra_account_metas)
+}
+
+/// Processes a [MigrateExtraAccountMetas](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_migrate_extra_account_metas(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let extra_account_metas_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    // Check validation account
+    let expected_validation_address = get_extra_account_metas_address(mint_info.key, program_id);
+    if expected_validation_address != *extra_account_metas_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if extra_account_metas_info.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let config_bump_seed = check_mint_config_address(program_id, mint_info, config_info)?;
+
+    let config = if config_info.owner == program_id {
+        // Already migrated: bring the metas in line with the config
+        let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+        check_role(
+            program_id,
+            &config,
+            Role::Admin,
+            authority_info,
+            account_info_iter.as_slice(),
+        )?;
+        if config.shared_policy.is_some() {
+            msg!("Use SyncExtraAccountMetas for mints linked to a shared policy");
+            return Err(CivicTransferHookError::MintLinkedToSharedPolicy.into());
+        }
+        emit_admin_action(
+            mint_info,
+            authority_info,
+            Role::Admin,
+            AdminAction::MigrateExtraAccountMetas,
+        );
+        config
+    } else {
+        let mint_authority = check_mint_authority(mint_info, authority_info)?;
+
+        // The original layout stores the gatekeeper network as its first extra account
+        let stored_extra_account_metas =
+            stored_extra_account_metas(&extra_account_metas_info.try_borrow_data()?)?;
+        if stored_extra_account_metas.len() != LEGACY_EXTRA_ACCOUNT_METAS_LEN {
+            return Err(CivicTransferHookError::UnsupportedExtraAccountMetas.into());
+        }
+        let gatekeeper_network = Pubkey::new_from_array(stored_extra_account_metas[0].address_config);
+        if stored_extra_account_metas != build_legacy_extra_account_metas(&gatekeeper_network)? {
+            return Err(CivicTransferHookError::UnsupportedExtraAccountMetas.into());
+        }
+
+        msg!("Migrating gatekeeper network {}", gatekeeper_network);
+
+        let config = MintConfig::new(
+            *mint_info.key,
+            config_bump_seed,
+            mint_authority,
+            TransferPolicy {
+                gatekeeper_networks: vec![gatekeeper_network],
+                ..TransferPolicy::default()
+            },
+        );
+        let config_bump_seed = [config_bump_seed];
+        let config_signer_seeds =
+            collect_mint_config_signer_seeds(mint_info.key, &config_bump_seed);
+        allocate_and_assign(program_id, config_info, config.packed_len()?, &config_signer_seeds)?;
+        write_mint_config(config_info, &config)?;
+        config
+    };
+
+    let (extra_account_metas, _) = build_extra_account_metas(&config)?;
+    write_extra_account_metas(extra_account_metas_info, &extra_account_metas)
+}
+
+/// Processes an [UpdateConfig](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: &ConfigUpdate,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let extra_account_metas_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let history_info = next_account_info(account_info_iter)?;
+
+    let expected_validation_address = get_extra_account_metas_address(mint_info.key, program_id);
+    if expected_validation_address != *extra_account_metas_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id || extra_account_metas_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let history_info = check_config_history(program_id, mint_info, history_info)?;
+
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    let shared_policy_info = next_shared_policy(program_id, &config, update, account_info_iter)?;
+    check_role(
+        program_id,
+        &config,
+        update.required_role(),
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    let update = &update.resolve(&config, Clock::get()?.slot)?;
+
+    if config.timelock_seconds > 0 && !update.is_exempt_from_timelock() {
+        return Err(CivicTransferHookError::TimelockRequired.into());
+    }
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        update.required_role(),
+        AdminAction::UpdateConfig(update.clone()),
+    );
+
+    apply_config_update(
+        config_info,
+        extra_account_metas_info,
+        history_info,
+        shared_policy_info,
+        authority_info.key,
+        config,
+        update,
+    )
+}
+
+/// Returns the shared policy account following the fixed accounts of a config
+/// update: the shared policy the mint is linked to, or the one
+/// `ConfigUpdate::LinkSharedPolicy` links it to
+fn next_shared_policy<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    program_id: &Pubkey,
+    config: &MintConfig,
+    update: &ConfigUpdate,
+    account_info_iter: &mut I,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    let shared_policy = match update {
+        ConfigUpdate::LinkSharedPolicy(shared_policy) => *shared_policy,
+        _ => match config.shared_policy {
+            Some(shared_policy) => shared_policy,
+            None => return Ok(None),
+        },
+    };
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    if *shared_policy_info.key != shared_policy {
+        return Err(CivicTransferHookError::IncorrectSharedPolicy.into());
+    }
+    if shared_policy_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(Some(shared_policy_info))
+}
+
+/// Checks that an update can be made to a config given its shared policy:
+/// policy updates are refused while linked, and a timelocked mint cannot
+/// rely on a shared policy with a shorter delay, which would let its policy
+/// change sooner
+fn check_shared_policy_update(
+    config: &MintConfig,
+    update: &ConfigUpdate,
+    shared_policy: Option<&SharedPolicy>,
+) -> ProgramResult {
+    match update {
+        ConfigUpdate::LinkSharedPolicy(_) if config.shared_policy.is_some() => {
+            Err(CivicTransferHookError::MintLinkedToSharedPolicy.into())
+        }
+        ConfigUpdate::UnlinkSharedPolicy if config.shared_policy.is_none() => {
+            Err(CivicTransferHookError::MintNotLinkedToSharedPolicy.into())
+        }
+        _ if config.shared_policy.is_some() && update.is_policy_update() => {
+            Err(CivicTransferHookError::MintLinkedToSharedPolicy.into())
+        }
+        ConfigUpdate::LinkSharedPolicy(_) | ConfigUpdate::SetTimelock(_) => {
+            let timelock_seconds = match update {
+                ConfigUpdate::SetTimelock(seconds) => *seconds,
+                _ => config.timelock_seconds,
+            };
+            match shared_policy {
+                Some(shared_policy) if shared_policy.timelock_seconds < timelock_seconds => {
+                    msg!("Shared policy timelock {}s", shared_policy.timelock_seconds);
+                    Err(CivicTransferHookError::SharedPolicyTimelockTooShort.into())
+                }
+                _ => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Applies an update to a config, and rewrites the config and extra account
+/// metas. Linking and unlinking also mark the mint as linked or unlinked in the
+/// shared policy, which must have approved it.
+fn apply_config_update(
+    config_info: &AccountInfo,
+    extra_account_metas_info: &AccountInfo,
+    history_info: Option<&AccountInfo>,
+    shared_policy_info: Option<&AccountInfo>,
+    actor: &Pubkey,
+    mut config: MintConfig,
+    update: &ConfigUpdate,
+) -> ProgramResult {
+    let mut shared_policy = shared_policy_info
+        .map(|shared_policy_info| SharedPolicy::unpack(&shared_policy_info.try_borrow_data()?))
+        .transpose()?;
+    check_shared_policy_update(&config, update, shared_policy.as_ref())?;
+    let old_hash = config.settings_hash()?;
+
+    if let (Some(shared_policy_info), Some(shared_policy)) = (shared_policy_info, &mut shared_policy)
+    {
+        match update {
+            ConfigUpdate::LinkSharedPolicy(_) => {
+                shared_policy.set_linked(&config.mint, true)?;
+            }
+            ConfigUpdate::UnlinkSharedPolicy => {
+                shared_policy.set_linked(&config.mint, false)?;
+                // the mint keeps enforcing the shared policy until its admin changes it
+                config.policy = shared_policy.policy.clone();
+            }
+            _ => (),
+        }
+        shared_policy.pack_into_slice(&mut shared_policy_info.try_borrow_mut_data()?)?;
+    }
+
+    update.apply(&mut config)?;
+    write_config_change(
+        config_info,
+        extra_account_metas_info,
+        history_info,
+        actor,
+        update.kind(),
+        old_hash,
+        &config,
+        shared_policy.as_ref(),
+    )
+}
+
+/// Processes a [QueueConfigUpdate](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_queue_config_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: &ConfigUpdate,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let pending_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    let shared_policy_info = next_shared_policy(program_id, &config, update, account_info_iter)?;
+    check_role(
+        program_id,
+        &config,
+        update.required_role(),
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    // a reconciliation is queued as a correction, keeping the transfers
+    // made while it waits for the timelock
+    let update = &update.resolve(&config, Clock::get()?.slot)?;
+
+    let (expected_pending_address, bump_seed) =
+        get_pending_config_update_address_and_bump_seed(mint_info.key, program_id);
+    if expected_pending_address != *pending_info.key {
+        return Err(CivicTransferHookError::IncorrectPendingConfigUpdate.into());
+    }
+    // only one update can be queued at a time, cancel it to queue another
+    if pending_info.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    // fail now rather than after the delay if the update is invalid
+    let shared_policy = shared_policy_info
+        .map(|shared_policy_info| SharedPolicy::unpack(&shared_policy_info.try_borrow_data()?))
+        .transpose()?;
+    check_shared_policy_update(&config, update, shared_policy.as_ref())?;
+    update.apply(&mut config.clone())?;
+
+    let pending = PendingConfigUpdate {
+        mint: *mint_info.key,
+        update: update.clone(),
+        executable_at: Clock::get()?
+            .unix_timestamp
+            .saturating_add(config.timelock_seconds as UnixTimestamp),
+    };
+    msg!("Update executable at {}", pending.executable_at);
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        update.required_role(),
+        AdminAction::QueueConfigUpdate {
+            update: update.clone(),
+            executable_at: pending.executable_at,
+        },
+    );
+
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_pending_config_update_signer_seeds(mint_info.key, &bump_seed);
+    allocate_and_assign(program_id, pending_info, pending.packed_len()?, &signer_seeds)?;
+    pending.pack_into_slice(&mut pending_info.try_borrow_mut_data()?)
+}
+
+/// Processes an [ExecuteConfigUpdate](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_execute_config_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let extra_account_metas_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let pending_info = next_account_info(account_info_iter)?;
+    let lamports_destination_info = next_account_info(account_info_iter)?;
+    let history_info = next_account_info(account_info_iter)?;
+
+    let expected_validation_address = get_extra_account_metas_address(mint_info.key, program_id);
+    if expected_validation_address != *extra_account_metas_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id
+        || extra_account_metas_info.owner != program_id
+        || pending_info.owner != program_id
+    {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let history_info = check_config_history(program_id, mint_info, history_info)?;
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    let pending = PendingConfigUpdate::unpack(&pending_info.try_borrow_data()?)?;
+    if pending.mint != *mint_info.key {
+        return Err(CivicTransferHookError::IncorrectPendingConfigUpdate.into());
+    }
+    let shared_policy_info =
+        next_shared_policy(program_id, &config, &pending.update, account_info_iter)?;
+    check_role(
+        program_id,
+        &config,
+        pending.update.required_role(),
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+
+    if Clock::get()?.unix_timestamp < pending.executable_at {
+        msg!("Update executable at {}", pending.executable_at);
+        return Err(CivicTransferHookError::TimelockNotExpired.into());
+    }
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        pending.update.required_role(),
+        AdminAction::ExecuteConfigUpdate(pending.update.clone()),
+    );
+
+    close_account(pending_info, lamports_destination_info)?;
+    apply_config_update(
+        config_info,
+        extra_account_metas_info,
+        history_info,
+        shared_policy_info,
+        authority_info.key,
+        config,
+        &pending.update,
+    )
+}
+
+/// Processes a [CancelConfigUpdate](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_cancel_config_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let pending_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let lamports_destination_info = next_account_info(account_info_iter)?;
+
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id || pending_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    let pending = PendingConfigUpdate::unpack(&pending_info.try_borrow_data()?)?;
+    if pending.mint != *mint_info.key {
+        return Err(CivicTransferHookError::IncorrectPendingConfigUpdate.into());
+    }
+    check_role(
+        program_id,
+        &config,
+        pending.update.required_role(),
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        pending.update.required_role(),
+        AdminAction::CancelConfigUpdate(pending.update.clone()),
+    );
+
+    close_account(pending_info, lamports_destination_info)
+}
+
+/// Processes a [CreateTransferPermit](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_create_transfer_permit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    source: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+    expiry: UnixTimestamp,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let permit_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    check_role(
+        program_id,
+        &config,
+        Role::ComplianceOfficer,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        Role::ComplianceOfficer,
+        AdminAction::CreateTransferPermit {
+            source: *source,
+            destination: *destination,
+            amount,
+            expiry,
+        },
+    );
+
+    let (expected_permit_address, bump_seed) =
+        get_transfer_permit_address_and_bump_seed(mint_info.key, source, destination, program_id);
+    if expected_permit_address != *permit_info.key {
+        return Err(CivicTransferHookError::IncorrectTransferPermit.into());
+    }
+    if expiry < Clock::get()?.unix_timestamp {
+        return Err(CivicTransferHookError::TransferPermitExpired.into());
+    }
+
+    let permit = TransferPermit {
+        bump_seed,
+        mint: *mint_info.key,
+        source: *source,
+        destination: *destination,
+        remaining_amount: amount,
+        expiry,
+    };
+    // An existing permit for the same accounts is replaced
+    if permit_info.owner != program_id {
+        let bump_seed = [bump_seed];
+        let signer_seeds =
+            collect_transfer_permit_signer_seeds(mint_info.key, source, destination, &bump_seed);
+        allocate_and_assign(program_id, permit_info, permit.packed_len()?, &signer_seeds)?;
+    }
+    permit.pack_into_slice(&mut permit_info.try_borrow_mut_data()?)
+}
+
+/// Processes a [CloseTransferPermit](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_close_transfer_permit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let permit_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let lamports_destination_info = next_account_info(account_info_iter)?;
+
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id || permit_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    check_role(
+        program_id,
+        &config,
+        Role::ComplianceOfficer,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        Role::ComplianceOfficer,
+        AdminAction::CloseTransferPermit {
+            permit: *permit_info.key,
+        },
+    );
+
+    let permit = TransferPermit::unpack(&permit_info.try_borrow_data()?)?;
+    if permit.mint != *mint_info.key {
+        return Err(CivicTransferHookError::IncorrectTransferPermit.into());
+    }
+
+    close_account(permit_info, lamports_destination_info)
+}
+
+/// Processes an [InitializeSourceState](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_initialize_source_state(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let source_state_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let source_account_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+
+    let (expected_source_state_address, bump_seed) =
+        get_source_state_address_and_bump_seed(mint_info.key, source_account_info.key, program_id);
+    if expected_source_state_address != *source_state_info.key {
+        return Err(CivicTransferHookError::IncorrectSourceState.into());
+    }
+    if source_state_info.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    {
+        let source_data = source_account_info.try_borrow_data()?;
+        let source = StateWithExtensions::<Account>::unpack(&source_data)?;
+        if source.base.mint != *mint_info.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+    }
+
+    let source_state = SourceState {
+        bump_seed,
+        mint: *mint_info.key,
+        source: *source_account_info.key,
+        last_transfer_time: 0,
+        approval_nonce: 0,
+    };
+    let bump_seed = [bump_seed];
+    let signer_seeds =
+        collect_source_state_signer_seeds(mint_info.key, source_account_info.key, &bump_seed);
+    allocate_and_assign(program_id, source_state_info, source_state.packed_len()?, &signer_seeds)?;
+    source_state.pack_into_slice(&mut source_state_info.try_borrow_mut_data()?)
+}
+
+/// Processes a [RotateTravelRuleBuffer](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_rotate_travel_rule_buffer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let extra_account_metas_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let buffer_info = next_account_info(account_info_iter)?;
+    let history_info = next_account_info(account_info_iter)?;
+
+    let expected_validation_address = get_extra_account_metas_address(mint_info.key, program_id);
+    if expected_validation_address != *extra_account_metas_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id
+        || extra_account_metas_info.owner != program_id
+        || buffer_info.owner != program_id
+    {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let history_info = check_config_history(program_id, mint_info, history_info)?;
+
+    let mut config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    let shared_policy = next_linked_shared_policy(program_id, &config, account_info_iter)?;
+    check_role(
+        program_id,
+        &config,
+        Role::Admin,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        Role::Admin,
+        AdminAction::RotateTravelRuleBuffer {
+            buffer: *buffer_info.key,
+        },
+    );
+
+    {
+        let mut data = buffer_info.try_borrow_mut_data()?;
+        if data.iter().any(|byte| *byte != 0) {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let buffer = TravelRuleBuffer::new(*mint_info.key, data.len());
+        if buffer.capacity == 0 {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        msg!("Travel rule buffer {} holds {} records", buffer_info.key, buffer.capacity);
+        buffer.pack_into_slice(&mut data)?;
+    }
+
+    let old_hash = config.settings_hash()?;
+    config.travel_rule_buffer = Some(*buffer_info.key);
+    write_config_change(
+        config_info,
+        extra_account_metas_info,
+        history_info,
+        authority_info.key,
+        ConfigChangeKind::RotateTravelRuleBuffer,
+        old_hash,
+        &config,
+        shared_policy.as_ref(),
+    )
+}
+
+/// Processes a [CloseTravelRuleBuffer](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_close_travel_rule_buffer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let buffer_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let lamports_destination_info = next_account_info(account_info_iter)?;
+
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id || buffer_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    check_role(
+        program_id,
+        &config,
+        Role::Admin,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        Role::Admin,
+        AdminAction::CloseTravelRuleBuffer {
+            buffer: *buffer_info.key,
+        },
+    );
+
+    let buffer = TravelRuleBuffer::unpack(&buffer_info.try_borrow_data()?)?;
+    if buffer.mint != *mint_info.key || config.travel_rule_buffer == Some(*buffer_info.key) {
+        return Err(CivicTransferHookError::IncorrectTravelRuleBuffer.into());
+    }
+    msg!(
+        "Closing travel rule buffer with {} records written",
+        buffer.total_records
+    );
+
+    close_account(buffer_info, lamports_destination_info)
+}
+
+/// Processes an [InitializeMultisig](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_initialize_multisig(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    m: u8,
+    signers: Vec<Pubkey>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let multisig_info = next_account_info(account_info_iter)?;
+
+    if multisig_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // only the creator of the account can initialize it
+    if !multisig_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let multisig = Multisig::new(m, signers)?;
+
+    let mut data = multisig_info.try_borrow_mut_data()?;
+    if data.iter().any(|byte| *byte != 0) {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    multisig.pack_into_slice(&mut data)
+}
+
+/// Processes an [InitializeConfigHistory](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_initialize_config_history(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let history_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    check_role(
+        program_id,
+        &config,
+        Role::Admin,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+
+    let (expected_history_address, bump_seed) =
+        get_config_history_address_and_bump_seed(mint_info.key, program_id);
+    if expected_history_address != *history_info.key {
+        return Err(CivicTransferHookError::IncorrectConfigHistory.into());
+    }
+    if history_info.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        Role::Admin,
+        AdminAction::InitializeConfigHistory,
+    );
+
+    let history = ConfigHistory {
+        mint: *mint_info.key,
+        total_entries: 0,
+    };
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_config_history_signer_seeds(mint_info.key, &bump_seed);
+    allocate_and_assign(program_id, history_info, history.next_size(), &signer_seeds)?;
+    history.pack_into_slice(&mut history_info.try_borrow_mut_data()?)?;
+
+    // the first entry records the config in force when the history starts
+    let settings_hash = config.settings_hash()?;
+    record_config_change(
+        history_info,
+        authority_info.key,
+        ConfigChangeKind::InitializeHistory,
+        settings_hash,
+        settings_hash,
+    )
+}
+
+/// The best state among the passes of `owner` in the accepted networks of a config.
+///
+/// The gateway token of every accepted network must be presented, at its
+/// canonical address, so that a missing or revoked pass cannot be faked.
+fn canonical_pass_state(
+    config: &MintConfig,
+    owner: &Pubkey,
+    gateway_token_infos: &[AccountInfo],
+) -> Result<PassState, ProgramError> {
+    let networks = &config.policy.gatekeeper_networks;
+    if gateway_token_infos.len() != networks.len() {
+        return Err(CivicTransferHookError::NotEnoughExtraAccounts.into());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    let mut pass_state = PassState::Missing;
+    for (gateway_token_info, network) in gateway_token_infos.iter().zip(networks) {
+        if *gateway_token_info.key != get_gateway_token_address(owner, network) {
+            return Err(CivicTransferHookError::IncorrectGatewayToken.into());
+        }
+        pass_state = pass_state.best(PassState::from_account_info(
+            gateway_token_info,
+            owner,
+            network,
+            now,
+            config.policy.pass_states.expiry_grace_seconds,
+        ));
+    }
+    Ok(pass_state)
+}
+
+/// Processes a [ThawAccount](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_thaw_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let token_account_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let hook_authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let config = next_mint_config(program_id, mint_info, config_info, account_info_iter)?;
+    let gateway_token_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if *mint_info.owner != spl_token_2022::id() || *token_program_info.key != spl_token_2022::id()
+    {
+        return Err(CivicTransferHookError::IncorrectTokenProgram.into());
+    }
+    check_token_account_mint(mint_info, token_account_info)?;
+    let bump_seed = check_hook_freeze_authority(program_id, mint_info, hook_authority_info)?;
+
+    if config.policy.deny_list.contains(token_account_info.key) {
+        return Err(CivicTransferHookError::AccountDenied.into());
+    }
+    // token passes are issued to the token account itself
+    let pass_state = PassState::for_networks(
+        gateway_token_info,
+        token_account_info.key,
+        &config.policy.gatekeeper_networks,
+        Clock::get()?.unix_timestamp,
+        config.policy.pass_states.expiry_grace_seconds,
+    );
+    if pass_state != PassState::Active {
+        msg!("pass is {:?}", pass_state);
+    }
+    pass_state.check(&config.policy.pass_states)?;
+
+    thaw_with_hook_authority(
+        token_account_info,
+        mint_info,
+        hook_authority_info,
+        token_program_info,
+        bump_seed,
+    )?;
+
+    Event::AccountThawed {
+        mint: *mint_info.key,
+        account: *token_account_info.key,
+        gateway_token: *gateway_token_info.key,
+    }
+    .emit();
+    Ok(())
+}
+
+/// Processes an [AuthorityThawAccount](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_authority_thaw_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let token_account_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let hook_authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    if *mint_info.owner != spl_token_2022::id() || *token_program_info.key != spl_token_2022::id()
+    {
+        return Err(CivicTransferHookError::IncorrectTokenProgram.into());
+    }
+    check_token_account_mint(mint_info, token_account_info)?;
+    let bump_seed = check_hook_freeze_authority(program_id, mint_info, hook_authority_info)?;
+
+    check_mint_config_address(program_id, mint_info, config_info)?;
+    if config_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = MintConfig::unpack_for_mint(&config_info.try_borrow_data()?, mint_info.key)?;
+    check_role(
+        program_id,
+        &config,
+        Role::ComplianceOfficer,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    emit_admin_action(
+        mint_info,
+        authority_info,
+        Role::ComplianceOfficer,
+        AdminAction::ThawAccount {
+            account: *token_account_info.key,
+        },
+    );
+
+    thaw_with_hook_authority(
+        token_account_info,
+        mint_info,
+        hook_authority_info,
+        token_program_info,
+        bump_seed,
+    )
+}
+
+/// Thaws a token account, signing as the hook authority of the mint
+fn thaw_with_hook_authority<'a>(
+    token_account_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    hook_authority_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    bump_seed: u8,
+) -> ProgramResult {
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_hook_authority_signer_seeds(mint_info.key, &bump_seed);
+    invoke_signed(
+        &thaw_account(
+            token_program_info.key,
+            token_account_info.key,
+            mint_info.key,
+            hook_authority_info.key,
+            &[],
+        )?,
+        &[
+            token_account_info.clone(),
+            mint_info.clone(),
+            hook_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[&signer_seeds],
+    )
+}
+
+/// Processes a [FreezeAccount](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_freeze_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let token_account_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let hook_authority_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let config = next_mint_config(program_id, mint_info, config_info, account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let record_info = next_account_info(account_info_iter)?;
+    let gateway_token_infos = account_info_iter.as_slice();
+
+    if *mint_info.owner != spl_token_2022::id() || *token_program_info.key != spl_token_2022::id()
+    {
+        return Err(CivicTransferHookError::IncorrectTokenProgram.into());
+    }
+    check_token_account_mint(mint_info, token_account_info)?;
+    let bump_seed = check_hook_freeze_authority(program_id, mint_info, hook_authority_info)?;
+
+    // accounts that never need a pass are left alone
+    if config.policy.allow_list.contains(token_account_info.key)
+        || config.policy.issuer_accounts.contains(token_account_info.key)
+    {
+        return Err(CivicTransferHookError::PassStillValid.into());
+    }
+
+    // the rules replace the pass check and depend on the transfer (amount, time,
+    // counterparties), so a missing or revoked pass says nothing about the holder
+    if config.policy.rules.is_some() {
+        return Err(CivicTransferHookError::FreezeNotSupportedWithRules.into());
+    }
+
+    // the token program moves no tokens into or out of a frozen account, not even
+    // for the permanent delegate, so freezing would block recoveries
+    if let PermanentDelegatePolicy::RecoveryOnly { quarantine, .. } =
+        config.policy.permanent_delegate
+    {
+        let (expected_record_address, _) = get_revocation_record_address_and_bump_seed(
+            mint_info.key,
+            token_account_info.key,
+            program_id,
+        );
+        if expected_record_address != *record_info.key {
+            return Err(CivicTransferHookError::IncorrectRevocationRecord.into());
+        }
+        if *token_account_info.key == quarantine
+            || (record_info.owner == program_id && !record_info.data_is_empty())
+        {
+            return Err(CivicTransferHookError::AccountAwaitingRecovery.into());
+        }
+    }
+
+    let pass_state = canonical_pass_state(&config, token_account_info.key, gateway_token_infos)?;
+    if pass_state.check(&config.policy.pass_states).is_ok() {
+        return Err(CivicTransferHookError::PassStillValid.into());
+    }
+    msg!("pass is {:?}", pass_state);
+
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_hook_authority_signer_seeds(mint_info.key, &bump_seed);
+    invoke_signed(
+        &freeze_account(
+            token_program_info.key,
+            token_account_info.key,
+            mint_info.key,
+            hook_authority_info.key,
+            &[],
+        )?,
+        &[
+            token_account_info.clone(),
+            mint_info.clone(),
+            hook_authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        &[&signer_seeds],
+    )?;
+
+    Event::AccountFrozen {
+        mint: *mint_info.key,
+        account: *token_account_info.key,
+    }
+    .emit();
+    Ok(())
+}
+
+/// Processes a [RecordRevokedPass](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_record_revoked_pass(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let record_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let token_account_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let config = next_mint_config(program_id, mint_info, config_info, account_info_iter)?;
+    let recorder_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+    let gateway_token_infos = account_info_iter.as_slice();
+
+    if !recorder_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_token_account_mint(mint_info, token_account_info)?;
+
+    let (expected_record_address, bump_seed) = get_revocation_record_address_and_bump_seed(
+        mint_info.key,
+        token_account_info.key,
+        program_id,
+    );
+    if expected_record_address != *record_info.key {
+        return Err(CivicTransferHookError::IncorrectRevocationRecord.into());
+    }
+    if record_info.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let pass_state = canonical_pass_state(&config, token_account_info.key, gateway_token_infos)?;
+    if pass_state != PassState::Revoked {
+        msg!("pass is {:?}", pass_state);
+        return Err(CivicTransferHookError::PassNotRevoked.into());
+    }
+
+    let record = RevocationRecord {
+        bump_seed,
+        mint: *mint_info.key,
+        account: *token_account_info.key,
+        recorder: *recorder_info.key,
+        revoked_since: Clock::get()?.unix_timestamp,
+    };
+    msg!("Revocation recorded at {}", record.revoked_since);
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_revocation_record_signer_seeds(
+        mint_info.key,
+        token_account_info.key,
+        &bump_seed,
+    );
+    allocate_and_assign(program_id, record_info, record.packed_len()?, &signer_seeds)?;
+    record.pack_into_slice(&mut record_info.try_borrow_mut_data()?)
+}
+
+/// Processes a [CloseRevocationRecord](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_close_revocation_record(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let record_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let token_account_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let config = next_mint_config(program_id, mint_info, config_info, account_info_iter)?;
+    let recorder_info = next_account_info(account_info_iter)?;
+    let gateway_token_infos = account_info_iter.as_slice();
+
+    if record_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_token_account_mint(mint_info, token_account_info)?;
+
+    let record = RevocationRecord::unpack(&record_info.try_borrow_data()?)?;
+    if record.mint != *mint_info.key || record.account != *token_account_info.key {
+        return Err(CivicTransferHookError::IncorrectRevocationRecord.into());
+    }
+    if record.recorder != *recorder_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // the record is stale once the account is emptied or has a valid pass again
+    if token_account_amount(token_account_info)? > 0 {
+        let pass_state =
+            canonical_pass_state(&config, token_account_info.key, gateway_token_infos)?;
+        if pass_state.check(&config.policy.pass_states).is_err() {
+            return Err(CivicTransferHookError::RevocationStillInEffect.into());
+        }
+    }
+
+    close_account(record_info, recorder_info)
+}
+
+/// Processes an [IssueTokenPass](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_issue_token_pass(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let payer_info = next_account_info(account_info_iter)?;
+    let gateway_token_info = next_account_info(account_info_iter)?;
+    let token_account_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let config = next_mint_config(program_id, mint_info, config_info, account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let base_pass_info = next_account_info(account_info_iter)?;
+    let gatekeeper_authority_info = next_account_info(account_info_iter)?;
+    let gatekeeper_account_info = next_account_info(account_info_iter)?;
+    let network_info = next_account_info(account_info_iter)?;
+    let rent_sysvar_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let gateway_program_info = next_account_info(account_info_iter)?;
+
+    if *gateway_program_info.key != GATEWAY_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let base_pass_network = config
+        .base_pass_network
+        .ok_or(CivicTransferHookError::BasePassNetworkNotSet)?;
+    if !config.policy.gatekeeper_networks.contains(network_info.key) {
+        return Err(CivicTransferHookError::GatekeeperNetworkMismatch.into());
+    }
+
+    // only the owner of the token account can claim a pass for it
+    {
+        let account_data = token_account_info.try_borrow_data()?;
+        let token_account = StateWithExtensions::<Account>::unpack(&account_data)?;
+        if token_account.base.mint != *mint_info.key {
+            return Err(CivicTransferHookError::TokenAccountMintMismatch.into());
+        }
+        if token_account.base.owner != *owner_info.key {
+            return Err(CivicTransferHookError::TokenAccountOwnerMismatch.into());
+        }
+    }
+    if !owner_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if config.policy.deny_list.contains(token_account_info.key) {
+        return Err(CivicTransferHookError::AccountDenied.into());
+    }
+
+    let base_pass_state = PassState::from_account_info(
+        base_pass_info,
+        owner_info.key,
+        &base_pass_network,
+        Clock::get()?.unix_timestamp,
+        0,
+    );
+    if base_pass_state != PassState::Active {
+        msg!("base pass is {:?}", base_pass_state);
+        return Err(CivicTransferHookError::BasePassNotActive.into());
+    }
+
+    let (expected_gatekeeper_authority, bump_seed) =
+        get_gatekeeper_authority_address_and_bump_seed(mint_info.key, network_info.key, program_id);
+    if expected_gatekeeper_authority != *gatekeeper_authority_info.key {
+        return Err(CivicTransferHookError::IncorrectGatekeeperAuthority.into());
+    }
+
+    // the token pass lasts as long as the base pass it was issued for
+    let bump_seed = [bump_seed];
+    let signer_seeds =
+        collect_gatekeeper_authority_signer_seeds(mint_info.key, network_info.key, &bump_seed);
+    invoke_signed(
+        &solana_gateway::instruction::issue_vanilla(
+            payer_info.key,
+            token_account_info.key,
+            gatekeeper_account_info.key,
+            gatekeeper_authority_info.key,
+            network_info.key,
+            None,
+            gateway_token_expiry(base_pass_info),
+        ),
+        &[
+            payer_info.clone(),
+            gateway_token_info.clone(),
+            token_account_info.clone(),
+            gatekeeper_account_info.clone(),
+            gatekeeper_authority_info.clone(),
+            network_info.clone(),
+            rent_sysvar_info.clone(),
+            system_program_info.clone(),
+            gateway_program_info.clone(),
+        ],
+        &[&signer_seeds],
+    )?;
+
+    Event::TokenPassIssued {
+        mint: *mint_info.key,
+        account: *token_account_info.key,
+        owner: *owner_info.key,
+        base_pass: *base_pass_info.key,
+        gateway_token: *gateway_token_info.key,
+        network: *network_info.key,
+    }
+    .emit();
+    Ok(())
+}
+
+/// Processes a [CreateSharedPolicy](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_create_shared_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    id: u64,
+    policy: &TransferPolicy,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    let creator_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+
+    if !creator_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (expected_shared_policy_address, bump_seed) =
+        get_shared_policy_address_and_bump_seed(creator_info.key, id, program_id);
+    if expected_shared_policy_address != *shared_policy_info.key {
+        return Err(CivicTransferHookError::IncorrectSharedPolicy.into());
+    }
+    policy.validate()?;
+
+    let shared_policy = SharedPolicy {
+        bump_seed,
+        creator: *creator_info.key,
+        id,
+        authority: *creator_info.key,
+        policy: policy.clone(),
+        approved_mints: Vec::new(),
+        timelock_seconds: 0,
+    };
+    Event::SharedPolicyAction {
+        policy: *shared_policy_info.key,
+        actor: *creator_info.key,
+        action: SharedPolicyAction::Create,
+    }
+    .emit();
+
+    let id = id.to_le_bytes();
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_shared_policy_signer_seeds(creator_info.key, &id, &bump_seed);
+    allocate_and_assign(
+        program_id,
+        shared_policy_info,
+        shared_policy.packed_len()?,
+        &signer_seeds,
+    )?;
+    write_shared_policy(shared_policy_info, &shared_policy)
+}
+
+/// Unpacks a shared policy, checking that its authority approved the instruction
+fn check_shared_policy_authority(
+    program_id: &Pubkey,
+    shared_policy_info: &AccountInfo,
+    authority_info: &AccountInfo,
+    signer_infos: &[AccountInfo],
+) -> Result<SharedPolicy, ProgramError> {
+    if shared_policy_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let shared_policy = SharedPolicy::unpack(&shared_policy_info.try_borrow_data()?)?;
+    if *authority_info.key != shared_policy.authority {
+        return Err(CivicTransferHookError::IncorrectConfigAuthority.into());
+    }
+    check_approval(program_id, authority_info, signer_infos)?;
+    Ok(shared_policy)
+}
+
+/// Applies an update to a shared policy and rewrites it. The change is recorded
+/// in the history of the shared policy if it has one. Updates changing the
+/// accounts transfers need are followed by `SyncExtraAccountMetas` for each
+/// linked mint.
+fn apply_shared_policy_update(
+    shared_policy_info: &AccountInfo,
+    history_info: Option<&AccountInfo>,
+    actor: &Pubkey,
+    mut shared_policy: SharedPolicy,
+    update: &ConfigUpdate,
+) -> ProgramResult {
+    let old_hash = shared_policy.settings_hash()?;
+    shared_policy.apply(update)?;
+    msg!("Updating {} linked mints", shared_policy.linked_mints().count());
+    write_shared_policy(shared_policy_info, &shared_policy)?;
+
+    if let Some(history_info) = history_info {
+        record_config_change(
+            history_info,
+            actor,
+            update.kind(),
+            old_hash,
+            shared_policy.settings_hash()?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Processes an [UpdateSharedPolicy](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_update_shared_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: &ConfigUpdate,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let history_info = next_account_info(account_info_iter)?;
+
+    let history_info = check_config_history(program_id, shared_policy_info, history_info)?;
+    let shared_policy = check_shared_policy_authority(
+        program_id,
+        shared_policy_info,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    if shared_policy.timelock_seconds > 0 {
+        return Err(CivicTransferHookError::TimelockRequired.into());
+    }
+    Event::SharedPolicyAction {
+        policy: *shared_policy_info.key,
+        actor: *authority_info.key,
+        action: SharedPolicyAction::Update(update.clone()),
+    }
+    .emit();
+
+    apply_shared_policy_update(
+        shared_policy_info,
+        history_info,
+        authority_info.key,
+        shared_policy,
+        update,
+    )
+}
+
+/// Processes a [QueueSharedPolicyUpdate](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_queue_shared_policy_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update: &ConfigUpdate,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let pending_info = next_account_info(account_info_iter)?;
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+
+    let shared_policy = check_shared_policy_authority(
+        program_id,
+        shared_policy_info,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+
+    let (expected_pending_address, bump_seed) =
+        get_pending_config_update_address_and_bump_seed(shared_policy_info.key, program_id);
+    if expected_pending_address != *pending_info.key {
+        return Err(CivicTransferHookError::IncorrectPendingConfigUpdate.into());
+    }
+    // only one update can be queued at a time, cancel it to queue another
+    if pending_info.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    // fail now rather than after the delay if the update is invalid
+    shared_policy.clone().apply(update)?;
+
+    let pending = PendingConfigUpdate {
+        mint: *shared_policy_info.key,
+        update: update.clone(),
+        executable_at: Clock::get()?
+            .unix_timestamp
+            .saturating_add(shared_policy.timelock_seconds as UnixTimestamp),
+    };
+    msg!("Update executable at {}", pending.executable_at);
+    Event::SharedPolicyAction {
+        policy: *shared_policy_info.key,
+        actor: *authority_info.key,
+        action: SharedPolicyAction::QueueUpdate {
+            update: update.clone(),
+            executable_at: pending.executable_at,
+        },
+    }
+    .emit();
+
+    let bump_seed = [bump_seed];
+    let signer_seeds =
+        collect_pending_config_update_signer_seeds(shared_policy_info.key, &bump_seed);
+    allocate_and_assign(program_id, pending_info, pending.packed_len()?, &signer_seeds)?;
+    pending.pack_into_slice(&mut pending_info.try_borrow_mut_data()?)
+}
+
+/// Processes an [ExecuteSharedPolicyUpdate](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_execute_shared_policy_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let pending_info = next_account_info(account_info_iter)?;
+    let lamports_destination_info = next_account_info(account_info_iter)?;
+    let history_info = next_account_info(account_info_iter)?;
+
+    if pending_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let history_info = check_config_history(program_id, shared_policy_info, history_info)?;
+    let shared_policy = check_shared_policy_authority(
+        program_id,
+        shared_policy_info,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    let pending = PendingConfigUpdate::unpack(&pending_info.try_borrow_data()?)?;
+    if pending.mint != *shared_policy_info.key {
+        return Err(CivicTransferHookError::IncorrectPendingConfigUpdate.into());
+    }
+
+    if Clock::get()?.unix_timestamp < pending.executable_at {
+        msg!("Update executable at {}", pending.executable_at);
+        return Err(CivicTransferHookError::TimelockNotExpired.into());
+    }
+    Event::SharedPolicyAction {
+        policy: *shared_policy_info.key,
+        actor: *authority_info.key,
+        action: SharedPolicyAction::ExecuteUpdate(pending.update.clone()),
+    }
+    .emit();
+
+    close_account(pending_info, lamports_destination_info)?;
+    apply_shared_policy_update(
+        shared_policy_info,
+        history_info,
+        authority_info.key,
+        shared_policy,
+        &pending.update,
+    )
+}
+
+/// Processes a [CancelSharedPolicyUpdate](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_cancel_shared_policy_update(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let pending_info = next_account_info(account_info_iter)?;
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let lamports_destination_info = next_account_info(account_info_iter)?;
+
+    if pending_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_shared_policy_authority(
+        program_id,
+        shared_policy_info,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+    let pending = PendingConfigUpdate::unpack(&pending_info.try_borrow_data()?)?;
+    if pending.mint != *shared_policy_info.key {
+        return Err(CivicTransferHookError::IncorrectPendingConfigUpdate.into());
+    }
+    Event::SharedPolicyAction {
+        policy: *shared_policy_info.key,
+        actor: *authority_info.key,
+        action: SharedPolicyAction::CancelUpdate(pending.update),
+    }
+    .emit();
+
+    close_account(pending_info, lamports_destination_info)
+}
+
+/// Processes an [InitializeSharedPolicyHistory](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_initialize_shared_policy_history(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let history_info = next_account_info(account_info_iter)?;
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+
+    let shared_policy = check_shared_policy_authority(
+        program_id,
+        shared_policy_info,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+
+    let (expected_history_address, bump_seed) =
+        get_config_history_address_and_bump_seed(shared_policy_info.key, program_id);
+    if expected_history_address != *history_info.key {
+        return Err(CivicTransferHookError::IncorrectConfigHistory.into());
+    }
+    if history_info.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    Event::SharedPolicyAction {
+        policy: *shared_policy_info.key,
+        actor: *authority_info.key,
+        action: SharedPolicyAction::InitializeHistory,
+    }
+    .emit();
+
+    let history = ConfigHistory {
+        mint: *shared_policy_info.key,
+        total_entries: 0,
+    };
+    let bump_seed = [bump_seed];
+    let signer_seeds = collect_config_history_signer_seeds(shared_policy_info.key, &bump_seed);
+    allocate_and_assign(program_id, history_info, history.next_size(), &signer_seeds)?;
+    history.pack_into_slice(&mut history_info.try_borrow_mut_data()?)?;
+
+    // the first entry records the shared policy in force when the history starts
+    let settings_hash = shared_policy.settings_hash()?;
+    record_config_change(
+        history_info,
+        authority_info.key,
+        ConfigChangeKind::InitializeHistory,
+        settings_hash,
+        settings_hash,
+    )
+}
+
+/// Processes a [CloseSharedPolicy](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_close_shared_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let shared_policy_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let lamports_destination_info = next_account_info(account_info_iter)?;
+
+    let shared_policy = check_shared_policy_authority(
+        program_id,
+        shared_policy_info,
+        authority_info,
+        account_info_iter.as_slice(),
+    )?;
+
+    // linked mints would stop working without their policy
+    let linked_mints = shared_policy.linked_mints().count();
+    if linked_mints > 0 {
+        msg!("{} mints still linked", linked_mints);
+        return Err(CivicTransferHookError::SharedPolicyInUse.into());
+    }
+    Event::SharedPolicyAction {
+        policy: *shared_policy_info.key,
+        actor: *authority_info.key,
+        action: SharedPolicyAction::Close,
+    }
+    .emit();
+
+    close_account(shared_policy_info, lamports_destination_info)
+}
+
+/// Processes a [SyncExtraAccountMetas](enum.CivicTransferHookInstruction.html) instruction.
+pub fn process_sync_extra_account_metas(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let extra_account_metas_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let config_info = next_account_info(account_info_iter)?;
+    let config = next_mint_config(program_id, mint_info, config_info, account_info_iter)?;
+
+    let expected_validation_address = get_extra_account_metas_address(mint_info.key, program_id);
+    if expected_validation_address != *extra_account_metas_info.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if extra_account_metas_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let (extra_account_metas, _) = build_extra_account_metas(&config)?;
+    write_extra_account_metas(extra_account_metas_info, &extra_account_metas)
+}
+
+/// Processes an [Instruction](enum.Instruction.html).
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+    let transfer_hook_instruction_result = TransferHookInstruction::unpack(input);
+
+    if let Ok(instruction) = transfer_hook_instruction_result {
+        match instruction {
+            TransferHookInstruction::Execute { amount } => {
+                msg!("Instruction: Execute");
+                return process_execute(program_id, accounts, amount);
+            }
+            _ => {}
+        }
+    }
+
+    let instruction = CivicTransferHookInstruction::unpack(input)?;
+
+    match instruction {
+        CivicTransferHookInstruction::InitializeExtraAccountMetas { gatekeeper_network } => {
+            msg!("Instruction: InitializeExtraAccountMetas");
+            process_initialize_extra_account_metas(program_id, accounts, &gatekeeper_network)
+        }
+        CivicTransferHookInstruction::MigrateExtraAccountMetas => {
+            msg!("Instruction: MigrateExtraAccountMetas");
+            process_migrate_extra_account_metas(program_id, accounts)
+        }
+        CivicTransferHookInstruction::UpdateConfig { update } => {
+            msg!("Instruction: UpdateConfig");
+            process_update_config(program_id, accounts, &update)
+        }
+        CivicTransferHookInstruction::CheckEligibility { amount } => {
+            msg!("Instruction: CheckEligibility");
+            process_check_eligibility(program_id, accounts, amount)
+        }
+        CivicTransferHookInstruction::CreateTransferPermit {
+            source,
+            destination,
+            amount,
+            expiry,
+        } => {
+            msg!("Instruction: CreateTransferPermit");
+            process_create_transfer_permit(
+                program_id,
+                accounts,
+                &source,
+                &destination,
+                amount,
+                expiry,
+            )
+        }
+        CivicTransferHookInstruction::CloseTransferPermit => {
+            msg!("Instruction: CloseTransferPermit");
+            process_close_transfer_permit(program_id, accounts)
+        }
+        CivicTransferHookInstruction::InitializeSourceState => {
+            msg!("Instruction: InitializeSourceState");
+            process_initialize_source_state(program_id, accounts)
+        }
+        CivicTransferHookInstruction::RotateTravelRuleBuffer => {
+            msg!("Instruction: RotateTravelRuleBuffer");
+            process_rotate_travel_rule_buffer(program_id, accounts)
+        }
+        CivicTransferHookInstruction::CloseTravelRuleBuffer => {
+            msg!("Instruction: CloseTravelRuleBuffer");
+            process_close_travel_rule_buffer(program_id, accounts)
+        }
+        CivicTransferHookInstruction::InitializeMultisig { m, signers } => {
+            msg!("Instruction: InitializeMultisig");
+            process_initialize_multisig(program_id, accounts, m, signers)
+        }
+        CivicTransferHookInstruction::QueueConfigUpdate { update } => {
+            msg!("Instruction: QueueConfigUpdate");
+            process_queue_config_update(program_id, accounts, &update)
+        }
+        CivicTransferHookInstruction::ExecuteConfigUpdate => {
+            msg!("Instruction: ExecuteConfigUpdate");
+            process_execute_config_update(program_id, accounts)
+        }
+        CivicTransferHookInstruction::CancelConfigUpdate => {
+            msg!("Instruction: CancelConfigUpdate");
+            process_cancel_config_update(program_id, accounts)
+        }
+        CivicTransferHookInstruction::InitializeConfigHistory => {
+            msg!("Instruction: InitializeConfigHistory");
+            process_initialize_config_history(program_id, accounts)
+        }
+        CivicTransferHookInstruction::ThawAccount => {
+            msg!("Instruction: ThawAccount");
+            process_thaw_account(program_id, accounts)
+        }
+        CivicTransferHookInstruction::FreezeAccount => {
+            msg!("Instruction: FreezeAccount");
+            process_freeze_account(program_id, accounts)
+        }
+        CivicTransferHookInstruction::RecordRevokedPass => {
+            msg!("Instruction: RecordRevokedPass");
+            process_record_revoked_pass(program_id, accounts)
+        }
+        CivicTransferHookInstruction::CloseRevocationRecord => {
+            msg!("Instruction: CloseRevocationRecord");
+            process_close_revocation_record(program_id, accounts)
+        }
+        CivicTransferHookInstruction::IssueTokenPass => {
+            msg!("Instruction: IssueTokenPass");
+            process_issue_token_pass(program_id, accounts)
+        }
+        CivicTransferHookInstruction::CreateSharedPolicy { id, policy } => {
+            msg!("Instruction: CreateSharedPolicy");
+            process_create_shared_policy(program_id, accounts, id, &policy)
+        }
+        CivicTransferHookInstruction::UpdateSharedPolicy { update } => {
+            msg!("Instruction: UpdateSharedPolicy");
+            process_update_shared_policy(program_id, accounts, &update)
+        }
+        CivicTransferHookInstruction::CloseSharedPolicy => {
+            msg!("Instruction: CloseSharedPolicy");
+            process_close_shared_policy(program_id, accounts)
+        }
+        CivicTransferHookInstruction::SyncExtraAccountMetas => {
+            msg!("Instruction: SyncExtraAccountMetas");
+            process_sync_extra_account_metas(program_id, accounts)
+        }
+        CivicTransferHookInstruction::AuthorityThawAccount => {
+            msg!("Instruction: AuthorityThawAccount");
+            process_authority_thaw_account(program_id, accounts)
+        }
+        CivicTransferHookInstruction::QueueSharedPolicyUpdate { update } => {
+            msg!("Instruction: QueueSharedPolicyUpdate");
+            process_queue_shared_policy_update(program_id, accounts, &update)
+        }
+        CivicTransferHookInstruction::ExecuteSharedPolicyUpdate => {
+            msg!("Instruction: ExecuteSharedPolicyUpdate");
+            process_execute_shared_policy_update(program_id, accounts)
+        }
+        CivicTransferHookInstruction::CancelSharedPolicyUpdate => {
+            msg!("Instruction: CancelSharedPolicyUpdate");
+            process_cancel_shared_policy_update(program_id, accounts)
+        }
+        CivicTransferHookInstruction::InitializeSharedPolicyHistory => {
+            msg!("Instruction: InitializeSharedPolicyHistory");
+            process_initialize_shared_policy_history(program_id, accounts)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*, crate::state::ApprovedMint, solana_program::program_pack::Pack,
+        spl_token_2022::state::AccountState,
+    };
+
+    /// An account from which `AccountInfo`s are borrowed
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+            Self {
+                key,
+                owner,
+                lamports: 1_000_000_000,
+                data,
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo {
+            AccountInfo::new(
+                &self.key,
+                false,
+                false,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+    }
+
+    fn token_account_data(mint: &Pubkey) -> Vec<u8> {
+        let mut data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                mint: *mint,
+                owner: Pubkey::new_unique(),
+                amount: 100,
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        data
+    }
+
+    /// The accounts of a transfer checked by `check_transfer`, for a mint
+    /// with a config accepting one network
+    fn transfer_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let mint = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let network = Pubkey::new_unique();
+        let (config_address, bump_seed) = get_mint_config_address_and_bump_seed(&mint, program_id);
+        let config = MintConfig::new(
+            mint,
+            bump_seed,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![network],
+                ..TransferPolicy::default()
+            },
+        );
+        let (extra_account_metas, _) = build_extra_account_metas(&config).unwrap();
+        let mut extra_account_metas_data =
+            vec![0; ExtraAccountMetaList::size_of(extra_account_metas.len()).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut extra_account_metas_data,
+            &extra_account_metas,
+        )
+        .unwrap();
+        let mut mint_data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                is_initialized: true,
+                ..Mint::default()
+            },
+            &mut mint_data,
+        )
+        .unwrap();
+
+        vec![
+            TestAccount::new(
+                Pubkey::new_unique(),
+                spl_token_2022::id(),
+                token_account_data(&mint),
+            ),
+            TestAccount::new(mint, spl_token_2022::id(), mint_data),
+            TestAccount::new(destination, spl_token_2022::id(), token_account_data(&mint)),
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(
+                get_extra_account_metas_address(&mint, program_id),
+                *program_id,
+                extra_account_metas_data,
+            ),
+            TestAccount::new(config_address, *program_id, config.pack().unwrap()),
+            TestAccount::new(GATEWAY_PROGRAM_ID, Pubkey::default(), vec![]),
+            TestAccount::new(network, Pubkey::default(), vec![]),
+            TestAccount::new(
+                get_gateway_token_address(&destination, &network),
+                GATEWAY_PROGRAM_ID,
+                vec![],
+            ),
+        ]
+    }
+
+    fn check_test_transfer(program_id: &Pubkey, accounts: &mut [TestAccount]) -> ProgramResult {
+        let account_infos = accounts
+            .iter_mut()
+            .map(TestAccount::info)
+            .collect::<Vec<_>>();
+        check_transfer(program_id, &account_infos, 1, CheckKind::Eligibility)
+    }
+
+    #[test]
+    fn transfer_rejects_token_accounts_of_another_mint() {
+        let program_id = crate::id();
+        for index in [0, 2] {
+            let mut accounts = transfer_accounts(&program_id);
+            accounts[index].data = token_account_data(&Pubkey::new_unique());
+            assert_eq!(
+                check_test_transfer(&program_id, &mut accounts).unwrap_err(),
+                CivicTransferHookError::TokenAccountMintMismatch.into()
+            );
+        }
+        for index in [0, 1, 2] {
+            let mut accounts = transfer_accounts(&program_id);
+            accounts[index].owner = Pubkey::new_unique();
+            assert_eq!(
+                check_test_transfer(&program_id, &mut accounts).unwrap_err(),
+                CivicTransferHookError::IncorrectTokenProgram.into()
+            );
+        }
+    }
+
+    #[test]
+    fn transfer_rejects_missing_extra_accounts() {
+        let program_id = crate::id();
+        let mut accounts = transfer_accounts(&program_id);
+        accounts.pop();
+        assert_eq!(
+            check_test_transfer(&program_id, &mut accounts).unwrap_err(),
+            CivicTransferHookError::NotEnoughExtraAccounts.into()
+        );
+    }
+
+    #[test]
+    fn transfer_rejects_config_of_another_mint() {
+        let program_id = crate::id();
+        let mut accounts = transfer_accounts(&program_id);
+        let config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![accounts[7].key],
+                ..TransferPolicy::default()
+            },
+        );
+        accounts[5].data = config.pack().unwrap();
+        assert_eq!(
+            check_test_transfer(&program_id, &mut accounts).unwrap_err(),
+            CivicTransferHookError::IncorrectMintConfig.into()
+        );
+    }
+
+    #[test]
+    fn legacy_transfer_rejects_another_network() {
+        let network = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let stored_extra_account_metas = build_legacy_extra_account_metas(&network).unwrap();
+        let mut destination_account = TestAccount::new(destination, spl_token_2022::id(), vec![]);
+        let mut extra_accounts = [
+            TestAccount::new(Pubkey::new_unique(), Pubkey::default(), vec![]),
+            TestAccount::new(GATEWAY_PROGRAM_ID, Pubkey::default(), vec![]),
+            TestAccount::new(
+                get_gateway_token_address(&destination, &network),
+                GATEWAY_PROGRAM_ID,
+                vec![],
+            ),
+        ];
+        let extra_account_infos = extra_accounts
+            .iter_mut()
+            .map(TestAccount::info)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            execute_legacy(
+                &destination_account.info(),
+                &stored_extra_account_metas,
+                &extra_account_infos,
+            )
+            .unwrap_err(),
+            CivicTransferHookError::GatekeeperNetworkMismatch.into()
+        );
+    }
+
+    #[test]
+    fn holder_changes_without_fees() {
+        // a new holder receives its first tokens
+        assert_eq!(holder_changes(CheckKind::Eligibility, 100, 100, 500, 0), (true, false));
+        assert_eq!(holder_changes(CheckKind::Execute, 100, 100, 400, 100), (true, false));
+        // the source sends all of its tokens to an existing holder
+        assert_eq!(holder_changes(CheckKind::Eligibility, 100, 100, 100, 50), (false, true));
+        assert_eq!(holder_changes(CheckKind::Execute, 100, 100, 0, 150), (false, true));
+        // empty transfers change nothing
+        assert_eq!(holder_changes(CheckKind::Execute, 0, 0, 0, 0), (false, false));
+    }
+
+    #[test]
+    fn holder_changes_with_fees() {
+        // a new holder receives the amount net of the fee
+        assert_eq!(holder_changes(CheckKind::Eligibility, 100, 98, 500, 0), (true, false));
+        assert_eq!(holder_changes(CheckKind::Execute, 100, 98, 400, 98), (true, false));
+        // an existing holder whose balance happened to equal the fee
+        assert_eq!(holder_changes(CheckKind::Execute, 100, 98, 400, 100), (false, false));
+        // a fee taking the whole amount leaves the destination empty
+        assert_eq!(holder_changes(CheckKind::Eligibility, 1, 0, 500, 0), (false, false));
+        assert_eq!(holder_changes(CheckKind::Execute, 1, 0, 499, 0), (false, false));
+        // the source is debited the full amount
+        assert_eq!(holder_changes(CheckKind::Execute, 100, 98, 0, 98), (true, true));
+    }
+
+    #[test]
+    fn shared_policy_updates() {
+        let authority = Pubkey::new_unique();
+        let shared_policy_address = Pubkey::new_unique();
+        let shared_policy = SharedPolicy {
+            bump_seed: 255,
+            creator: authority,
+            id: 0,
+            authority,
+            policy: TransferPolicy::default(),
+            approved_mints: Vec::new(),
+            timelock_seconds: 3_600,
+        };
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            authority,
+            TransferPolicy::default(),
+        );
+        let link = ConfigUpdate::LinkSharedPolicy(shared_policy_address);
+
+        // a timelocked mint cannot link to a shared policy with a shorter delay
+        config.timelock_seconds = 7_200;
+        assert_eq!(
+            check_shared_policy_update(&config, &link, Some(&shared_policy)).unwrap_err(),
+            CivicTransferHookError::SharedPolicyTimelockTooShort.into()
+        );
+        config.timelock_seconds = 3_600;
+        check_shared_policy_update(&config, &link, Some(&shared_policy)).unwrap();
+        assert_eq!(
+            check_shared_policy_update(&config, &ConfigUpdate::UnlinkSharedPolicy, None)
+                .unwrap_err(),
+            CivicTransferHookError::MintNotLinkedToSharedPolicy.into()
+        );
+
+        config.shared_policy = Some(shared_policy_address);
+        for (update, error) in [
+            (link, CivicTransferHookError::MintLinkedToSharedPolicy),
+            (
+                ConfigUpdate::SetDenyList(vec![Pubkey::new_unique()]),
+                CivicTransferHookError::MintLinkedToSharedPolicy,
+            ),
+            (
+                ConfigUpdate::SetTimelock(7_200),
+                CivicTransferHookError::SharedPolicyTimelockTooShort,
+            ),
+        ] {
+            assert_eq!(
+                check_shared_policy_update(&config, &update, Some(&shared_policy)).unwrap_err(),
+                error.into()
+            );
+        }
+        for update in [
+            ConfigUpdate::SetTimelock(60),
+            ConfigUpdate::SetPaused(true),
+            ConfigUpdate::UnlinkSharedPolicy,
+        ] {
+            check_shared_policy_update(&config, &update, Some(&shared_policy)).unwrap();
+        }
+    }
+
+    #[test]
+    fn link_requires_approval() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let shared_policy_address = Pubkey::new_unique();
+        let (config_address, bump_seed) = get_mint_config_address_and_bump_seed(&mint, &program_id);
+        let config = MintConfig::new(mint, bump_seed, authority, TransferPolicy::default());
+        // the shared policy approved another mint only
+        let shared_policy = SharedPolicy {
+            bump_seed: 255,
+            creator: authority,
+            id: 0,
+            authority,
+            policy: TransferPolicy::default(),
+            approved_mints: vec![ApprovedMint {
+                mint: Pubkey::new_unique(),
+                linked: false,
+            }],
+            timelock_seconds: 0,
+        };
+        let mut config_account =
+            TestAccount::new(config_address, program_id, config.pack().unwrap());
+        let mut extra_account_metas_account = TestAccount::new(
+            get_extra_account_metas_address(&mint, &program_id),
+            program_id,
+            vec![],
+        );
+        let mut shared_policy_account = TestAccount::new(
+            shared_policy_address,
+            program_id,
+            shared_policy.pack().unwrap(),
+        );
+        let shared_policy_data = shared_policy_account.data.clone();
+
+        assert_eq!(
+            apply_config_update(
+                &config_account.info(),
+                &extra_account_metas_account.info(),
+                None,
+                Some(&shared_policy_account.info()),
+                &authority,
+                config,
+                &ConfigUpdate::LinkSharedPolicy(shared_policy_address),
+            )
+            .unwrap_err(),
+            CivicTransferHookError::MintNotApprovedForSharedPolicy.into()
+        );
+        assert_eq!(shared_policy_account.data, shared_policy_data);
+    }
+}
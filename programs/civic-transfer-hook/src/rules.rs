@@ -0,0 +1,404 @@
+//! Composable transfer rules
+//!
+//! A [RuleSet](struct.RuleSet.html) combines primitive conditions on a transfer
+//! (passes, amount, time, account lists) with `And`, `Or` and `Not` nodes, and
+//! is stored in the transfer policy of a mint. When a policy has rules, the
+//! destination must satisfy them instead of holding a pass, so that new
+//! requirements can be rolled out without a program change.
+//!
+//! Nodes are stored in a flat list in which every node comes after its
+//! children, and the last node is the root. The hook evaluates the nodes in
+//! order, each exactly once, so the cost of a rule set is bounded by its size.
+//! Rule sets are assembled off-chain with [RuleBuilder](struct.RuleBuilder.html)
+//! and checked with `RuleSet::validate`, which the program also runs when the
+//! rules are set.
+
+use {
+    crate::{error::CivicTransferHookError, state::MAX_LIST_LEN},
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{clock::UnixTimestamp, program_error::ProgramError, pubkey::Pubkey},
+};
+
+/// The current version of the rule encoding
+pub const RULES_VERSION: u8 = 1;
+
+/// The maximum number of nodes in a rule set
+pub const MAX_RULE_NODES: usize = 32;
+
+/// The maximum number of account lists in a rule set
+pub const MAX_RULE_LISTS: usize = 4;
+
+/// The maximum number of children of an `And` or `Or` node
+pub const MAX_RULE_CHILDREN: usize = 8;
+
+/// A node of a rule set. Children and lists are referred to by their index
+/// in the rule set, and networks by their index in the accepted networks of
+/// the policy.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum Rule {
+    /// All children hold
+    And(Vec<u8>),
+    /// At least one child holds
+    Or(Vec<u8>),
+    /// The child does not hold
+    Not(u8),
+    /// The destination holds a pass accepted by the policy in the network
+    DestinationHasPass(u8),
+    /// The source holds a pass accepted by the policy in the network
+    SourceHasPass(u8),
+    /// The amount is at most this
+    AmountAtMost(u64),
+    /// The amount is at least this
+    AmountAtLeast(u64),
+    /// The transfer happens before this time
+    Before(UnixTimestamp),
+    /// The transfer happens at or after this time
+    After(UnixTimestamp),
+    /// The destination token account is in the list
+    DestinationInList(u8),
+    /// The source token account is in the list
+    SourceInList(u8),
+    /// The transfer authority is in the list
+    AuthorityInList(u8),
+}
+
+/// What the rules know about a transfer
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleContext<'a> {
+    /// The source token account
+    pub source: &'a Pubkey,
+    /// The destination token account
+    pub destination: &'a Pubkey,
+    /// The transfer authority
+    pub authority: &'a Pubkey,
+    /// The amount transferred
+    pub amount: u64,
+    /// The time of the transfer
+    pub now: UnixTimestamp,
+    /// The networks in which the source holds an accepted pass, as a bitmask
+    /// over network indices
+    pub source_networks: u8,
+    /// The networks in which the destination holds an accepted pass
+    pub destination_networks: u8,
+}
+
+/// A versioned set of rules, see the [module documentation](index.html)
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct RuleSet {
+    /// Version of the rule encoding
+    pub version: u8,
+    /// Account lists referred to by the rules
+    pub lists: Vec<Vec<Pubkey>>,
+    /// The nodes, each after its children, ending with the root
+    pub nodes: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Checks that the rules can be stored and evaluated for a policy with
+    /// `network_count` accepted networks
+    pub fn validate(&self, network_count: usize) -> Result<(), ProgramError> {
+        if self.version != RULES_VERSION
+            || self.nodes.is_empty()
+            || self.nodes.len() > MAX_RULE_NODES
+            || self.lists.len() > MAX_RULE_LISTS
+            || self.lists.iter().any(|list| list.len() > MAX_LIST_LEN)
+        {
+            return Err(CivicTransferHookError::InvalidRules.into());
+        }
+        let is_list = |list: &u8| (*list as usize) < self.lists.len();
+        let is_network = |network: &u8| (*network as usize) < network_count;
+        for (index, rule) in self.nodes.iter().enumerate() {
+            let is_child = |child: &u8| (*child as usize) < index;
+            let valid = match rule {
+                Rule::And(children) | Rule::Or(children) => {
+                    !children.is_empty()
+                        && children.len() <= MAX_RULE_CHILDREN
+                        && children.iter().all(is_child)
+                }
+                Rule::Not(child) => is_child(child),
+                Rule::DestinationHasPass(network) | Rule::SourceHasPass(network) => {
+                    is_network(network)
+                }
+                Rule::DestinationInList(list)
+                | Rule::SourceInList(list)
+                | Rule::AuthorityInList(list) => is_list(list),
+                Rule::AmountAtMost(_)
+                | Rule::AmountAtLeast(_)
+                | Rule::Before(_)
+                | Rule::After(_) => true,
+            };
+            if !valid {
+                return Err(CivicTransferHookError::InvalidRules.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the rules depend on the passes of the source, which the hook
+    /// then needs among the extra accounts
+    pub fn uses_source_passes(&self) -> bool {
+        self.nodes
+            .iter()
+            .any(|rule| matches!(rule, Rule::SourceHasPass(_)))
+    }
+
+    /// Whether the transfer satisfies the rules. Invalid references evaluate
+    /// to false.
+    pub fn evaluate(&self, context: &RuleContext) -> bool {
+        let mut results: Vec<bool> = Vec::with_capacity(self.nodes.len());
+        for rule in &self.nodes {
+            let result = |child: &u8| results.get(*child as usize).copied().unwrap_or(false);
+            let in_list = |list: &u8, key: &Pubkey| {
+                self.lists
+                    .get(*list as usize)
+                    .map_or(false, |list| list.contains(key))
+            };
+            let holds = match rule {
+                Rule::And(children) => children.iter().all(result),
+                Rule::Or(children) => children.iter().any(result),
+                Rule::Not(child) => !result(child),
+                Rule::DestinationHasPass(network) => {
+                    *network < 8 && context.destination_networks & (1 << network) != 0
+                }
+                Rule::SourceHasPass(network) => {
+                    *network < 8 && context.source_networks & (1 << network) != 0
+                }
+                Rule::AmountAtMost(amount) => context.amount <= *amount,
+                Rule::AmountAtLeast(amount) => context.amount >= *amount,
+                Rule::Before(time) => context.now < *time,
+                Rule::After(time) => context.now >= *time,
+                Rule::DestinationInList(list) => in_list(list, context.destination),
+                Rule::SourceInList(list) => in_list(list, context.source),
+                Rule::AuthorityInList(list) => in_list(list, context.authority),
+            };
+            results.push(holds);
+        }
+        results.last().copied().unwrap_or(false)
+    }
+}
+
+/// A node added to a [RuleBuilder](struct.RuleBuilder.html)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NodeRef(u8);
+
+/// An account list added to a [RuleBuilder](struct.RuleBuilder.html)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListRef(u8);
+
+/// Assembles a rule set off-chain. Each node is added after its children,
+/// and the builder returns a reference to it for use in later nodes.
+#[derive(Clone, Debug, Default)]
+pub struct RuleBuilder {
+    lists: Vec<Vec<Pubkey>>,
+    nodes: Vec<Rule>,
+}
+
+impl RuleBuilder {
+    fn push(&mut self, rule: Rule) -> NodeRef {
+        self.nodes.push(rule);
+        // oversized rule sets are rejected by `build`
+        NodeRef(u8::try_from(self.nodes.len() - 1).unwrap_or(u8::MAX))
+    }
+
+    /// Adds an account list
+    pub fn list(&mut self, accounts: Vec<Pubkey>) -> ListRef {
+        self.lists.push(accounts);
+        ListRef(u8::try_from(self.lists.len() - 1).unwrap_or(u8::MAX))
+    }
+
+    /// All of `children` hold
+    pub fn and(&mut self, children: &[NodeRef]) -> NodeRef {
+        self.push(Rule::And(children.iter().map(|child| child.0).collect()))
+    }
+
+    /// At least one of `children` holds
+    pub fn or(&mut self, children: &[NodeRef]) -> NodeRef {
+        self.push(Rule::Or(children.iter().map(|child| child.0).collect()))
+    }
+
+    /// `child` does not hold
+    pub fn not(&mut self, child: NodeRef) -> NodeRef {
+        self.push(Rule::Not(child.0))
+    }
+
+    /// The destination holds a pass in the network at index `network` of the policy
+    pub fn destination_has_pass(&mut self, network: u8) -> NodeRef {
+        self.push(Rule::DestinationHasPass(network))
+    }
+
+    /// The source holds a pass in the network at index `network` of the policy
+    pub fn source_has_pass(&mut self, network: u8) -> NodeRef {
+        self.push(Rule::SourceHasPass(network))
+    }
+
+    /// The amount is at most `amount`
+    pub fn amount_at_most(&mut self, amount: u64) -> NodeRef {
+        self.push(Rule::AmountAtMost(amount))
+    }
+
+    /// The amount is at least `amount`
+    pub fn amount_at_least(&mut self, amount: u64) -> NodeRef {
+        self.push(Rule::AmountAtLeast(amount))
+    }
+
+    /// The transfer happens before `time`
+    pub fn before(&mut self, time: UnixTimestamp) -> NodeRef {
+        self.push(Rule::Before(time))
+    }
+
+    /// The transfer happens at or after `time`
+    pub fn after(&mut self, time: UnixTimestamp) -> NodeRef {
+        self.push(Rule::After(time))
+    }
+
+    /// The destination token account is in `list`
+    pub fn destination_in_list(&mut self, list: ListRef) -> NodeRef {
+        self.push(Rule::DestinationInList(list.0))
+    }
+
+    /// The source token account is in `list`
+    pub fn source_in_list(&mut self, list: ListRef) -> NodeRef {
+        self.push(Rule::SourceInList(list.0))
+    }
+
+    /// The transfer authority is in `list`
+    pub fn authority_in_list(&mut self, list: ListRef) -> NodeRef {
+        self.push(Rule::AuthorityInList(list.0))
+    }
+
+    /// Finishes the rule set with `root` as its root, validated for a policy
+    /// with `network_count` accepted networks
+    pub fn build(mut self, root: NodeRef, network_count: usize) -> Result<RuleSet, ProgramError> {
+        if root.0 as usize + 1 != self.nodes.len() {
+            self.push(Rule::And(vec![root.0]));
+        }
+        let rules = RuleSet {
+            version: RULES_VERSION,
+            lists: self.lists,
+            nodes: self.nodes,
+        };
+        rules.validate(network_count)?;
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn context<'a>(
+        source: &'a Pubkey,
+        destination: &'a Pubkey,
+        amount: u64,
+        destination_networks: u8,
+    ) -> RuleContext<'a> {
+        RuleContext {
+            source,
+            destination,
+            authority: source,
+            amount,
+            now: 1_000,
+            source_networks: 0,
+            destination_networks,
+        }
+    }
+
+    #[test]
+    fn evaluates_rules() {
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+
+        let mut builder = RuleBuilder::default();
+        let treasuries = builder.list(vec![treasury]);
+        let has_pass = builder.destination_has_pass(1);
+        let small = builder.amount_at_most(100);
+        let started = builder.after(500);
+        let kyc = builder.and(&[has_pass, small, started]);
+        let exempt = builder.destination_in_list(treasuries);
+        let root = builder.or(&[kyc, exempt]);
+        let rules = builder.build(root, 2).unwrap();
+
+        assert!(rules.evaluate(&context(&source, &destination, 100, 0b10)));
+        assert!(!rules.evaluate(&context(&source, &destination, 101, 0b10)));
+        assert!(!rules.evaluate(&context(&source, &destination, 100, 0b01)));
+        assert!(rules.evaluate(&context(&source, &treasury, 1_000_000, 0)));
+        assert!(!rules.uses_source_passes());
+
+        let packed = rules.try_to_vec().unwrap();
+        assert_eq!(RuleSet::try_from_slice(&packed).unwrap(), rules);
+    }
+
+    #[test]
+    fn root_need_not_be_last() {
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+
+        let mut builder = RuleBuilder::default();
+        let small = builder.amount_at_most(100);
+        let _unused = builder.source_has_pass(0);
+        let not_small = builder.not(small);
+        let rules = builder.build(not_small, 1).unwrap();
+        assert!(rules.uses_source_passes());
+
+        let mut builder = RuleBuilder::default();
+        let small = builder.amount_at_most(100);
+        builder.not(small);
+        let rules = builder.build(small, 1).unwrap();
+        assert_eq!(rules.nodes.last(), Some(&Rule::And(vec![0])));
+        assert!(rules.evaluate(&context(&source, &destination, 100, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let valid = RuleSet {
+            version: RULES_VERSION,
+            lists: vec![vec![]],
+            nodes: vec![Rule::DestinationHasPass(0), Rule::Not(0)],
+        };
+        assert_eq!(valid.validate(1), Ok(()));
+
+        let invalid: ProgramError = CivicTransferHookError::InvalidRules.into();
+        for rules in [
+            RuleSet {
+                version: RULES_VERSION + 1,
+                ..valid.clone()
+            },
+            RuleSet {
+                nodes: vec![],
+                ..valid.clone()
+            },
+            // children must come first, which also rules out cycles
+            RuleSet {
+                nodes: vec![Rule::Not(0)],
+                ..valid.clone()
+            },
+            RuleSet {
+                nodes: vec![Rule::Or(vec![1]), Rule::AmountAtMost(1)],
+                ..valid.clone()
+            },
+            RuleSet {
+                nodes: vec![Rule::And(vec![])],
+                ..valid.clone()
+            },
+            RuleSet {
+                nodes: vec![Rule::SourceInList(1)],
+                ..valid.clone()
+            },
+            RuleSet {
+                nodes: vec![Rule::AmountAtMost(1); MAX_RULE_NODES + 1],
+                ..valid.clone()
+            },
+            RuleSet {
+                nodes: vec![Rule::AmountAtMost(1); MAX_RULE_CHILDREN + 1]
+                    .into_iter()
+                    .chain([Rule::And((0..=MAX_RULE_CHILDREN as u8).collect())])
+                    .collect(),
+                ..valid.clone()
+            },
+        ] {
+            assert_eq!(rules.validate(1), Err(invalid.clone()));
+        }
+        assert_eq!(valid.validate(0), Err(invalid));
+    }
+}
@@ -0,0 +1,2168 @@
+//! State types for accounts owned by the program
+
+use {
+    crate::{error::CivicTransferHookError, rules::RuleSet},
+    borsh::{BorshDeserialize, BorshSerialize},
+    solana_program::{
+        clock::{Slot, UnixTimestamp},
+        hash::{hash, Hash},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+};
+
+/// The seed literal for deriving the mint config account address.
+pub const MINT_CONFIG_SEED: &[u8] = br"config";
+
+/// The seed literal for deriving transfer permit addresses.
+pub const TRANSFER_PERMIT_SEED: &[u8] = br"permit";
+
+/// The seed literal for deriving source state addresses.
+pub const SOURCE_STATE_SEED: &[u8] = br"source-state";
+
+/// The seed literal for deriving pending config update addresses.
+pub const PENDING_CONFIG_UPDATE_SEED: &[u8] = br"pending";
+
+/// The seed literal for deriving config history addresses.
+pub const CONFIG_HISTORY_SEED: &[u8] = br"history";
+
+/// The seed literal for deriving the hook authority address of a mint.
+pub const HOOK_AUTHORITY_SEED: &[u8] = br"hook-authority";
+
+/// The seed literal for deriving revocation record addresses.
+pub const REVOCATION_RECORD_SEED: &[u8] = br"revocation";
+
+/// The seed literal for deriving the gatekeeper authority address of a mint in a network.
+pub const GATEKEEPER_AUTHORITY_SEED: &[u8] = br"gatekeeper";
+
+/// The seed literal for deriving shared policy addresses.
+pub const SHARED_POLICY_SEED: &[u8] = br"shared-policy";
+
+/// The maximum number of gatekeeper networks a mint can accept passes from.
+/// Each network adds two accounts to every transfer, so this is kept small.
+pub const MAX_GATEKEEPER_NETWORKS: usize = 4;
+
+/// The maximum number of signers of a multisig authority
+pub const MAX_MULTISIG_SIGNERS: usize = 11;
+
+/// The maximum number of token accounts in the allow list or the deny list
+pub const MAX_LIST_LEN: usize = 32;
+
+/// The maximum number of issuer accounts in issuer-only mode
+pub const MAX_ISSUER_ACCOUNTS: usize = 8;
+
+/// The number of holder count checkpoints kept in a mint config, bounding how
+/// far back a holder count snapshot can be reconciled
+pub const HOLDER_COUNT_CHECKPOINTS: usize = 16;
+
+/// The maximum number of entries kept in a config history. Once reached,
+/// new entries overwrite the oldest ones instead of growing the account.
+pub const MAX_CONFIG_HISTORY_ENTRIES: usize = 512;
+
+/// Policy flag: when a delegate moves tokens on behalf of the owner of the
+/// source account, the delegate must hold a valid pass as well.
+/// The delegate's pass is the one issued to its wallet.
+pub const FLAG_VERIFY_DELEGATE: u64 = 1 << 0;
+
+/// Policy flag: transfers that fail a check are allowed if the config
+/// authority has issued a matching [TransferPermit](struct.TransferPermit.html).
+/// Adds the permit PDA for the source and destination to the extra accounts.
+pub const FLAG_TRANSFER_PERMITS: u64 = 1 << 1;
+
+/// Policy flag: the destination must be the associated token account of its
+/// owner, so that each holder has a single token account, and a single token pass.
+pub const FLAG_REQUIRE_ASSOCIATED_DESTINATION: u64 = 1 << 2;
+
+/// Get the mint config address for a mint
+pub fn get_mint_config_address(mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    get_mint_config_address_and_bump_seed(mint, program_id).0
+}
+
+/// Get the mint config address and bump seed for a mint
+pub fn get_mint_config_address_and_bump_seed(mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&collect_mint_config_seeds(mint), program_id)
+}
+
+/// Collect the seeds used to derive the mint config address
+pub fn collect_mint_config_seeds(mint: &Pubkey) -> [&[u8]; 2] {
+    [MINT_CONFIG_SEED, mint.as_ref()]
+}
+
+/// Collect the signer seeds used to create the mint config account
+pub fn collect_mint_config_signer_seeds<'a>(mint: &'a Pubkey, bump_seed: &'a [u8]) -> [&'a [u8]; 3] {
+    [MINT_CONFIG_SEED, mint.as_ref(), bump_seed]
+}
+
+/// Get the transfer permit address for a source and destination token account
+pub fn get_transfer_permit_address(
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    get_transfer_permit_address_and_bump_seed(mint, source, destination, program_id).0
+}
+
+/// Get the transfer permit address and bump seed for a source and destination token account
+pub fn get_transfer_permit_address_and_bump_seed(
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &collect_transfer_permit_seeds(mint, source, destination),
+        program_id,
+    )
+}
+
+/// Collect the seeds used to derive a transfer permit address
+pub fn collect_transfer_permit_seeds<'a>(
+    mint: &'a Pubkey,
+    source: &'a Pubkey,
+    destination: &'a Pubkey,
+) -> [&'a [u8]; 4] {
+    [
+        TRANSFER_PERMIT_SEED,
+        mint.as_ref(),
+        source.as_ref(),
+        destination.as_ref(),
+    ]
+}
+
+/// Collect the signer seeds used to create a transfer permit account
+pub fn collect_transfer_permit_signer_seeds<'a>(
+    mint: &'a Pubkey,
+    source: &'a Pubkey,
+    destination: &'a Pubkey,
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 5] {
+    [
+        TRANSFER_PERMIT_SEED,
+        mint.as_ref(),
+        source.as_ref(),
+        destination.as_ref(),
+        bump_seed,
+    ]
+}
+
+/// Get the source state address for a token account
+pub fn get_source_state_address(mint: &Pubkey, source: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    get_source_state_address_and_bump_seed(mint, source, program_id).0
+}
+
+/// Get the source state address and bump seed for a token account
+pub fn get_source_state_address_and_bump_seed(
+    mint: &Pubkey,
+    source: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&collect_source_state_seeds(mint, source), program_id)
+}
+
+/// Collect the seeds used to derive a source state address
+pub fn collect_source_state_seeds<'a>(mint: &'a Pubkey, source: &'a Pubkey) -> [&'a [u8]; 3] {
+    [SOURCE_STATE_SEED, mint.as_ref(), source.as_ref()]
+}
+
+/// Collect the signer seeds used to create a source state account
+pub fn collect_source_state_signer_seeds<'a>(
+    mint: &'a Pubkey,
+    source: &'a Pubkey,
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 4] {
+    [SOURCE_STATE_SEED, mint.as_ref(), source.as_ref(), bump_seed]
+}
+
+/// Get the pending config update address for a mint
+pub fn get_pending_config_update_address(mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    get_pending_config_update_address_and_bump_seed(mint, program_id).0
+}
+
+/// Get the pending config update address and bump seed for a mint
+pub fn get_pending_config_update_address_and_bump_seed(
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&collect_pending_config_update_seeds(mint), program_id)
+}
+
+/// Collect the seeds used to derive the pending config update address
+pub fn collect_pending_config_update_seeds(mint: &Pubkey) -> [&[u8]; 2] {
+    [PENDING_CONFIG_UPDATE_SEED, mint.as_ref()]
+}
+
+/// Collect the signer seeds used to create the pending config update account
+pub fn collect_pending_config_update_signer_seeds<'a>(
+    mint: &'a Pubkey,
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 3] {
+    [PENDING_CONFIG_UPDATE_SEED, mint.as_ref(), bump_seed]
+}
+
+/// Get the config history address for a mint
+pub fn get_config_history_address(mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    get_config_history_address_and_bump_seed(mint, program_id).0
+}
+
+/// Get the config history address and bump seed for a mint
+pub fn get_config_history_address_and_bump_seed(
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&collect_config_history_seeds(mint), program_id)
+}
+
+/// Collect the seeds used to derive the config history address
+pub fn collect_config_history_seeds(mint: &Pubkey) -> [&[u8]; 2] {
+    [CONFIG_HISTORY_SEED, mint.as_ref()]
+}
+
+/// Collect the signer seeds used to create the config history account
+pub fn collect_config_history_signer_seeds<'a>(
+    mint: &'a Pubkey,
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 3] {
+    [CONFIG_HISTORY_SEED, mint.as_ref(), bump_seed]
+}
+
+/// Get the hook authority address for a mint. The hook authority is a PDA
+/// that the mint can appoint as its freeze authority, so that the program can
+/// freeze and thaw token accounts according to their passes.
+pub fn get_hook_authority_address(mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    get_hook_authority_address_and_bump_seed(mint, program_id).0
+}
+
+/// Get the hook authority address and bump seed for a mint
+pub fn get_hook_authority_address_and_bump_seed(
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&collect_hook_authority_seeds(mint), program_id)
+}
+
+/// Collect the seeds used to derive the hook authority address
+pub fn collect_hook_authority_seeds(mint: &Pubkey) -> [&[u8]; 2] {
+    [HOOK_AUTHORITY_SEED, mint.as_ref()]
+}
+
+/// Collect the signer seeds used to sign as the hook authority
+pub fn collect_hook_authority_signer_seeds<'a>(
+    mint: &'a Pubkey,
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 3] {
+    [HOOK_AUTHORITY_SEED, mint.as_ref(), bump_seed]
+}
+
+/// Get the revocation record address for a token account
+pub fn get_revocation_record_address(
+    mint: &Pubkey,
+    account: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    get_revocation_record_address_and_bump_seed(mint, account, program_id).0
+}
+
+/// Get the revocation record address and bump seed for a token account
+pub fn get_revocation_record_address_and_bump_seed(
+    mint: &Pubkey,
+    account: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&collect_revocation_record_seeds(mint, account), program_id)
+}
+
+/// Collect the seeds used to derive a revocation record address
+pub fn collect_revocation_record_seeds<'a>(
+    mint: &'a Pubkey,
+    account: &'a Pubkey,
+) -> [&'a [u8]; 3] {
+    [REVOCATION_RECORD_SEED, mint.as_ref(), account.as_ref()]
+}
+
+/// Collect the signer seeds used to create a revocation record account
+pub fn collect_revocation_record_signer_seeds<'a>(
+    mint: &'a Pubkey,
+    account: &'a Pubkey,
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 4] {
+    [REVOCATION_RECORD_SEED, mint.as_ref(), account.as_ref(), bump_seed]
+}
+
+/// Get the gatekeeper authority address of a mint in a token pass network.
+/// Once the network registers it as a gatekeeper, the program issues token
+/// passes in the network to holders of the mint presenting a valid base pass.
+pub fn get_gatekeeper_authority_address(
+    mint: &Pubkey,
+    network: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    get_gatekeeper_authority_address_and_bump_seed(mint, network, program_id).0
+}
+
+/// Get the gatekeeper authority address and bump seed of a mint in a token pass network
+pub fn get_gatekeeper_authority_address_and_bump_seed(
+    mint: &Pubkey,
+    network: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&collect_gatekeeper_authority_seeds(mint, network), program_id)
+}
+
+/// Collect the seeds used to derive the gatekeeper authority address
+pub fn collect_gatekeeper_authority_seeds<'a>(
+    mint: &'a Pubkey,
+    network: &'a Pubkey,
+) -> [&'a [u8]; 3] {
+    [GATEKEEPER_AUTHORITY_SEED, mint.as_ref(), network.as_ref()]
+}
+
+/// Collect the signer seeds used to sign as the gatekeeper authority
+pub fn collect_gatekeeper_authority_signer_seeds<'a>(
+    mint: &'a Pubkey,
+    network: &'a Pubkey,
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 4] {
+    [GATEKEEPER_AUTHORITY_SEED, mint.as_ref(), network.as_ref(), bump_seed]
+}
+
+/// Get the address of a shared policy, identified by its creator and an id
+/// chosen by the creator
+pub fn get_shared_policy_address(creator: &Pubkey, id: u64, program_id: &Pubkey) -> Pubkey {
+    get_shared_policy_address_and_bump_seed(creator, id, program_id).0
+}
+
+/// Get the address and bump seed of a shared policy
+pub fn get_shared_policy_address_and_bump_seed(
+    creator: &Pubkey,
+    id: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &collect_shared_policy_seeds(creator, &id.to_le_bytes()),
+        program_id,
+    )
+}
+
+/// Collect the seeds used to derive a shared policy address
+pub fn collect_shared_policy_seeds<'a>(creator: &'a Pubkey, id: &'a [u8; 8]) -> [&'a [u8]; 3] {
+    [SHARED_POLICY_SEED, creator.as_ref(), id]
+}
+
+/// Collect the signer seeds used to create a shared policy account
+pub fn collect_shared_policy_signer_seeds<'a>(
+    creator: &'a Pubkey,
+    id: &'a [u8; 8],
+    bump_seed: &'a [u8],
+) -> [&'a [u8]; 4] {
+    [SHARED_POLICY_SEED, creator.as_ref(), id, bump_seed]
+}
+
+/// Discriminates the kinds of account owned by the program.
+/// Stored as the first byte of the account data.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountType {
+    /// Not yet initialized
+    Uninitialized,
+    /// A [MintConfig](struct.MintConfig.html)
+    MintConfig,
+    /// A [TransferPermit](struct.TransferPermit.html)
+    TransferPermit,
+    /// A [SourceState](struct.SourceState.html)
+    SourceState,
+    /// A [TravelRuleBuffer](struct.TravelRuleBuffer.html)
+    TravelRuleBuffer,
+    /// A [Multisig](struct.Multisig.html)
+    Multisig,
+    /// A [PendingConfigUpdate](struct.PendingConfigUpdate.html)
+    PendingConfigUpdate,
+    /// A [ConfigHistory](struct.ConfigHistory.html)
+    ConfigHistory,
+    /// A [RevocationRecord](struct.RevocationRecord.html)
+    RevocationRecord,
+    /// A [SharedPolicy](struct.SharedPolicy.html)
+    SharedPolicy,
+}
+
+/// Common packing logic for accounts owned by the program.
+///
+/// The account type is written as a single byte ahead of the borsh-encoded
+/// body, and any trailing bytes are ignored on unpacking, so accounts may be
+/// allocated with room to grow.
+pub trait ProgramAccount: BorshSerialize + BorshDeserialize {
+    /// The account type stored in the first byte of the account data
+    const ACCOUNT_TYPE: AccountType;
+
+    /// Deserializes the account, checking its account type
+    fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let mut body = account_body(input, Self::ACCOUNT_TYPE)?;
+        Self::deserialize(&mut body).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Serializes the account, including its account type
+    fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut buf = vec![Self::ACCOUNT_TYPE as u8];
+        self.serialize(&mut buf)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        Ok(buf)
+    }
+
+    /// Serializes the account into the start of `output`
+    fn pack_into_slice(&self, output: &mut [u8]) -> Result<(), ProgramError> {
+        let packed = self.pack()?;
+        output
+            .get_mut(..packed.len())
+            .ok_or(ProgramError::AccountDataTooSmall)?
+            .copy_from_slice(&packed);
+        Ok(())
+    }
+
+    /// The number of bytes needed to store the account
+    fn packed_len(&self) -> Result<usize, ProgramError> {
+        Ok(self.pack()?.len())
+    }
+}
+
+/// Checks the account type stored in the first byte of `input`,
+/// and returns the body following it
+fn account_body(input: &[u8], account_type: AccountType) -> Result<&[u8], ProgramError> {
+    let (stored_account_type, body) = input
+        .split_first()
+        .ok_or(ProgramError::UninitializedAccount)?;
+    if *stored_account_type == AccountType::Uninitialized as u8 {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    if *stored_account_type != account_type as u8 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(body)
+}
+
+/// What the hook does when a pass is in a given inactive state
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum PassStateAction {
+    /// Reject the transfer
+    #[default]
+    Reject,
+    /// Allow the transfer as if the pass were active
+    Allow,
+}
+
+/// How the hook treats passes that are not active.
+/// The default rejects every inactive pass.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct PassStatePolicy {
+    /// Action for passes frozen by their gatekeeper, e.g. pending a re-check
+    pub frozen: PassStateAction,
+    /// Action for passes revoked by their gatekeeper
+    pub revoked: PassStateAction,
+    /// Action for passes past their expiry time plus the grace period
+    pub expired: PassStateAction,
+    /// Seconds after expiry during which a pass still counts as active
+    pub expiry_grace_seconds: u32,
+}
+
+/// How the hook treats transfers made by the permanent delegate of the mint,
+/// which issuers use to recover tokens, e.g. from holders whose pass was revoked
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum PermanentDelegatePolicy {
+    /// Apply the same checks as to any other transfer
+    #[default]
+    Enforce,
+    /// Allow the transfer without checking passes
+    SkipPassChecks,
+    /// Reject every transfer by the permanent delegate
+    Reject,
+    /// Only allow the permanent delegate to move tokens to a quarantine account,
+    /// out of token accounts whose pass has been revoked for the recovery period,
+    /// as established by their [RevocationRecord](struct.RevocationRecord.html)
+    RecoveryOnly {
+        /// The token account recovered tokens must be sent to
+        quarantine: Pubkey,
+        /// Seconds a pass must have been known to be revoked before recovery
+        recovery_period_seconds: u32,
+    },
+}
+
+/// Whether failed checks block transfers
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum EnforcementMode {
+    /// Transfers that fail a check are rejected
+    #[default]
+    Enforce,
+    /// Transfers that fail a check are allowed, and a violation event is logged.
+    /// Used to see who would be blocked before rolling out a policy.
+    /// `CheckEligibility` reports such transfers as eligible, since they would succeed.
+    Monitor,
+}
+
+/// An allowed transfer between the holders of passes in two gatekeeper networks,
+/// identified by their index in the accepted networks of the policy
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TransferRoute {
+    /// Network of the sender's pass
+    pub sender_network: u8,
+    /// Network of the recipient's pass
+    pub recipient_network: u8,
+}
+
+/// The transfer policy for a mint.
+///
+/// New policy fields are added here. Whenever the encoding changes, the config
+/// version is bumped and a decoder for the previous encoding is kept, see
+/// [MintConfig::unpack](struct.MintConfig.html#method.unpack).
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct TransferPolicy {
+    /// Recipients must hold a valid gateway token in at least one of these networks
+    pub gatekeeper_networks: Vec<Pubkey>,
+    /// Policy flags, reserved for switches that do not need their own field
+    pub flags: u64,
+    /// How passes that are not active are treated
+    pub pass_states: PassStatePolicy,
+    /// How transfers by the permanent delegate of the mint are treated
+    pub permanent_delegate: PermanentDelegatePolicy,
+    /// Whether failed checks block transfers
+    pub enforcement: EnforcementMode,
+    /// Key whose Ed25519-signed approvals, included in the transaction,
+    /// allow transfers that would otherwise fail
+    pub approver: Option<Pubkey>,
+    /// Minimum number of seconds between two outgoing transfers from the
+    /// same token account, or 0 for no cooldown
+    pub transfer_cooldown_seconds: u32,
+    /// Maximum number of token accounts with a non-zero balance, if any.
+    /// Transfers that would add a holder beyond the cap are rejected.
+    pub holder_cap: Option<u64>,
+    /// Transfers of at least this amount are recorded in the travel rule buffer
+    /// of the mint, and rejected if the mint has none
+    pub travel_rule_threshold: Option<u64>,
+    /// Pairs of networks whose holders may transfer to each other, e.g. per
+    /// jurisdiction. If empty, any accepted network may send to any other.
+    pub transfer_matrix: Vec<TransferRoute>,
+    /// If not empty, the token is soulbound: only these token accounts may send
+    /// tokens, apart from the permanent delegate recovering them. Recipients
+    /// still need a valid pass.
+    pub issuer_accounts: Vec<Pubkey>,
+    /// Destination token accounts that do not need a pass, e.g. issuer treasuries
+    pub allow_list: Vec<Pubkey>,
+    /// Token accounts that may neither send nor receive tokens
+    pub deny_list: Vec<Pubkey>,
+    /// Rules the destination must satisfy instead of holding a pass, if set.
+    /// Destinations on the allow list are exempt from the rules as well.
+    pub rules: Option<RuleSet>,
+}
+
+impl TransferPolicy {
+    /// Whether a policy flag is set
+    pub fn has_flag(&self, flag: u64) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Checks that the policy can be stored and enforced
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if self.gatekeeper_networks.is_empty()
+            || self.gatekeeper_networks.len() > MAX_GATEKEEPER_NETWORKS
+        {
+            return Err(CivicTransferHookError::InvalidGatekeeperNetworks.into());
+        }
+        if self.transfer_matrix.len() > MAX_GATEKEEPER_NETWORKS * MAX_GATEKEEPER_NETWORKS
+            || self.transfer_matrix.iter().any(|route| {
+                route.sender_network as usize >= self.gatekeeper_networks.len()
+                    || route.recipient_network as usize >= self.gatekeeper_networks.len()
+            })
+        {
+            return Err(CivicTransferHookError::InvalidTransferMatrix.into());
+        }
+        if self.issuer_accounts.len() > MAX_ISSUER_ACCOUNTS {
+            return Err(CivicTransferHookError::TooManyIssuerAccounts.into());
+        }
+        if self.allow_list.len() > MAX_LIST_LEN || self.deny_list.len() > MAX_LIST_LEN {
+            return Err(CivicTransferHookError::ListTooLong.into());
+        }
+        if let Some(rules) = &self.rules {
+            rules.validate(self.gatekeeper_networks.len())?;
+        }
+        Ok(())
+    }
+
+    /// Whether a holder of a pass in one of `sender_networks` may send to a holder
+    /// of a pass in one of `recipient_networks`. Both are bitmasks over the
+    /// indices of the gatekeeper networks.
+    pub fn allows_route(&self, sender_networks: u8, recipient_networks: u8) -> bool {
+        self.transfer_matrix.iter().any(|route| {
+            sender_networks & (1 << route.sender_network) != 0
+                && recipient_networks & (1 << route.recipient_network) != 0
+        })
+    }
+}
+
+/// Per-mint configuration, stored in a PDA derived from the mint.
+/// The extra account metas of the mint reference this account.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct MintConfig {
+    /// Version of the config encoding. Configs written at an older version are
+    /// upgraded when they are read, and stored at the current version on the
+    /// next write.
+    pub version: u8,
+    /// Bump seed of the config PDA
+    pub bump_seed: u8,
+    /// The mint this config belongs to
+    pub mint: Pubkey,
+    /// The admin, allowed to manage roles and change the policy, either a wallet
+    /// or a [Multisig](struct.Multisig.html) account
+    pub authority: Pubkey,
+    /// Manages the allow and deny lists and transfer permits. The admin holds the role if unset.
+    pub compliance_officer: Option<Pubkey>,
+    /// May pause and resume transfers. The admin holds the role if unset.
+    pub guardian: Option<Pubkey>,
+    /// The transfer policy enforced by the hook
+    pub policy: TransferPolicy,
+    /// Number of token accounts with a non-zero balance, tracked by the hook
+    /// while the policy has a holder cap. Mints and burns do not go through
+    /// the hook, so the admin corrects the count from snapshots with
+    /// `ConfigUpdate::ReconcileHolderCount`.
+    pub holder_count: u64,
+    /// The holder count at the end of the last slots in which the hook changed
+    /// it, oldest first, against which snapshots are reconciled
+    pub holder_count_checkpoints: [HolderCountCheckpoint; HOLDER_COUNT_CHECKPOINTS],
+    /// The buffer that travel rule records are currently written to
+    pub travel_rule_buffer: Option<Pubkey>,
+    /// Seconds a config update must wait in the queue before it can be applied,
+    /// or 0 to apply updates immediately. Pausing is never delayed.
+    pub timelock_seconds: u32,
+    /// Whether all transfers are paused
+    pub paused: bool,
+    /// The network of the wallet passes (base passes) that entitle their holders
+    /// to a token pass issued by the program, if self-service issuance is enabled
+    pub base_pass_network: Option<Pubkey>,
+    /// The [SharedPolicy](struct.SharedPolicy.html) enforced in place of `policy`,
+    /// if the mint is linked to one
+    pub shared_policy: Option<Pubkey>,
+}
+
+impl MintConfig {
+    /// The current version of the config encoding.
+    ///
+    /// Version 1 only held the gatekeeper networks and flags of the policy.
+    /// Version 2 added the remaining policy fields, the roles, the holder count,
+    /// the travel rule buffer, the timelock, pausing, self-service issuance and
+    /// shared policies.
+    /// Version 3 added the holder count checkpoints.
+    pub const CURRENT_VERSION: u8 = 3;
+
+    /// Creates a config at the current version
+    pub fn new(mint: Pubkey, bump_seed: u8, authority: Pubkey, policy: TransferPolicy) -> Self {
+        Self {
+            version: Self::CURRENT_VERSION,
+            bump_seed,
+            mint,
+            authority,
+            compliance_officer: None,
+            guardian: None,
+            policy,
+            holder_count: 0,
+            holder_count_checkpoints: [HolderCountCheckpoint::default(); HOLDER_COUNT_CHECKPOINTS],
+            travel_rule_buffer: None,
+            timelock_seconds: 0,
+            paused: false,
+            base_pass_network: None,
+            shared_policy: None,
+        }
+    }
+
+    /// The key holding a role
+    pub fn role_holder(&self, role: Role) -> Pubkey {
+        match role {
+            Role::Admin => self.authority,
+            Role::ComplianceOfficer => self.compliance_officer.unwrap_or(self.authority),
+            Role::Guardian => self.guardian.unwrap_or(self.authority),
+        }
+    }
+
+    /// Hash of the settings of the config, as recorded in its history.
+    /// The holder count is left out, as it changes with transfers.
+    pub fn settings_hash(&self) -> Result<Hash, ProgramError> {
+        let settings = Self {
+            holder_count: 0,
+            holder_count_checkpoints: [HolderCountCheckpoint::default(); HOLDER_COUNT_CHECKPOINTS],
+            ..self.clone()
+        };
+        Ok(hash(&settings.pack()?))
+    }
+
+    /// Sets the holder count as tracked by the hook in `slot`, checkpointing it
+    pub fn track_holder_count(&mut self, holder_count: u64, slot: Slot) {
+        self.holder_count = holder_count;
+        let checkpoints = &mut self.holder_count_checkpoints;
+        if checkpoints[HOLDER_COUNT_CHECKPOINTS - 1].slot != slot {
+            checkpoints.rotate_left(1);
+        }
+        checkpoints[HOLDER_COUNT_CHECKPOINTS - 1] = HolderCountCheckpoint { slot, holder_count };
+    }
+
+    /// The holder count at the end of `slot`, if the checkpoints go back that far
+    pub fn holder_count_at(&self, slot: Slot) -> Option<u64> {
+        self.holder_count_checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.slot <= slot)
+            .map(|checkpoint| checkpoint.holder_count)
+    }
+
+    /// Unpacks a config, checking that it belongs to the given mint and
+    /// was written by a supported version of the program
+    pub fn unpack_for_mint(input: &[u8], mint: &Pubkey) -> Result<Self, ProgramError> {
+        let config = Self::unpack(input)?;
+        if config.mint != *mint {
+            return Err(CivicTransferHookError::IncorrectMintConfig.into());
+        }
+        Ok(config)
+    }
+}
+
+impl ProgramAccount for MintConfig {
+    const ACCOUNT_TYPE: AccountType = AccountType::MintConfig;
+
+    /// Deserializes a config written at any supported version, upgrading
+    /// older encodings to the current one
+    fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let mut body = account_body(input, Self::ACCOUNT_TYPE)?;
+        let config = match body.first() {
+            Some(1) => MintConfigV1::deserialize(&mut body).map(Self::from),
+            Some(2) => MintConfigV2::deserialize(&mut body).map(Self::from),
+            Some(&version) if version > Self::CURRENT_VERSION => {
+                return Err(CivicTransferHookError::UnsupportedMintConfigVersion.into())
+            }
+            _ => Self::deserialize(&mut body),
+        };
+        config.map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+/// The holder count tracked by the hook at the end of a slot
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct HolderCountCheckpoint {
+    /// The slot
+    pub slot: Slot,
+    /// The holder count at the end of the slot
+    pub holder_count: u64,
+}
+
+/// The policy of a version 1 config
+#[derive(BorshDeserialize)]
+struct TransferPolicyV1 {
+    gatekeeper_networks: Vec<Pubkey>,
+    flags: u64,
+}
+
+/// A config at version 1, as written by the migration from the original
+/// extra account metas layout
+#[derive(BorshDeserialize)]
+struct MintConfigV1 {
+    _version: u8,
+    bump_seed: u8,
+    mint: Pubkey,
+    authority: Pubkey,
+    policy: TransferPolicyV1,
+}
+
+impl From<MintConfigV1> for MintConfig {
+    fn from(config: MintConfigV1) -> Self {
+        Self::new(
+            config.mint,
+            config.bump_seed,
+            config.authority,
+            TransferPolicy {
+                gatekeeper_networks: config.policy.gatekeeper_networks,
+                flags: config.policy.flags,
+                ..TransferPolicy::default()
+            },
+        )
+    }
+}
+
+/// A config at version 2, before holder count checkpoints
+#[derive(BorshDeserialize)]
+struct MintConfigV2 {
+    _version: u8,
+    bump_seed: u8,
+    mint: Pubkey,
+    authority: Pubkey,
+    compliance_officer: Option<Pubkey>,
+    guardian: Option<Pubkey>,
+    policy: TransferPolicy,
+    holder_count: u64,
+    travel_rule_buffer: Option<Pubkey>,
+    timelock_seconds: u32,
+    paused: bool,
+    base_pass_network: Option<Pubkey>,
+    shared_policy: Option<Pubkey>,
+}
+
+impl From<MintConfigV2> for MintConfig {
+    fn from(config: MintConfigV2) -> Self {
+        // earlier counts are unknown, so snapshots match the count at the upgrade
+        let checkpoint = HolderCountCheckpoint {
+            slot: 0,
+            holder_count: config.holder_count,
+        };
+        Self {
+            version: Self::CURRENT_VERSION,
+            bump_seed: config.bump_seed,
+            mint: config.mint,
+            authority: config.authority,
+            compliance_officer: config.compliance_officer,
+            guardian: config.guardian,
+            policy: config.policy,
+            holder_count: config.holder_count,
+            holder_count_checkpoints: [checkpoint; HOLDER_COUNT_CHECKPOINTS],
+            travel_rule_buffer: config.travel_rule_buffer,
+            timelock_seconds: config.timelock_seconds,
+            paused: config.paused,
+            base_pass_network: config.base_pass_network,
+            shared_policy: config.shared_policy,
+        }
+    }
+}
+
+/// Allows transfers between two token accounts that would otherwise fail
+/// the pass checks of the mint, e.g. for OTC trades or corporate actions.
+/// The deny list, holder cap, cooldown and other checks still apply.
+///
+/// Created by the config authority, and used up by the hook as transfers are made.
+/// A used-up permit stays open until the config authority closes it to reclaim its rent.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct TransferPermit {
+    /// Bump seed of the permit PDA
+    pub bump_seed: u8,
+    /// The mint
+    pub mint: Pubkey,
+    /// The source token account
+    pub source: Pubkey,
+    /// The destination token account
+    pub destination: Pubkey,
+    /// The amount that may still be transferred under the permit
+    pub remaining_amount: u64,
+    /// The time after which the permit can no longer be used
+    pub expiry: UnixTimestamp,
+}
+
+impl TransferPermit {
+    /// Whether the permit covers a transfer of `amount` at time `now`
+    pub fn covers(&self, amount: u64, now: UnixTimestamp) -> bool {
+        now <= self.expiry && amount <= self.remaining_amount
+    }
+}
+
+impl ProgramAccount for TransferPermit {
+    const ACCOUNT_TYPE: AccountType = AccountType::TransferPermit;
+}
+
+/// Per token account state, tracking the outgoing transfers of the account.
+/// Only required by the hook if the mint has a transfer cooldown, or for
+/// transfers allowed by an approval.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SourceState {
+    /// Bump seed of the source state PDA
+    pub bump_seed: u8,
+    /// The mint
+    pub mint: Pubkey,
+    /// The token account
+    pub source: Pubkey,
+    /// Time of the last outgoing transfer, or 0 if there was none
+    pub last_transfer_time: UnixTimestamp,
+    /// The nonce the next approval for a transfer out of the account must carry.
+    /// Incremented each time an approval is used, so that it cannot be replayed.
+    pub approval_nonce: u64,
+}
+
+impl SourceState {
+    /// Whether the cooldown since the last outgoing transfer has passed at `now`
+    pub fn cooldown_elapsed(&self, cooldown_seconds: u32, now: UnixTimestamp) -> bool {
+        self.last_transfer_time == 0
+            || now >= self.last_transfer_time.saturating_add(cooldown_seconds as UnixTimestamp)
+    }
+}
+
+impl ProgramAccount for SourceState {
+    const ACCOUNT_TYPE: AccountType = AccountType::SourceState;
+}
+
+/// Records when the pass of a token account was first seen revoked, which
+/// starts the recovery period of mints with a recovery-only permanent delegate.
+///
+/// Created permissionlessly with `RecordRevokedPass`, and closed once the
+/// token account has a valid pass again or has been emptied.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct RevocationRecord {
+    /// Bump seed of the revocation record PDA
+    pub bump_seed: u8,
+    /// The mint
+    pub mint: Pubkey,
+    /// The token account
+    pub account: Pubkey,
+    /// The key that funded the record, refunded when it is closed
+    pub recorder: Pubkey,
+    /// The time the revocation was recorded
+    pub revoked_since: UnixTimestamp,
+}
+
+impl RevocationRecord {
+    /// Whether the recovery period has passed at `now`
+    pub fn recovery_period_elapsed(&self, recovery_period_seconds: u32, now: UnixTimestamp) -> bool {
+        now >= self
+            .revoked_since
+            .saturating_add(recovery_period_seconds as UnixTimestamp)
+    }
+}
+
+impl ProgramAccount for RevocationRecord {
+    const ACCOUNT_TYPE: AccountType = AccountType::RevocationRecord;
+}
+
+/// A transfer policy enforced by every mint linked to it, so that mints
+/// running under the same rules are updated together.
+///
+/// Mints are linked and unlinked by their admin, with
+/// `ConfigUpdate::LinkSharedPolicy` and `ConfigUpdate::UnlinkSharedPolicy`,
+/// once the authority of the shared policy approved them with
+/// `ConfigUpdate::SetApprovedMints`. The policy can only be closed once no
+/// mint is linked to it.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SharedPolicy {
+    /// Bump seed of the shared policy PDA
+    pub bump_seed: u8,
+    /// The key that created the policy, part of its address
+    pub creator: Pubkey,
+    /// The id chosen by the creator, part of its address
+    pub id: u64,
+    /// Allowed to update and close the policy, either a wallet or a
+    /// [Multisig](struct.Multisig.html) account
+    pub authority: Pubkey,
+    /// The transfer policy enforced by the linked mints
+    pub policy: TransferPolicy,
+    /// The mints the authority approved to link to the policy, and whether
+    /// each of them is linked
+    pub approved_mints: Vec<ApprovedMint>,
+    /// Delay before queued updates can be applied. Timelocked mints can only
+    /// link to a shared policy with at least their own delay.
+    pub timelock_seconds: u32,
+}
+
+impl SharedPolicy {
+    /// Hash of the settings of the shared policy, as recorded in its history.
+    /// Which mints are linked is left out, as it changes with links.
+    pub fn settings_hash(&self) -> Result<Hash, ProgramError> {
+        let mut settings = self.clone();
+        for approved_mint in &mut settings.approved_mints {
+            approved_mint.linked = false;
+        }
+        Ok(hash(&settings.pack()?))
+    }
+
+    /// The mints linked to the policy
+    pub fn linked_mints(&self) -> impl Iterator<Item = &Pubkey> {
+        self.approved_mints
+            .iter()
+            .filter(|approved_mint| approved_mint.linked)
+            .map(|approved_mint| &approved_mint.mint)
+    }
+
+    /// Marks a mint as linked or unlinked, refusing mints that were not approved
+    pub fn set_linked(&mut self, mint: &Pubkey, linked: bool) -> Result<(), ProgramError> {
+        let approved_mint = self
+            .approved_mints
+            .iter_mut()
+            .find(|approved_mint| approved_mint.mint == *mint)
+            .ok_or(CivicTransferHookError::MintNotApprovedForSharedPolicy)?;
+        approved_mint.linked = linked;
+        Ok(())
+    }
+
+    /// Applies an update to the shared policy. Besides policy updates, only
+    /// `SetAuthority`, transferring the shared policy itself, `SetTimelock` and
+    /// `SetApprovedMints` are accepted.
+    pub fn apply(&mut self, update: &ConfigUpdate) -> Result<(), ProgramError> {
+        match update {
+            ConfigUpdate::SetAuthority(authority) => {
+                self.authority = *authority;
+                Ok(())
+            }
+            ConfigUpdate::SetTimelock(seconds) => {
+                self.timelock_seconds = *seconds;
+                Ok(())
+            }
+            ConfigUpdate::SetApprovedMints(mints) => {
+                if mints.len() > MAX_LIST_LEN {
+                    return Err(CivicTransferHookError::ListTooLong.into());
+                }
+                // a linked mint keeps its approval until its admin unlinks it
+                if self.linked_mints().any(|mint| !mints.contains(mint)) {
+                    return Err(CivicTransferHookError::SharedPolicyInUse.into());
+                }
+                let linked_mints = self.linked_mints().copied().collect::<Vec<_>>();
+                self.approved_mints = mints
+                    .iter()
+                    .map(|mint| ApprovedMint {
+                        mint: *mint,
+                        linked: linked_mints.contains(mint),
+                    })
+                    .collect();
+                Ok(())
+            }
+            _ => update.apply_to_policy(&mut self.policy),
+        }
+    }
+}
+
+/// A mint approved to link to a shared policy
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ApprovedMint {
+    /// The mint
+    pub mint: Pubkey,
+    /// Whether the mint is linked to the shared policy
+    pub linked: bool,
+}
+
+impl ProgramAccount for SharedPolicy {
+    const ACCOUNT_TYPE: AccountType = AccountType::SharedPolicy;
+}
+
+/// Originator and beneficiary information kept for a transfer above the
+/// travel rule threshold of the mint
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct TravelRuleRecord {
+    /// Slot of the transfer
+    pub slot: Slot,
+    /// The source token account
+    pub source: Pubkey,
+    /// The destination token account
+    pub destination: Pubkey,
+    /// The amount transferred
+    pub amount: u64,
+    /// The pass of the source, or the default pubkey if it had none
+    pub source_gateway_token: Pubkey,
+    /// The pass of the destination, or the default pubkey if it had none
+    pub destination_gateway_token: Pubkey,
+}
+
+impl TravelRuleRecord {
+    /// Size of an encoded record
+    pub const LEN: usize = 144;
+}
+
+/// Header of a buffer of [TravelRuleRecord](struct.TravelRuleRecord.html)s.
+///
+/// Buffers are created by the config authority with the system program, owned
+/// by this program, as they are usually too large to be created by the program.
+/// Records follow the header. Records are never overwritten: once the buffer is
+/// full, transfers that must be recorded are rejected until the config authority
+/// rotates to a new buffer.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct TravelRuleBuffer {
+    /// The mint
+    pub mint: Pubkey,
+    /// Number of records the buffer can hold
+    pub capacity: u32,
+    /// Number of records ever written to the buffer
+    pub total_records: u64,
+}
+
+impl TravelRuleBuffer {
+    /// Size of the packed header, including the account type
+    pub const HEADER_LEN: usize = 45;
+
+    /// Creates the header of an empty buffer for an account of `data_len` bytes
+    pub fn new(mint: Pubkey, data_len: usize) -> Self {
+        let capacity = data_len.saturating_sub(Self::HEADER_LEN) / TravelRuleRecord::LEN;
+        Self {
+            mint,
+            capacity: capacity.min(u32::MAX as usize) as u32,
+            total_records: 0,
+        }
+    }
+
+    /// Whether the buffer has no room for another record
+    pub fn is_full(&self) -> bool {
+        self.total_records >= self.capacity as u64
+    }
+
+    fn record_offset(&self, position: u64) -> usize {
+        Self::HEADER_LEN + position as usize * TravelRuleRecord::LEN
+    }
+
+    /// Appends a record to the buffer stored in `data`, failing if it is full
+    pub fn push(&mut self, data: &mut [u8], record: &TravelRuleRecord) -> Result<(), ProgramError> {
+        if self.is_full() {
+            return Err(CivicTransferHookError::TravelRuleBufferFull.into());
+        }
+        let offset = self.record_offset(self.total_records);
+        let mut output = data
+            .get_mut(offset..offset + TravelRuleRecord::LEN)
+            .ok_or(ProgramError::AccountDataTooSmall)?;
+        record
+            .serialize(&mut output)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        self.total_records += 1;
+        self.pack_into_slice(data)
+    }
+
+    /// Decodes the records held in a buffer account, oldest first
+    pub fn records(data: &[u8]) -> Result<Vec<TravelRuleRecord>, ProgramError> {
+        let buffer = Self::unpack(data)?;
+        (0..buffer.total_records.min(buffer.capacity as u64))
+            .map(|position| {
+                let offset = buffer.record_offset(position);
+                data.get(offset..offset + TravelRuleRecord::LEN)
+                    .ok_or(ProgramError::AccountDataTooSmall)
+                    .and_then(|record| {
+                        TravelRuleRecord::try_from_slice(record)
+                            .map_err(|_| ProgramError::InvalidAccountData)
+                    })
+            })
+            .collect()
+    }
+}
+
+impl ProgramAccount for TravelRuleBuffer {
+    const ACCOUNT_TYPE: AccountType = AccountType::TravelRuleBuffer;
+}
+
+/// An M-of-N authority, understood by every admin instruction of the program.
+///
+/// Multisig accounts are created with the system program, owned by this program,
+/// and initialized with `InitializeMultisig`. When a multisig is the authority,
+/// the instruction is approved if at least `m` of its signers sign it.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct Multisig {
+    /// Number of signers required
+    pub m: u8,
+    /// The keys that may approve instructions
+    pub signers: Vec<Pubkey>,
+}
+
+impl Multisig {
+    /// Size of a multisig account able to hold the maximum number of signers
+    pub const LEN: usize = 1 + 1 + 4 + 32 * MAX_MULTISIG_SIGNERS;
+
+    /// Creates a multisig, checking that `m` signers out of `signers` can be reached.
+    /// Signers must be distinct, as a key counts once however often it is listed.
+    pub fn new(m: u8, signers: Vec<Pubkey>) -> Result<Self, ProgramError> {
+        if m == 0 || m as usize > signers.len() || signers.len() > MAX_MULTISIG_SIGNERS {
+            return Err(CivicTransferHookError::InvalidMultisig.into());
+        }
+        if signers
+            .iter()
+            .enumerate()
+            .any(|(i, signer)| signers[..i].contains(signer))
+        {
+            return Err(CivicTransferHookError::InvalidMultisig.into());
+        }
+        Ok(Self { m, signers })
+    }
+
+    /// Counts the distinct multisig signers among the keys that signed
+    pub fn count_approvals<'a>(&self, signed: impl Iterator<Item = &'a Pubkey>) -> usize {
+        let mut approved = [false; MAX_MULTISIG_SIGNERS];
+        for key in signed {
+            if let Some(position) = self.signers.iter().position(|signer| signer == key) {
+                approved[position] = true;
+            }
+        }
+        approved.iter().filter(|approved| **approved).count()
+    }
+}
+
+impl ProgramAccount for Multisig {
+    const ACCOUNT_TYPE: AccountType = AccountType::Multisig;
+}
+
+/// A config update queued by the config authority of a timelocked mint, or
+/// by the authority of a timelocked shared policy
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PendingConfigUpdate {
+    /// The mint, or the shared policy, the update applies to, from which the
+    /// address of the pending update is derived
+    pub mint: Pubkey,
+    /// The queued update
+    pub update: ConfigUpdate,
+    /// The time from which the update can be applied
+    pub executable_at: UnixTimestamp,
+}
+
+impl ProgramAccount for PendingConfigUpdate {
+    const ACCOUNT_TYPE: AccountType = AccountType::PendingConfigUpdate;
+}
+
+/// A change recorded in the history of a mint config
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigHistoryEntry {
+    /// Slot of the change
+    pub slot: Slot,
+    /// Time of the change
+    pub unix_timestamp: UnixTimestamp,
+    /// The role holder that approved the change
+    pub actor: Pubkey,
+    /// What changed
+    pub kind: ConfigChangeKind,
+    /// [Settings hash](struct.MintConfig.html#method.settings_hash) before the change,
+    /// or that of the [SharedPolicy](struct.SharedPolicy.html#method.settings_hash)
+    pub old_hash: Hash,
+    /// Settings hash after the change
+    pub new_hash: Hash,
+}
+
+impl ConfigHistoryEntry {
+    /// Size of a packed entry
+    pub const LEN: usize = 113;
+}
+
+/// The history of changes to a mint config, kept so that the policy in force
+/// at any point in time can be established.
+///
+/// A shared policy keeps its own history, at the config history address of
+/// the shared policy. The history of a linked mint records when it was linked
+/// and unlinked, and its settings hash covers the address of the shared
+/// policy, whose history then gives the policy in force in between.
+///
+/// Entries follow the header. The account grows by one entry with each change,
+/// so it must be funded for rent beforehand, until it holds
+/// `MAX_CONFIG_HISTORY_ENTRIES`, after which the oldest entry is overwritten.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigHistory {
+    /// The mint, or the shared policy
+    pub mint: Pubkey,
+    /// Number of entries ever written to the history
+    pub total_entries: u64,
+}
+
+impl ConfigHistory {
+    /// Size of the packed header, including the account type
+    pub const HEADER_LEN: usize = 41;
+
+    /// Size of a history account holding `entries` entries
+    pub fn size_of(entries: usize) -> usize {
+        Self::HEADER_LEN + entries.min(MAX_CONFIG_HISTORY_ENTRIES) * ConfigHistoryEntry::LEN
+    }
+
+    fn entry_offset(position: u64) -> usize {
+        Self::HEADER_LEN
+            + (position % MAX_CONFIG_HISTORY_ENTRIES as u64) as usize * ConfigHistoryEntry::LEN
+    }
+
+    /// Size the account must have to hold the next entry
+    pub fn next_size(&self) -> usize {
+        Self::size_of(self.total_entries as usize + 1)
+    }
+
+    /// Appends an entry to the history stored in `data`, which must already
+    /// be `next_size()` bytes long
+    pub fn push(&mut self, data: &mut [u8], entry: &ConfigHistoryEntry) -> Result<(), ProgramError> {
+        let offset = Self::entry_offset(self.total_entries);
+        let mut output = data
+            .get_mut(offset..offset + ConfigHistoryEntry::LEN)
+            .ok_or(ProgramError::AccountDataTooSmall)?;
+        entry
+            .serialize(&mut output)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        self.total_entries += 1;
+        self.pack_into_slice(data)
+    }
+
+    /// Decodes the entries held in a history account, oldest first
+    pub fn entries(data: &[u8]) -> Result<Vec<ConfigHistoryEntry>, ProgramError> {
+        let history = Self::unpack(data)?;
+        let held = history
+            .total_entries
+            .min(MAX_CONFIG_HISTORY_ENTRIES as u64);
+        (history.total_entries - held..history.total_entries)
+            .map(|position| {
+                let offset = Self::entry_offset(position);
+                data.get(offset..offset + ConfigHistoryEntry::LEN)
+                    .ok_or(ProgramError::AccountDataTooSmall)
+                    .and_then(|entry| {
+                        ConfigHistoryEntry::try_from_slice(entry)
+                            .map_err(|_| ProgramError::InvalidAccountData)
+                    })
+            })
+            .collect()
+    }
+}
+
+impl ProgramAccount for ConfigHistory {
+    const ACCOUNT_TYPE: AccountType = AccountType::ConfigHistory;
+}
+
+/// The kind of a change recorded in a config history
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ConfigChangeKind {
+    /// The history was started, recording the config at that time
+    InitializeHistory,
+    /// A new travel rule buffer was registered
+    RotateTravelRuleBuffer,
+    /// `ConfigUpdate::SetAuthority`
+    SetAuthority,
+    /// `ConfigUpdate::SetGatekeeperNetworks`
+    SetGatekeeperNetworks,
+    /// `ConfigUpdate::SetFlags`
+    SetFlags,
+    /// `ConfigUpdate::SetPassStatePolicy`
+    SetPassStatePolicy,
+    /// `ConfigUpdate::SetPermanentDelegatePolicy`
+    SetPermanentDelegatePolicy,
+    /// `ConfigUpdate::SetEnforcementMode`
+    SetEnforcementMode,
+    /// `ConfigUpdate::SetApprover`
+    SetApprover,
+    /// `ConfigUpdate::SetTransferCooldown`
+    SetTransferCooldown,
+    /// `ConfigUpdate::SetHolderCap`
+    SetHolderCap,
+    /// `ConfigUpdate::SetTravelRuleThreshold`
+    SetTravelRuleThreshold,
+    /// `ConfigUpdate::SetTransferMatrix`
+    SetTransferMatrix,
+    /// `ConfigUpdate::SetIssuerAccounts`
+    SetIssuerAccounts,
+    /// `ConfigUpdate::SetTimelock`
+    SetTimelock,
+    /// `ConfigUpdate::SetPaused`
+    SetPaused,
+    /// `ConfigUpdate::SetComplianceOfficer`
+    SetComplianceOfficer,
+    /// `ConfigUpdate::SetGuardian`
+    SetGuardian,
+    /// `ConfigUpdate::SetAllowList`
+    SetAllowList,
+    /// `ConfigUpdate::SetDenyList`
+    SetDenyList,
+    /// `ConfigUpdate::SetBasePassNetwork`
+    SetBasePassNetwork,
+    /// `ConfigUpdate::LinkSharedPolicy`
+    LinkSharedPolicy,
+    /// `ConfigUpdate::UnlinkSharedPolicy`
+    UnlinkSharedPolicy,
+    /// `ConfigUpdate::SetRules`
+    SetRules,
+    /// `ConfigUpdate::AdjustHolderCount`
+    AdjustHolderCount,
+    /// `ConfigUpdate::SetApprovedMints`
+    SetApprovedMints,
+}
+
+/// The administrative roles of a mint
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// Manages roles and the policy
+    Admin,
+    /// Manages the allow and deny lists and transfer permits
+    ComplianceOfficer,
+    /// May pause and resume transfers
+    Guardian,
+}
+
+/// A change to a mint config, applied by the holder of the role it requires
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum ConfigUpdate {
+    /// Transfer the admin role to a new key
+    SetAuthority(Pubkey),
+    /// Replace the accepted gatekeeper networks
+    SetGatekeeperNetworks(Vec<Pubkey>),
+    /// Replace the policy flags
+    SetFlags(u64),
+    /// Replace the handling of inactive passes
+    SetPassStatePolicy(PassStatePolicy),
+    /// Replace the handling of transfers by the permanent delegate
+    SetPermanentDelegatePolicy(PermanentDelegatePolicy),
+    /// Switch between enforcing and monitoring the policy
+    SetEnforcementMode(EnforcementMode),
+    /// Set or clear the key that signs off-chain transfer approvals
+    SetApprover(Option<Pubkey>),
+    /// Set the minimum time between outgoing transfers of a token account, 0 to disable
+    SetTransferCooldown(u32),
+    /// Set or clear the maximum number of holders
+    SetHolderCap(Option<u64>),
+    /// Set or clear the amount from which transfers are recorded for the travel rule
+    SetTravelRuleThreshold(Option<u64>),
+    /// Replace the allowed routes between networks, empty to allow all
+    SetTransferMatrix(Vec<TransferRoute>),
+    /// Replace the issuer accounts, empty to let any holder send tokens
+    SetIssuerAccounts(Vec<Pubkey>),
+    /// Set the delay before queued updates can be applied
+    SetTimelock(u32),
+    /// Pause or resume all transfers. Not subject to the timelock.
+    SetPaused(bool),
+    /// Set or clear the compliance officer
+    SetComplianceOfficer(Option<Pubkey>),
+    /// Set or clear the guardian
+    SetGuardian(Option<Pubkey>),
+    /// Replace the allow list
+    SetAllowList(Vec<Pubkey>),
+    /// Replace the deny list
+    SetDenyList(Vec<Pubkey>),
+    /// Set or clear the network of the base passes that entitle holders to
+    /// a token pass issued by the program
+    SetBasePassNetwork(Option<Pubkey>),
+    /// Set or clear the rules that replace the recipient pass check
+    SetRules(Option<RuleSet>),
+    /// Add a difference to the holder count. Reconciliations are turned into
+    /// this correction when they are submitted.
+    AdjustHolderCount(i64),
+    /// Link the mint to a shared policy, which the hook then enforces in place
+    /// of the policy in the mint config. The mint must be approved by the
+    /// authority of the shared policy. Policy updates to the mint config are
+    /// refused while linked.
+    LinkSharedPolicy(Pubkey),
+    /// Unlink the mint from its shared policy. The mint config keeps a copy of
+    /// the shared policy, so the rules in force do not change until updated.
+    UnlinkSharedPolicy,
+    /// Replace the mints approved to link to a shared policy. Only accepted by
+    /// shared policies, and refused while a mint left out is still linked.
+    SetApprovedMints(Vec<Pubkey>),
+    /// Reconcile the holder count with a count taken off-chain, e.g. from the
+    /// token accounts with a non-zero balance. Holders added or removed by
+    /// transfers after the snapshot, including while the update is queued,
+    /// are kept.
+    ReconcileHolderCount {
+        /// The number of holders in the snapshot
+        holder_count: u64,
+        /// The slot of the snapshot, within the holder count checkpoints
+        /// of the config when the update is submitted
+        snapshot_slot: Slot,
+    },
+}
+
+impl ConfigUpdate {
+    /// Whether the update can be applied right away on a timelocked config,
+    /// as needed for emergency actions
+    pub fn is_exempt_from_timelock(&self) -> bool {
+        matches!(self, Self::SetPaused(_))
+    }
+
+    /// Whether the update only changes the transfer policy. Only such updates
+    /// can be made to a shared policy, and none can be made to the config of a
+    /// mint linked to one.
+    pub fn is_policy_update(&self) -> bool {
+        !matches!(
+            self,
+            Self::SetAuthority(_)
+                | Self::SetTimelock(_)
+                | Self::SetPaused(_)
+                | Self::SetComplianceOfficer(_)
+                | Self::SetGuardian(_)
+                | Self::SetBasePassNetwork(_)
+                | Self::AdjustHolderCount(_)
+                | Self::ReconcileHolderCount { .. }
+                | Self::LinkSharedPolicy(_)
+                | Self::UnlinkSharedPolicy
+                | Self::SetApprovedMints(_)
+        )
+    }
+
+    /// The role allowed to make the update
+    pub fn required_role(&self) -> Role {
+        match self {
+            Self::SetPaused(_) => Role::Guardian,
+            Self::SetAllowList(_) | Self::SetDenyList(_) => Role::ComplianceOfficer,
+            _ => Role::Admin,
+        }
+    }
+
+    /// The kind of change recorded in the config history
+    pub fn kind(&self) -> ConfigChangeKind {
+        match self {
+            Self::SetAuthority(_) => ConfigChangeKind::SetAuthority,
+            Self::SetGatekeeperNetworks(_) => ConfigChangeKind::SetGatekeeperNetworks,
+            Self::SetFlags(_) => ConfigChangeKind::SetFlags,
+            Self::SetPassStatePolicy(_) => ConfigChangeKind::SetPassStatePolicy,
+            Self::SetPermanentDelegatePolicy(_) => ConfigChangeKind::SetPermanentDelegatePolicy,
+            Self::SetEnforcementMode(_) => ConfigChangeKind::SetEnforcementMode,
+            Self::SetApprover(_) => ConfigChangeKind::SetApprover,
+            Self::SetTransferCooldown(_) => ConfigChangeKind::SetTransferCooldown,
+            Self::SetHolderCap(_) => ConfigChangeKind::SetHolderCap,
+            Self::SetTravelRuleThreshold(_) => ConfigChangeKind::SetTravelRuleThreshold,
+            Self::SetTransferMatrix(_) => ConfigChangeKind::SetTransferMatrix,
+            Self::SetIssuerAccounts(_) => ConfigChangeKind::SetIssuerAccounts,
+            Self::SetTimelock(_) => ConfigChangeKind::SetTimelock,
+            Self::SetPaused(_) => ConfigChangeKind::SetPaused,
+            Self::SetComplianceOfficer(_) => ConfigChangeKind::SetComplianceOfficer,
+            Self::SetGuardian(_) => ConfigChangeKind::SetGuardian,
+            Self::SetAllowList(_) => ConfigChangeKind::SetAllowList,
+            Self::SetDenyList(_) => ConfigChangeKind::SetDenyList,
+            Self::SetBasePassNetwork(_) => ConfigChangeKind::SetBasePassNetwork,
+            Self::SetRules(_) => ConfigChangeKind::SetRules,
+            Self::AdjustHolderCount(_) | Self::ReconcileHolderCount { .. } => {
+                ConfigChangeKind::AdjustHolderCount
+            }
+            Self::LinkSharedPolicy(_) => ConfigChangeKind::LinkSharedPolicy,
+            Self::UnlinkSharedPolicy => ConfigChangeKind::UnlinkSharedPolicy,
+            Self::SetApprovedMints(_) => ConfigChangeKind::SetApprovedMints,
+        }
+    }
+
+    /// The update to queue or apply in place of this one at `slot`. A
+    /// reconciliation becomes the difference between its snapshot and the count
+    /// tracked at the snapshot slot, so that later changes to the count are kept.
+    pub fn resolve(&self, config: &MintConfig, slot: Slot) -> Result<Self, ProgramError> {
+        match self {
+            Self::ReconcileHolderCount {
+                holder_count,
+                snapshot_slot,
+            } => {
+                let tracked_holder_count = config
+                    .holder_count_at(*snapshot_slot)
+                    .filter(|_| *snapshot_slot <= slot)
+                    .ok_or(CivicTransferHookError::InvalidHolderCountSnapshot)?;
+                Ok(Self::AdjustHolderCount(
+                    (*holder_count as i64).saturating_sub(tracked_holder_count as i64),
+                ))
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /// Applies the update to a config. Unlinking only clears the shared policy,
+    /// the caller copies the shared policy into the config beforehand.
+    pub fn apply(&self, config: &mut MintConfig) -> Result<(), ProgramError> {
+        match self {
+            Self::SetAuthority(authority) => config.authority = *authority,
+            Self::SetTimelock(seconds) => config.timelock_seconds = *seconds,
+            Self::SetPaused(paused) => config.paused = *paused,
+            Self::SetComplianceOfficer(compliance_officer) => {
+                config.compliance_officer = *compliance_officer
+            }
+            Self::SetGuardian(guardian) => config.guardian = *guardian,
+            Self::SetBasePassNetwork(network) => config.base_pass_network = *network,
+            Self::AdjustHolderCount(difference) => {
+                // the checkpoints are corrected as well, for later reconciliations
+                config.holder_count = config.holder_count.saturating_add_signed(*difference);
+                for checkpoint in &mut config.holder_count_checkpoints {
+                    checkpoint.holder_count =
+                        checkpoint.holder_count.saturating_add_signed(*difference);
+                }
+            }
+            // resolved into a correction when submitted
+            Self::ReconcileHolderCount { .. } => {
+                return Err(CivicTransferHookError::InvalidHolderCountSnapshot.into())
+            }
+            Self::LinkSharedPolicy(shared_policy) => config.shared_policy = Some(*shared_policy),
+            Self::UnlinkSharedPolicy => config.shared_policy = None,
+            _ => return self.apply_to_policy(&mut config.policy),
+        }
+        config.policy.validate()
+    }
+
+    /// Applies a policy update to a transfer policy
+    pub fn apply_to_policy(&self, policy: &mut TransferPolicy) -> Result<(), ProgramError> {
+        match self {
+            Self::SetGatekeeperNetworks(networks) => policy.gatekeeper_networks = networks.clone(),
+            Self::SetFlags(flags) => policy.flags = *flags,
+            Self::SetPassStatePolicy(pass_states) => policy.pass_states = pass_states.clone(),
+            Self::SetPermanentDelegatePolicy(permanent_delegate) => {
+                policy.permanent_delegate = *permanent_delegate
+            }
+            Self::SetEnforcementMode(enforcement) => policy.enforcement = *enforcement,
+            Self::SetApprover(approver) => policy.approver = *approver,
+            Self::SetTransferCooldown(seconds) => policy.transfer_cooldown_seconds = *seconds,
+            Self::SetHolderCap(holder_cap) => policy.holder_cap = *holder_cap,
+            Self::SetTravelRuleThreshold(threshold) => policy.travel_rule_threshold = *threshold,
+            Self::SetTransferMatrix(transfer_matrix) => {
+                policy.transfer_matrix = transfer_matrix.clone()
+            }
+            Self::SetIssuerAccounts(issuer_accounts) => {
+                policy.issuer_accounts = issuer_accounts.clone()
+            }
+            Self::SetAllowList(allow_list) => policy.allow_list = allow_list.clone(),
+            Self::SetDenyList(deny_list) => policy.deny_list = deny_list.clone(),
+            Self::SetRules(rules) => policy.rules = rules.clone(),
+            _ => return Err(CivicTransferHookError::NotAPolicyUpdate.into()),
+        }
+        policy.validate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::rules::RuleBuilder};
+
+    #[test]
+    fn mint_config_packing() {
+        let mint = Pubkey::new_unique();
+        let policy = TransferPolicy {
+            gatekeeper_networks: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            flags: 0,
+            pass_states: PassStatePolicy {
+                frozen: PassStateAction::Allow,
+                expiry_grace_seconds: 3600,
+                ..PassStatePolicy::default()
+            },
+            approver: Some(Pubkey::new_unique()),
+            ..TransferPolicy::default()
+        };
+        let config = MintConfig::new(mint, 254, Pubkey::new_unique(), policy);
+        let mut packed = config.pack().unwrap();
+        assert_eq!(packed[0], AccountType::MintConfig as u8);
+        assert_eq!(packed.len(), config.packed_len().unwrap());
+
+        // trailing space is ignored
+        packed.extend_from_slice(&[0; 16]);
+        assert_eq!(MintConfig::unpack_for_mint(&packed, &mint).unwrap(), config);
+        assert_eq!(
+            MintConfig::unpack_for_mint(&packed, &Pubkey::new_unique()).unwrap_err(),
+            CivicTransferHookError::IncorrectMintConfig.into()
+        );
+        assert_eq!(
+            MintConfig::unpack(&[0; 64]).unwrap_err(),
+            ProgramError::UninitializedAccount
+        );
+
+        // configs from a newer program are rejected
+        packed[1] = MintConfig::CURRENT_VERSION + 1;
+        assert_eq!(
+            MintConfig::unpack(&packed).unwrap_err(),
+            CivicTransferHookError::UnsupportedMintConfigVersion.into()
+        );
+    }
+
+    #[test]
+    fn upgrades_version_1_configs() {
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let network = Pubkey::new_unique();
+        let mut packed = vec![AccountType::MintConfig as u8, 1, 253];
+        packed.extend_from_slice(mint.as_ref());
+        packed.extend_from_slice(authority.as_ref());
+        packed.extend_from_slice(&1u32.to_le_bytes());
+        packed.extend_from_slice(network.as_ref());
+        packed.extend_from_slice(&7u64.to_le_bytes());
+
+        let config = MintConfig::unpack_for_mint(&packed, &mint).unwrap();
+        assert_eq!(
+            config,
+            MintConfig::new(
+                mint,
+                253,
+                authority,
+                TransferPolicy {
+                    gatekeeper_networks: vec![network],
+                    flags: 7,
+                    ..TransferPolicy::default()
+                },
+            )
+        );
+        assert_eq!(config.version, MintConfig::CURRENT_VERSION);
+        assert_eq!(MintConfig::unpack(&config.pack().unwrap()).unwrap(), config);
+    }
+
+    #[test]
+    fn upgrades_version_2_configs() {
+        let mint = Pubkey::new_unique();
+        let mut config = MintConfig::new(
+            mint,
+            253,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                holder_cap: Some(100),
+                ..TransferPolicy::default()
+            },
+        );
+        config.holder_count = 42;
+        config.paused = true;
+        config.shared_policy = Some(Pubkey::new_unique());
+
+        // a version 2 config has the same layout, without the checkpoints
+        let mut packed = config.pack().unwrap();
+        let checkpoints_len = config.holder_count_checkpoints.try_to_vec().unwrap().len();
+        // followed by an empty travel rule buffer, the timelock, the pause flag,
+        // an empty base pass network and the shared policy
+        let checkpoints_end = packed.len() - (1 + 4 + 1 + 1 + 33);
+        packed.drain(checkpoints_end - checkpoints_len..checkpoints_end);
+        packed[1] = 2;
+
+        let upgraded = MintConfig::unpack_for_mint(&packed, &mint).unwrap();
+        assert_eq!(upgraded.version, MintConfig::CURRENT_VERSION);
+        assert_eq!(upgraded.holder_count_at(0), Some(42));
+        assert_eq!(
+            upgraded,
+            MintConfig {
+                holder_count_checkpoints: [HolderCountCheckpoint {
+                    slot: 0,
+                    holder_count: 42,
+                }; HOLDER_COUNT_CHECKPOINTS],
+                ..config
+            }
+        );
+    }
+
+    #[test]
+    fn transfer_permit_coverage() {
+        let permit = TransferPermit {
+            bump_seed: 255,
+            mint: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            remaining_amount: 100,
+            expiry: 1_000,
+        };
+        assert!(permit.covers(100, 1_000));
+        assert!(!permit.covers(101, 1_000));
+        assert!(!permit.covers(1, 1_001));
+        let packed = permit.pack().unwrap();
+        assert_eq!(TransferPermit::unpack(&packed).unwrap(), permit);
+        assert_eq!(
+            MintConfig::unpack(&packed).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    #[test]
+    fn source_state_cooldown() {
+        let mut state = SourceState {
+            bump_seed: 255,
+            mint: Pubkey::new_unique(),
+            source: Pubkey::new_unique(),
+            last_transfer_time: 0,
+            approval_nonce: 0,
+        };
+        assert!(state.cooldown_elapsed(60, 0));
+        state.last_transfer_time = 1_000;
+        assert!(!state.cooldown_elapsed(60, 1_059));
+        assert!(state.cooldown_elapsed(60, 1_060));
+    }
+
+    #[test]
+    fn revocation_recovery_period() {
+        let record = RevocationRecord {
+            bump_seed: 255,
+            mint: Pubkey::new_unique(),
+            account: Pubkey::new_unique(),
+            recorder: Pubkey::new_unique(),
+            revoked_since: 1_000,
+        };
+        assert!(!record.recovery_period_elapsed(86_400, 87_399));
+        assert!(record.recovery_period_elapsed(86_400, 87_400));
+        assert!(record.recovery_period_elapsed(0, 1_000));
+    }
+
+    #[test]
+    fn travel_rule_buffer_fills_up() {
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0; TravelRuleBuffer::HEADER_LEN + 2 * TravelRuleRecord::LEN + 10];
+        let mut buffer = TravelRuleBuffer::new(mint, data.len());
+        assert_eq!(buffer.capacity, 2);
+        assert_eq!(buffer.packed_len().unwrap(), TravelRuleBuffer::HEADER_LEN);
+        buffer.pack_into_slice(&mut data).unwrap();
+
+        let record = |slot| TravelRuleRecord {
+            slot,
+            source: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            amount: 10_000,
+            source_gateway_token: Pubkey::new_unique(),
+            destination_gateway_token: Pubkey::default(),
+        };
+        assert_eq!(record(0).try_to_vec().unwrap().len(), TravelRuleRecord::LEN);
+        let records = [record(1), record(2)];
+        for record in &records {
+            assert!(!buffer.is_full());
+            buffer.push(&mut data, record).unwrap();
+        }
+        assert!(buffer.is_full());
+        assert_eq!(TravelRuleBuffer::unpack(&data).unwrap(), buffer);
+        assert_eq!(TravelRuleBuffer::records(&data).unwrap(), records.to_vec());
+
+        // a full buffer keeps its records and rejects new ones
+        let packed = data.clone();
+        assert_eq!(
+            buffer.push(&mut data, &record(3)).unwrap_err(),
+            CivicTransferHookError::TravelRuleBufferFull.into()
+        );
+        assert_eq!(buffer.total_records, 2);
+        assert_eq!(data, packed);
+
+        // so does a buffer too small for any record
+        let mut data = vec![0; TravelRuleBuffer::HEADER_LEN + 10];
+        let mut buffer = TravelRuleBuffer::new(mint, data.len());
+        assert_eq!(buffer.capacity, 0);
+        assert_eq!(
+            buffer.push(&mut data, &record(1)).unwrap_err(),
+            CivicTransferHookError::TravelRuleBufferFull.into()
+        );
+    }
+
+    #[test]
+    fn multisig_approvals() {
+        let signers: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        assert!(Multisig::new(0, signers.clone()).is_err());
+        assert!(Multisig::new(4, signers.clone()).is_err());
+        // a repeated signer could never reach the threshold
+        assert_eq!(
+            Multisig::new(2, vec![signers[0], signers[0]]).unwrap_err(),
+            CivicTransferHookError::InvalidMultisig.into()
+        );
+        assert!(Multisig::new(2, vec![signers[0], signers[1], signers[0]]).is_err());
+        let multisig = Multisig::new(2, signers.clone()).unwrap();
+        assert_eq!(multisig.pack().unwrap().len(), 1 + 1 + 4 + 32 * 3);
+
+        let outsider = Pubkey::new_unique();
+        assert_eq!(multisig.count_approvals([&signers[0], &outsider].into_iter()), 1);
+        // signing twice does not count twice
+        assert_eq!(multisig.count_approvals([&signers[1], &signers[1]].into_iter()), 1);
+        assert_eq!(multisig.count_approvals(signers.iter()), 3);
+    }
+
+    #[test]
+    fn config_update_validates_networks() {
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                ..TransferPolicy::default()
+            },
+        );
+        assert_eq!(
+            ConfigUpdate::SetGatekeeperNetworks(vec![]).apply(&mut config),
+            Err(CivicTransferHookError::InvalidGatekeeperNetworks.into())
+        );
+        let networks: Vec<Pubkey> = (0..MAX_GATEKEEPER_NETWORKS)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        ConfigUpdate::SetGatekeeperNetworks(networks.clone())
+            .apply(&mut config)
+            .unwrap();
+        assert_eq!(config.policy.gatekeeper_networks, networks);
+
+        // rules may only refer to accepted networks
+        let mut builder = RuleBuilder::default();
+        let root = builder.destination_has_pass(MAX_GATEKEEPER_NETWORKS as u8 - 1);
+        let rules = builder.build(root, MAX_GATEKEEPER_NETWORKS).unwrap();
+        ConfigUpdate::SetRules(Some(rules)).apply(&mut config).unwrap();
+        assert_eq!(
+            ConfigUpdate::SetGatekeeperNetworks(networks[..1].to_vec()).apply(&mut config),
+            Err(CivicTransferHookError::InvalidRules.into())
+        );
+    }
+
+    #[test]
+    fn roles_default_to_admin() {
+        let admin = Pubkey::new_unique();
+        let guardian = Pubkey::new_unique();
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            admin,
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                ..TransferPolicy::default()
+            },
+        );
+        ConfigUpdate::SetGuardian(Some(guardian))
+            .apply(&mut config)
+            .unwrap();
+        assert_eq!(config.role_holder(Role::Admin), admin);
+        assert_eq!(config.role_holder(Role::ComplianceOfficer), admin);
+        assert_eq!(config.role_holder(Role::Guardian), guardian);
+        assert_eq!(ConfigUpdate::SetPaused(true).required_role(), Role::Guardian);
+        assert_eq!(
+            ConfigUpdate::SetDenyList(vec![]).required_role(),
+            Role::ComplianceOfficer
+        );
+        assert_eq!(ConfigUpdate::SetGuardian(None).required_role(), Role::Admin);
+    }
+
+    #[test]
+    fn only_pausing_skips_the_timelock() {
+        assert!(ConfigUpdate::SetPaused(true).is_exempt_from_timelock());
+        assert!(ConfigUpdate::SetPaused(false).is_exempt_from_timelock());
+        assert!(!ConfigUpdate::SetTimelock(0).is_exempt_from_timelock());
+        assert!(!ConfigUpdate::SetFlags(0).is_exempt_from_timelock());
+    }
+
+    #[test]
+    fn policy_updates() {
+        let mut policy = TransferPolicy::default();
+        ConfigUpdate::SetTransferCooldown(60)
+            .apply_to_policy(&mut policy)
+            .unwrap();
+        assert_eq!(policy.transfer_cooldown_seconds, 60);
+        assert!(ConfigUpdate::SetAllowList(vec![]).is_policy_update());
+
+        for update in [
+            ConfigUpdate::SetAuthority(Pubkey::new_unique()),
+            ConfigUpdate::SetPaused(true),
+            ConfigUpdate::SetBasePassNetwork(None),
+            ConfigUpdate::AdjustHolderCount(1),
+            ConfigUpdate::ReconcileHolderCount {
+                holder_count: 1,
+                snapshot_slot: 0,
+            },
+        ] {
+            assert!(!update.is_policy_update());
+            assert_eq!(
+                update.apply_to_policy(&mut policy).unwrap_err(),
+                CivicTransferHookError::NotAPolicyUpdate.into()
+            );
+        }
+    }
+
+    #[test]
+    fn holder_count_adjustments() {
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy {
+                gatekeeper_networks: vec![Pubkey::new_unique()],
+                holder_cap: Some(100),
+                ..TransferPolicy::default()
+            },
+        );
+        config.track_holder_count(10, 1);
+        ConfigUpdate::AdjustHolderCount(5).apply(&mut config).unwrap();
+        assert_eq!(config.holder_count, 15);
+        assert_eq!(config.holder_count_at(1), Some(15));
+        ConfigUpdate::AdjustHolderCount(-20).apply(&mut config).unwrap();
+        assert_eq!(config.holder_count, 0);
+        assert_eq!(config.holder_count_at(1), Some(0));
+        assert!(!ConfigUpdate::AdjustHolderCount(1).is_exempt_from_timelock());
+        assert_eq!(ConfigUpdate::AdjustHolderCount(1).required_role(), Role::Admin);
+    }
+
+    #[test]
+    fn holder_count_reconciliation() {
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy::default(),
+        );
+        config.track_holder_count(10, 100);
+        config.track_holder_count(11, 100);
+        config.track_holder_count(12, 105);
+        assert_eq!(config.holder_count_at(99), Some(0));
+        assert_eq!(config.holder_count_at(100), Some(11));
+        assert_eq!(config.holder_count_at(104), Some(11));
+        assert_eq!(config.holder_count_at(110), Some(12));
+
+        // a snapshot of 20 holders at slot 102 keeps the holder added at slot 105
+        let reconciliation = ConfigUpdate::ReconcileHolderCount {
+            holder_count: 20,
+            snapshot_slot: 102,
+        };
+        let update = reconciliation.resolve(&config, 110).unwrap();
+        assert_eq!(update, ConfigUpdate::AdjustHolderCount(9));
+        assert_eq!(update.kind(), reconciliation.kind());
+        update.apply(&mut config).unwrap();
+        assert_eq!(config.holder_count, 21);
+        assert_eq!(config.holder_count_at(102), Some(20));
+        assert_eq!(
+            reconciliation.apply(&mut config).unwrap_err(),
+            CivicTransferHookError::InvalidHolderCountSnapshot.into()
+        );
+
+        // snapshots from future slots are rejected
+        assert_eq!(
+            reconciliation.resolve(&config, 101).unwrap_err(),
+            CivicTransferHookError::InvalidHolderCountSnapshot.into()
+        );
+        // as are snapshots older than the checkpoints
+        for slot in 200..200 + HOLDER_COUNT_CHECKPOINTS as Slot {
+            config.track_holder_count(slot, slot);
+        }
+        assert_eq!(
+            reconciliation.resolve(&config, 300).unwrap_err(),
+            CivicTransferHookError::InvalidHolderCountSnapshot.into()
+        );
+        assert_eq!(
+            ConfigUpdate::ReconcileHolderCount {
+                holder_count: 200,
+                snapshot_slot: 200,
+            }
+            .resolve(&config, 300)
+            .unwrap(),
+            ConfigUpdate::AdjustHolderCount(0)
+        );
+        assert_eq!(
+            ConfigUpdate::AdjustHolderCount(1).resolve(&config, 300).unwrap(),
+            ConfigUpdate::AdjustHolderCount(1)
+        );
+    }
+
+    #[test]
+    fn config_history_wraps_around() {
+        let mint = Pubkey::new_unique();
+        let mut history = ConfigHistory {
+            mint,
+            total_entries: 0,
+        };
+        let entry = |slot| ConfigHistoryEntry {
+            slot,
+            unix_timestamp: slot as UnixTimestamp * 2,
+            actor: Pubkey::new_unique(),
+            kind: ConfigChangeKind::SetFlags,
+            old_hash: Hash::new_unique(),
+            new_hash: Hash::new_unique(),
+        };
+        assert_eq!(entry(0).try_to_vec().unwrap().len(), ConfigHistoryEntry::LEN);
+        assert_eq!(history.packed_len().unwrap(), ConfigHistory::HEADER_LEN);
+
+        let mut data = vec![0; ConfigHistory::HEADER_LEN];
+        history.pack_into_slice(&mut data).unwrap();
+        let entries: Vec<_> = (0..MAX_CONFIG_HISTORY_ENTRIES as u64 + 2).map(entry).collect();
+        for entry in &entries {
+            data.resize(history.next_size(), 0);
+            history.push(&mut data, entry).unwrap();
+        }
+        assert_eq!(data.len(), ConfigHistory::size_of(MAX_CONFIG_HISTORY_ENTRIES));
+        assert_eq!(ConfigHistory::unpack(&data).unwrap(), history);
+        assert_eq!(ConfigHistory::entries(&data).unwrap(), entries[2..].to_vec());
+    }
+
+    #[test]
+    fn settings_hash_ignores_holder_count() {
+        let mut config = MintConfig::new(
+            Pubkey::new_unique(),
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy::default(),
+        );
+        let settings_hash = config.settings_hash().unwrap();
+        config.track_holder_count(10, 1);
+        assert_eq!(config.settings_hash().unwrap(), settings_hash);
+        config.paused = true;
+        assert_ne!(config.settings_hash().unwrap(), settings_hash);
+    }
+
+    #[test]
+    fn shared_policy_settings_hash_ignores_links() {
+        let mint = Pubkey::new_unique();
+        let mut shared_policy = SharedPolicy {
+            bump_seed: 255,
+            creator: Pubkey::new_unique(),
+            id: 7,
+            authority: Pubkey::new_unique(),
+            policy: TransferPolicy::default(),
+            approved_mints: vec![ApprovedMint {
+                mint,
+                linked: false,
+            }],
+            timelock_seconds: 0,
+        };
+        let settings_hash = shared_policy.settings_hash().unwrap();
+        shared_policy.set_linked(&mint, true).unwrap();
+        assert_eq!(shared_policy.settings_hash().unwrap(), settings_hash);
+        shared_policy.apply(&ConfigUpdate::SetTimelock(3_600)).unwrap();
+        assert_ne!(shared_policy.settings_hash().unwrap(), settings_hash);
+        assert_eq!(
+            shared_policy
+                .apply(&ConfigUpdate::LinkSharedPolicy(Pubkey::new_unique()))
+                .unwrap_err(),
+            CivicTransferHookError::NotAPolicyUpdate.into()
+        );
+    }
+
+    #[test]
+    fn shared_policy_approved_mints() {
+        let linked_mint = Pubkey::new_unique();
+        let other_mint = Pubkey::new_unique();
+        let mut shared_policy = SharedPolicy {
+            bump_seed: 255,
+            creator: Pubkey::new_unique(),
+            id: 0,
+            authority: Pubkey::new_unique(),
+            policy: TransferPolicy::default(),
+            approved_mints: Vec::new(),
+            timelock_seconds: 0,
+        };
+
+        // mints link only once approved
+        assert_eq!(
+            shared_policy.set_linked(&linked_mint, true).unwrap_err(),
+            CivicTransferHookError::MintNotApprovedForSharedPolicy.into()
+        );
+        shared_policy
+            .apply(&ConfigUpdate::SetApprovedMints(vec![
+                linked_mint,
+                other_mint,
+            ]))
+            .unwrap();
+        shared_policy.set_linked(&linked_mint, true).unwrap();
+        assert_eq!(
+            shared_policy.linked_mints().collect::<Vec<_>>(),
+            vec![&linked_mint]
+        );
+
+        // approvals can be withdrawn from unlinked mints only, links are kept
+        assert_eq!(
+            shared_policy
+                .apply(&ConfigUpdate::SetApprovedMints(vec![other_mint]))
+                .unwrap_err(),
+            CivicTransferHookError::SharedPolicyInUse.into()
+        );
+        shared_policy
+            .apply(&ConfigUpdate::SetApprovedMints(vec![linked_mint]))
+            .unwrap();
+        assert_eq!(
+            shared_policy.approved_mints,
+            vec![ApprovedMint {
+                mint: linked_mint,
+                linked: true,
+            }]
+        );
+        assert_eq!(
+            shared_policy
+                .apply(&ConfigUpdate::SetApprovedMints(vec![
+                    linked_mint;
+                    MAX_LIST_LEN + 1
+                ]))
+                .unwrap_err(),
+            CivicTransferHookError::ListTooLong.into()
+        );
+
+        // the config of a mint does not accept approvals
+        let mut config = MintConfig::new(
+            linked_mint,
+            255,
+            Pubkey::new_unique(),
+            TransferPolicy::default(),
+        );
+        assert_eq!(
+            ConfigUpdate::SetApprovedMints(vec![other_mint])
+                .apply(&mut config)
+                .unwrap_err(),
+            CivicTransferHookError::NotAPolicyUpdate.into()
+        );
+    }
+
+    #[test]
+    fn transfer_matrix_routes() {
+        let route = |sender_network, recipient_network| TransferRoute {
+            sender_network,
+            recipient_network,
+        };
+        // 0: EU, 1: UK, 2: US
+        let mut policy = TransferPolicy {
+            gatekeeper_networks: vec![
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            ],
+            transfer_matrix: vec![route(0, 0), route(0, 1), route(2, 2)],
+            ..TransferPolicy::default()
+        };
+        policy.validate().unwrap();
+        assert!(policy.allows_route(0b001, 0b010));
+        assert!(policy.allows_route(0b101, 0b100));
+        assert!(!policy.allows_route(0b001, 0b100));
+        assert!(!policy.allows_route(0b010, 0b001));
+
+        policy.transfer_matrix.push(route(0, 3));
+        assert_eq!(
+            policy.validate().unwrap_err(),
+            CivicTransferHookError::InvalidTransferMatrix.into()
+        );
+    }
+}
@@ -3,12 +3,32 @@
 #![cfg(feature = "test-sbf")]
 
 use {
-    solana_program_test::{processor, tokio, ProgramTest},
+    borsh::{BorshDeserialize, BorshSerialize},
+    civic_transfer_hook::{
+        error::CivicTransferHookError,
+        extra_account_metas::build_extra_account_metas,
+        instruction::{
+            check_eligibility, create_shared_policy, freeze_account,
+            initialize_extra_account_metas, issue_token_pass, rotate_travel_rule_buffer,
+            thaw_account, update_config, update_shared_policy, EligibilityVerdict,
+        },
+        pass::get_gateway_token_address,
+        processor::{ASSOCIATED_TOKEN_PROGRAM_ID, GATEWAY_PROGRAM_ID},
+        solana_program::borsh0_10::try_from_slice_unchecked,
+        state::{
+            get_gatekeeper_authority_address, get_hook_authority_address, get_mint_config_address,
+            get_shared_policy_address, ConfigUpdate, MintConfig, PermanentDelegatePolicy,
+            ProgramAccount, SharedPolicy, TransferPolicy, TravelRuleBuffer, TravelRuleRecord,
+            FLAG_REQUIRE_ASSOCIATED_DESTINATION,
+        },
+    },
+    solana_gateway::state::{GatewayToken, GatewayTokenState},
+    solana_program_test::{processor, tokio, ProgramTest, ProgramTestContext},
     solana_sdk::{
         account::Account as SolanaAccount,
         account_info::AccountInfo,
         entrypoint::ProgramResult,
-        instruction::{AccountMeta, InstructionError},
+        instruction::{AccountMeta, Instruction, InstructionError},
         program_option::COption,
         pubkey::Pubkey,
         signature::Signer,
@@ -16,15 +36,18 @@ use {
         system_instruction, sysvar,
         transaction::{Transaction, TransactionError},
     },
-    spl_tlv_account_resolution::state::ExtraAccountMetas,
+    spl_tlv_account_resolution::{error::AccountResolutionError, state::ExtraAccountMetaList},
     spl_token_2022::{
-        extension::{transfer_hook::TransferHookAccount, ExtensionType, StateWithExtensionsMut},
+        extension::{
+            permanent_delegate::PermanentDelegate, transfer_hook::TransferHookAccount,
+            ExtensionType, StateWithExtensions, StateWithExtensionsMut,
+        },
         state::{Account, AccountState, Mint},
     },
     spl_transfer_hook_interface::{
         error::TransferHookError,
         get_extra_account_metas_address,
-        instruction::{execute_with_extra_account_metas, initialize_extra_account_metas},
+        instruction::{execute_with_extra_account_metas, ExecuteInstruction},
         onchain,
     },
 };
@@ -55,151 +78,356 @@ fn setup(program_id: &Pubkey) -> ProgramTest {
     program_test
 }
 
-#[allow(clippy::too_many_arguments)]
-fn setup_token_accounts(
+/// Adds a mint by hand, with the given freeze authority and permanent delegate
+fn add_mint(
     program_test: &mut ProgramTest,
-    program_id: &Pubkey,
     mint_address: &Pubkey,
     mint_authority: &Pubkey,
-    source: &Pubkey,
-    destination: &Pubkey,
-    owner: &Pubkey,
-    decimals: u8,
-    transferring: bool,
+    freeze_authority: Option<&Pubkey>,
+    permanent_delegate: Option<&Pubkey>,
 ) {
-    // add mint, source, and destination accounts by hand to always force
-    // the "transferring" flag to true
-    let mint_size = ExtensionType::try_calculate_account_len::<Mint>(&[]).unwrap();
+    let extension_types = if permanent_delegate.is_some() {
+        vec![ExtensionType::PermanentDelegate]
+    } else {
+        vec![]
+    };
+    let mint_size = ExtensionType::try_calculate_account_len::<Mint>(&extension_types).unwrap();
     let mut mint_data = vec![0; mint_size];
     let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut mint_data).unwrap();
-    let token_amount = 1_000_000_000_000;
+    if let Some(permanent_delegate) = permanent_delegate {
+        let extension = state.init_extension::<PermanentDelegate>(true).unwrap();
+        extension.delegate = Some(*permanent_delegate).try_into().unwrap();
+    }
     state.base = Mint {
         mint_authority: COption::Some(*mint_authority),
-        supply: token_amount,
-        decimals,
+        supply: 1_000_000_000_000,
+        decimals: 2,
         is_initialized: true,
-        freeze_authority: COption::None,
+        freeze_authority: freeze_authority.copied().into(),
     };
     state.pack_base();
+    if !extension_types.is_empty() {
+        state.init_account_type().unwrap();
+    }
     program_test.add_account(
         *mint_address,
         SolanaAccount {
             lamports: 1_000_000_000,
             data: mint_data,
-            owner: *program_id,
+            owner: spl_token_2022::id(),
             ..SolanaAccount::default()
         },
     );
+}
 
+/// Adds a token account by hand, to be able to force the "transferring" flag
+fn add_token_account(
+    program_test: &mut ProgramTest,
+    address: &Pubkey,
+    mint_address: &Pubkey,
+    owner: &Pubkey,
+    state: AccountState,
+    transferring: bool,
+) {
     let account_size =
         ExtensionType::try_calculate_account_len::<Account>(&[ExtensionType::TransferHookAccount])
             .unwrap();
     let mut account_data = vec![0; account_size];
-    let mut state =
+    let mut account =
         StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut account_data).unwrap();
-    let extension = state.init_extension::<TransferHookAccount>(true).unwrap();
+    let extension = account.init_extension::<TransferHookAccount>(true).unwrap();
     extension.transferring = transferring.into();
-    let token_amount = 1_000_000_000_000;
-    state.base = Account {
+    account.base = Account {
         mint: *mint_address,
         owner: *owner,
-        amount: token_amount,
+        amount: 1_000_000_000_000,
         delegate: COption::None,
-        state: AccountState::Initialized,
+        state,
         is_native: COption::None,
         delegated_amount: 0,
         close_authority: COption::None,
     };
-    state.pack_base();
-    state.init_account_type().unwrap();
+    account.pack_base();
+    account.init_account_type().unwrap();
 
     program_test.add_account(
-        *source,
+        *address,
         SolanaAccount {
             lamports: 1_000_000_000,
-            data: account_data.clone(),
-            owner: *program_id,
+            data: account_data,
+            owner: spl_token_2022::id(),
             ..SolanaAccount::default()
         },
     );
+}
+
+/// Adds the gateway token of `owner` in `gatekeeper_network`, in the given state
+fn add_gateway_token(
+    program_test: &mut ProgramTest,
+    owner: &Pubkey,
+    gatekeeper_network: &Pubkey,
+    state: GatewayTokenState,
+) {
+    let mut gateway_token =
+        GatewayToken::new_vanilla(owner, gatekeeper_network, &Pubkey::new_unique(), &None);
+    gateway_token.state = state;
     program_test.add_account(
-        *destination,
+        get_gateway_token_address(owner, gatekeeper_network),
         SolanaAccount {
             lamports: 1_000_000_000,
-            data: account_data,
-            owner: *program_id,
+            data: gateway_token.try_to_vec().unwrap(),
+            owner: GATEWAY_PROGRAM_ID,
             ..SolanaAccount::default()
         },
     );
 }
 
+/// The extra accounts of a transfer to `destination`, for a mint initialized
+/// with a single gatekeeper network and no other policy
+fn transfer_extra_accounts(
+    program_id: &Pubkey,
+    mint_address: &Pubkey,
+    destination: &Pubkey,
+    gatekeeper_network: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(get_mint_config_address(mint_address, program_id), false),
+        AccountMeta::new_readonly(GATEWAY_PROGRAM_ID, false),
+        AccountMeta::new_readonly(*gatekeeper_network, false),
+        AccountMeta::new_readonly(
+            get_gateway_token_address(destination, gatekeeper_network),
+            false,
+        ),
+    ]
+}
+
+/// Creates an `Execute` instruction for a transfer to `destination`, for a mint
+/// initialized with a single gatekeeper network and no other policy
+fn execute(
+    program_id: &Pubkey,
+    source: &Pubkey,
+    mint_address: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    gatekeeper_network: &Pubkey,
+) -> Instruction {
+    execute_with_extra_account_metas(
+        program_id,
+        source,
+        mint_address,
+        destination,
+        authority,
+        &get_extra_account_metas_address(mint_address, program_id),
+        &transfer_extra_accounts(program_id, mint_address, destination, gatekeeper_network),
+        0,
+    )
+}
+
+/// Reads the state of a token account
+async fn token_account_state(context: &mut ProgramTestContext, address: &Pubkey) -> AccountState {
+    let account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap();
+    StateWithExtensions::<Account>::unpack(&account.data)
+        .unwrap()
+        .base
+        .state
+}
+
+/// Signs `instructions` with the payer and `signers`, and processes them
+async fn process_instructions(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let signers = [&[&context.payer][..], signers].concat();
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        signers.as_slice(),
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|error| error.unwrap())
+}
+
+/// The error of a transaction whose first instruction failed with `error`
+fn custom_error(error: CivicTransferHookError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+/// Simulates a `CheckEligibility` instruction for a transfer to `destination`,
+/// for a mint initialized with a single gatekeeper network, returning its verdict
+async fn simulate_check_eligibility(
+    context: &mut ProgramTestContext,
+    program_id: &Pubkey,
+    source: &Pubkey,
+    mint_address: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    gatekeeper_network: &Pubkey,
+) -> EligibilityVerdict {
+    let mut instruction = check_eligibility(
+        program_id,
+        source,
+        mint_address,
+        destination,
+        authority,
+        &get_extra_account_metas_address(mint_address, program_id),
+        0,
+    );
+    instruction.accounts.extend(transfer_extra_accounts(
+        program_id,
+        mint_address,
+        destination,
+        gatekeeper_network,
+    ));
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    let simulation = context
+        .banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    let mut data = simulation
+        .simulation_details
+        .unwrap()
+        .return_data
+        .unwrap()
+        .data;
+    // the runtime trims trailing zeros off return data, the verdict is a bool and a u64
+    data.resize(9, 0);
+    EligibilityVerdict::try_from_slice(&data).unwrap()
+}
+
+/// Funds the extra account metas and mint config of a mint, and initializes
+/// them for `gatekeeper_network`
+async fn initialize_mint(
+    context: &mut ProgramTestContext,
+    program_id: &Pubkey,
+    mint_address: &Pubkey,
+    mint_authority: &Keypair,
+    gatekeeper_network: &Pubkey,
+) {
+    let payer = context.payer.pubkey();
+    let extra_account_metas = get_extra_account_metas_address(mint_address, program_id);
+    let rent = context.banks_client.get_rent().await.unwrap();
+    // leaves room for the config updates made by the tests
+    let rent_lamports = rent.minimum_balance(1_000);
+    process_instructions(
+        context,
+        &[
+            system_instruction::transfer(&payer, &extra_account_metas, rent_lamports),
+            system_instruction::transfer(
+                &payer,
+                &get_mint_config_address(mint_address, program_id),
+                rent_lamports,
+            ),
+            initialize_extra_account_metas(
+                program_id,
+                &extra_account_metas,
+                mint_address,
+                &mint_authority.pubkey(),
+                gatekeeper_network,
+            ),
+        ],
+        &[mint_authority],
+    )
+    .await
+    .unwrap();
+}
+
+/// Applies a config update, approved by the config authority
+async fn apply_config_update(
+    context: &mut ProgramTestContext,
+    program_id: &Pubkey,
+    mint_address: &Pubkey,
+    authority: &Keypair,
+    update: ConfigUpdate,
+) {
+    process_instructions(
+        context,
+        &[update_config(
+            program_id,
+            &get_extra_account_metas_address(mint_address, program_id),
+            mint_address,
+            &authority.pubkey(),
+            &[],
+            update,
+            None,
+        )],
+        &[authority],
+    )
+    .await
+    .unwrap();
+    // transactions repeated after the update must not be taken for those sent before
+    context.last_blockhash = context.get_new_latest_blockhash().await.unwrap();
+}
+
 #[tokio::test]
 async fn success_execute() {
     let program_id = Pubkey::new_unique();
     let mut program_test = setup(&program_id);
 
-    let token_program_id = spl_token_2022::id();
     let wallet = Keypair::new();
     let mint_address = Pubkey::new_unique();
     let mint_authority = Keypair::new();
-    let mint_authority_pubkey = mint_authority.pubkey();
     let source = Pubkey::new_unique();
     let destination = Pubkey::new_unique();
-    let decimals = 2;
 
-    setup_token_accounts(
+    add_mint(
         &mut program_test,
-        &token_program_id,
         &mint_address,
-        &mint_authority_pubkey,
-        &source,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
+    for address in [&source, &destination] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Initialized,
+            true,
+        );
+    }
+    add_gateway_token(
+        &mut program_test,
         &destination,
-        &wallet.pubkey(),
-        decimals,
-        true,
+        &TEST_GKN,
+        GatewayTokenState::Active,
     );
 
-    let extra_account_metas = get_extra_account_metas_address(&mint_address, &program_id);
-
-    let extra_account_pubkeys = [
-        AccountMeta::new_readonly(TEST_GKN, false)
-        // AccountMeta::new_readonly(sysvar::instructions::id(), false),
-        // AccountMeta::new_readonly(mint_authority_pubkey, true),
-        // AccountMeta::new(extra_account_metas, false),
-    ];
     let mut context = program_test.start_with_context().await;
-    let rent = context.banks_client.get_rent().await.unwrap();
-    let rent_lamports =
-        rent.minimum_balance(ExtraAccountMetas::size_of(extra_account_pubkeys.len()).unwrap());
-    let transaction = Transaction::new_signed_with_payer(
-        &[
-            system_instruction::transfer(
-                &context.payer.pubkey(),
-                &extra_account_metas,
-                rent_lamports,
-            ),
-            initialize_extra_account_metas(
-                &program_id,
-                &extra_account_metas,
-                &mint_address,
-                &mint_authority_pubkey,
-                &extra_account_pubkeys,
-            ),
-        ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &mint_authority],
-        context.last_blockhash,
-    );
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
 
-    context
-        .banks_client
-        .process_transaction(transaction)
-        .await
-        .unwrap();
+    let extra_account_metas = get_extra_account_metas_address(&mint_address, &program_id);
+    let extra_account_pubkeys =
+        transfer_extra_accounts(&program_id, &mint_address, &destination, &TEST_GKN);
 
     // fail with missing account
     {
-        let transaction = Transaction::new_signed_with_payer(
+        let error = process_instructions(
+            &mut context,
             &[execute_with_extra_account_metas(
                 &program_id,
                 &source,
@@ -207,34 +435,25 @@ async fn success_execute() {
                 &destination,
                 &wallet.pubkey(),
                 &extra_account_metas,
-                &[],
+                &extra_account_pubkeys[..3],
                 0,
             )],
-            Some(&context.payer.pubkey()),
-            &[&context.payer],
-            context.last_blockhash,
-        );
-        let error = context
-            .banks_client
-            .process_transaction(transaction)
-            .await
-            .unwrap_err()
-            .unwrap();
+            &[],
+        )
+        .await
+        .unwrap_err();
         assert_eq!(
             error,
-            TransactionError::InstructionError(
-                0,
-                InstructionError::Custom(TransferHookError::IncorrectAccount as u32),
-            )
+            custom_error(CivicTransferHookError::NotEnoughExtraAccounts)
         );
     }
 
     // fail with wrong account
     {
-        let extra_account_pubkeys = [
-            AccountMeta::new_readonly(sysvar::instructions::id(), false),
-        ];
-        let transaction = Transaction::new_signed_with_payer(
+        let mut extra_account_pubkeys = extra_account_pubkeys.clone();
+        extra_account_pubkeys[1] = AccountMeta::new_readonly(sysvar::instructions::id(), false);
+        let error = process_instructions(
+            &mut context,
             &[execute_with_extra_account_metas(
                 &program_id,
                 &source,
@@ -245,107 +464,147 @@ async fn success_execute() {
                 &extra_account_pubkeys,
                 0,
             )],
-            Some(&context.payer.pubkey()),
-            &[&context.payer],
-            context.last_blockhash,
-        );
-        let error = context
-            .banks_client
-            .process_transaction(transaction)
-            .await
-            .unwrap_err()
-            .unwrap();
+            &[],
+        )
+        .await
+        .unwrap_err();
         assert_eq!(
             error,
             TransactionError::InstructionError(
                 0,
-                InstructionError::Custom(TransferHookError::IncorrectAccount as u32),
+                InstructionError::Custom(AccountResolutionError::IncorrectAccount as u32),
             )
         );
     }
 
     // success with correct params
-    {
-        let transaction = Transaction::new_signed_with_payer(
-            &[execute_with_extra_account_metas(
-                &program_id,
-                &source,
-                &mint_address,
-                &destination,
-                &wallet.pubkey(),
-                &extra_account_metas,
-                &extra_account_pubkeys,
-                0,
-            )],
-            Some(&context.payer.pubkey()),
-            &[&context.payer],
-            context.last_blockhash,
-        );
-        context
-            .banks_client
-            .process_transaction(transaction)
-            .await
-            .unwrap();
-    }
+    process_instructions(
+        &mut context,
+        &[execute(
+            &program_id,
+            &source,
+            &mint_address,
+            &destination,
+            &wallet.pubkey(),
+            &TEST_GKN,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
 }
 
 #[tokio::test]
-async fn fail_incorrect_derivation() {
+async fn fail_token_accounts_of_another_mint() {
     let program_id = Pubkey::new_unique();
     let mut program_test = setup(&program_id);
 
-    let token_program_id = spl_token_2022::id();
     let wallet = Keypair::new();
     let mint_address = Pubkey::new_unique();
+    let other_mint_address = Pubkey::new_unique();
     let mint_authority = Keypair::new();
-    let mint_authority_pubkey = mint_authority.pubkey();
     let source = Pubkey::new_unique();
     let destination = Pubkey::new_unique();
-    let decimals = 2;
-    setup_token_accounts(
+
+    add_mint(
         &mut program_test,
-        &token_program_id,
         &mint_address,
-        &mint_authority_pubkey,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
+    add_token_account(
+        &mut program_test,
         &source,
+        &other_mint_address,
+        &wallet.pubkey(),
+        AccountState::Initialized,
+        true,
+    );
+    add_token_account(
+        &mut program_test,
         &destination,
+        &mint_address,
         &wallet.pubkey(),
-        decimals,
+        AccountState::Initialized,
         true,
     );
+    add_gateway_token(
+        &mut program_test,
+        &destination,
+        &TEST_GKN,
+        GatewayTokenState::Active,
+    );
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+
+    let error = process_instructions(
+        &mut context,
+        &[execute(
+            &program_id,
+            &source,
+            &mint_address,
+            &destination,
+            &wallet.pubkey(),
+            &TEST_GKN,
+        )],
+        &[],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        custom_error(CivicTransferHookError::TokenAccountMintMismatch)
+    );
+}
+
+#[tokio::test]
+async fn fail_incorrect_derivation() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
 
     // wrong derivation
     let extra_account_metas = get_extra_account_metas_address(&program_id, &mint_address);
 
     let mut context = program_test.start_with_context().await;
     let rent = context.banks_client.get_rent().await.unwrap();
-    let rent_lamports = rent.minimum_balance(ExtraAccountMetas::size_of(0).unwrap());
+    let rent_lamports = rent.minimum_balance(1_000);
+    let payer = context.payer.pubkey();
 
-    let transaction = Transaction::new_signed_with_payer(
+    let error = process_instructions(
+        &mut context,
         &[
-            system_instruction::transfer(
-                &context.payer.pubkey(),
-                &extra_account_metas,
-                rent_lamports,
-            ),
+            system_instruction::transfer(&payer, &extra_account_metas, rent_lamports),
             initialize_extra_account_metas(
                 &program_id,
                 &extra_account_metas,
                 &mint_address,
-                &mint_authority_pubkey,
-                &[],
+                &mint_authority.pubkey(),
+                &TEST_GKN,
             ),
         ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &mint_authority],
-        context.last_blockhash,
-    );
-    let error = context
-        .banks_client
-        .process_transaction(transaction)
-        .await
-        .unwrap_err()
-        .unwrap();
+        &[&mint_authority],
+    )
+    .await
+    .unwrap_err();
     assert_eq!(
         error,
         TransactionError::InstructionError(1, InstructionError::InvalidSeeds)
@@ -380,64 +639,45 @@ async fn success_on_chain_invoke() {
         processor!(process_instruction),
     );
 
-    let token_program_id = spl_token_2022::id();
     let wallet = Keypair::new();
     let mint_address = Pubkey::new_unique();
     let mint_authority = Keypair::new();
-    let mint_authority_pubkey = mint_authority.pubkey();
     let source = Pubkey::new_unique();
     let destination = Pubkey::new_unique();
-    let decimals = 2;
 
-    setup_token_accounts(
+    add_mint(
         &mut program_test,
-        &token_program_id,
         &mint_address,
-        &mint_authority_pubkey,
-        &source,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
+    for address in [&source, &destination] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Initialized,
+            true,
+        );
+    }
+    add_gateway_token(
+        &mut program_test,
         &destination,
-        &wallet.pubkey(),
-        decimals,
-        true,
+        &TEST_GKN,
+        GatewayTokenState::Active,
     );
 
-    let extra_account_metas = get_extra_account_metas_address(&mint_address, &hook_program_id);
-
-    let writable_pubkey = Pubkey::new_unique();
-    let extra_account_pubkeys = [
-        AccountMeta::new_readonly(sysvar::instructions::id(), false),
-        AccountMeta::new_readonly(mint_authority_pubkey, true),
-        AccountMeta::new(writable_pubkey, false),
-    ];
     let mut context = program_test.start_with_context().await;
-    let rent = context.banks_client.get_rent().await.unwrap();
-    let rent_lamports =
-        rent.minimum_balance(ExtraAccountMetas::size_of(extra_account_pubkeys.len()).unwrap());
-    let transaction = Transaction::new_signed_with_payer(
-        &[
-            system_instruction::transfer(
-                &context.payer.pubkey(),
-                &extra_account_metas,
-                rent_lamports,
-            ),
-            initialize_extra_account_metas(
-                &hook_program_id,
-                &extra_account_metas,
-                &mint_address,
-                &mint_authority_pubkey,
-                &extra_account_pubkeys,
-            ),
-        ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &mint_authority],
-        context.last_blockhash,
-    );
-
-    context
-        .banks_client
-        .process_transaction(transaction)
-        .await
-        .unwrap();
+    initialize_mint(
+        &mut context,
+        &hook_program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
 
     // easier to hack this up!
     let mut test_instruction = execute_with_extra_account_metas(
@@ -446,23 +686,15 @@ async fn success_on_chain_invoke() {
         &mint_address,
         &destination,
         &wallet.pubkey(),
-        &extra_account_metas,
-        &extra_account_pubkeys,
+        &get_extra_account_metas_address(&mint_address, &hook_program_id),
+        &transfer_extra_accounts(&hook_program_id, &mint_address, &destination, &TEST_GKN),
         0,
     );
     test_instruction
         .accounts
         .insert(0, AccountMeta::new_readonly(hook_program_id, false));
-    let transaction = Transaction::new_signed_with_payer(
-        &[test_instruction],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &mint_authority],
-        context.last_blockhash,
-    );
 
-    context
-        .banks_client
-        .process_transaction(transaction)
+    process_instructions(&mut context, &[test_instruction], &[])
         .await
         .unwrap();
 }
@@ -472,83 +704,896 @@ async fn fail_without_transferring_flag() {
     let program_id = Pubkey::new_unique();
     let mut program_test = setup(&program_id);
 
-    let token_program_id = spl_token_2022::id();
     let wallet = Keypair::new();
     let mint_address = Pubkey::new_unique();
     let mint_authority = Keypair::new();
-    let mint_authority_pubkey = mint_authority.pubkey();
     let source = Pubkey::new_unique();
     let destination = Pubkey::new_unique();
-    let decimals = 2;
-    setup_token_accounts(
+
+    add_mint(
         &mut program_test,
-        &token_program_id,
         &mint_address,
-        &mint_authority_pubkey,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
+    for address in [&source, &destination] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Initialized,
+            false,
+        );
+    }
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+
+    let error = process_instructions(
+        &mut context,
+        &[execute(
+            &program_id,
+            &source,
+            &mint_address,
+            &destination,
+            &wallet.pubkey(),
+            &TEST_GKN,
+        )],
+        &[],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(TransferHookError::ProgramCalledOutsideOfTransfer as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn permanent_delegate_policy() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let wallet = Keypair::new();
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let permanent_delegate = Pubkey::new_unique();
+    let source = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        None,
+        Some(&permanent_delegate),
+    );
+    for address in [&source, &destination] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Initialized,
+            true,
+        );
+    }
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+
+    let delegate_transfer = execute(
+        &program_id,
+        &source,
+        &mint_address,
+        &destination,
+        &permanent_delegate,
+        &TEST_GKN,
+    );
+    let owner_transfer = execute(
+        &program_id,
         &source,
+        &mint_address,
         &destination,
         &wallet.pubkey(),
-        decimals,
+        &TEST_GKN,
+    );
+
+    // by default, the permanent delegate needs the recipient to hold a pass
+    let error = process_instructions(&mut context, &[delegate_transfer.clone()], &[])
+        .await
+        .unwrap_err();
+    assert_eq!(error, custom_error(CivicTransferHookError::PassMissing));
+
+    // skipping pass checks only lets the permanent delegate through
+    apply_config_update(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        ConfigUpdate::SetPermanentDelegatePolicy(PermanentDelegatePolicy::SkipPassChecks),
+    )
+    .await;
+    process_instructions(&mut context, &[delegate_transfer.clone()], &[])
+        .await
+        .unwrap();
+    let error = process_instructions(&mut context, &[owner_transfer], &[])
+        .await
+        .unwrap_err();
+    assert_eq!(error, custom_error(CivicTransferHookError::PassMissing));
+
+    apply_config_update(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        ConfigUpdate::SetPermanentDelegatePolicy(PermanentDelegatePolicy::Reject),
+    )
+    .await;
+    let error = process_instructions(&mut context, &[delegate_transfer], &[])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        custom_error(CivicTransferHookError::PermanentDelegateTransferRejected)
+    );
+}
+
+#[tokio::test]
+async fn check_eligibility_verdicts() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let wallet = Keypair::new();
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let source = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    let destination_without_pass = Pubkey::new_unique();
+
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
+    // the token accounts are not transferring, as nothing is transferred
+    for address in [&source, &destination, &destination_without_pass] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Initialized,
+            false,
+        );
+    }
+    add_gateway_token(
+        &mut program_test,
+        &destination,
+        &TEST_GKN,
+        GatewayTokenState::Active,
+    );
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+
+    let verdict = simulate_check_eligibility(
+        &mut context,
+        &program_id,
+        &source,
+        &mint_address,
+        &destination,
+        &wallet.pubkey(),
+        &TEST_GKN,
+    )
+    .await;
+    assert_eq!(
+        verdict,
+        EligibilityVerdict {
+            eligible: true,
+            error: 0,
+        }
+    );
+
+    let verdict = simulate_check_eligibility(
+        &mut context,
+        &program_id,
+        &source,
+        &mint_address,
+        &destination_without_pass,
+        &wallet.pubkey(),
+        &TEST_GKN,
+    )
+    .await;
+    assert_eq!(
+        verdict,
+        EligibilityVerdict {
+            eligible: false,
+            error: CivicTransferHookError::PassMissing as u64,
+        }
+    );
+}
+
+#[tokio::test]
+async fn issuer_only_transfers() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let wallet = Keypair::new();
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let issuer = Pubkey::new_unique();
+    let holder = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
+    for address in [&issuer, &holder, &destination] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Initialized,
+            true,
+        );
+    }
+    add_gateway_token(
+        &mut program_test,
+        &destination,
+        &TEST_GKN,
+        GatewayTokenState::Active,
+    );
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+    apply_config_update(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        ConfigUpdate::SetIssuerAccounts(vec![issuer]),
+    )
+    .await;
+
+    let error = process_instructions(
+        &mut context,
+        &[execute(
+            &program_id,
+            &holder,
+            &mint_address,
+            &destination,
+            &wallet.pubkey(),
+            &TEST_GKN,
+        )],
+        &[],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error, custom_error(CivicTransferHookError::SourceNotIssuer));
+
+    process_instructions(
+        &mut context,
+        &[execute(
+            &program_id,
+            &issuer,
+            &mint_address,
+            &destination,
+            &wallet.pubkey(),
+            &TEST_GKN,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn associated_destination_required() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let wallet = Keypair::new();
+    let recipient = Pubkey::new_unique();
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let source = Pubkey::new_unique();
+    let (associated_destination, _) = Pubkey::find_program_address(
+        &[
+            recipient.as_ref(),
+            spl_token_2022::id().as_ref(),
+            mint_address.as_ref(),
+        ],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    );
+    let auxiliary_destination = Pubkey::new_unique();
+
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
+    add_token_account(
+        &mut program_test,
+        &source,
+        &mint_address,
+        &wallet.pubkey(),
+        AccountState::Initialized,
+        true,
+    );
+    for destination in [&associated_destination, &auxiliary_destination] {
+        add_token_account(
+            &mut program_test,
+            destination,
+            &mint_address,
+            &recipient,
+            AccountState::Initialized,
+            true,
+        );
+        add_gateway_token(
+            &mut program_test,
+            destination,
+            &TEST_GKN,
+            GatewayTokenState::Active,
+        );
+    }
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+    apply_config_update(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        ConfigUpdate::SetFlags(FLAG_REQUIRE_ASSOCIATED_DESTINATION),
+    )
+    .await;
+
+    let error = process_instructions(
+        &mut context,
+        &[execute(
+            &program_id,
+            &source,
+            &mint_address,
+            &auxiliary_destination,
+            &wallet.pubkey(),
+            &TEST_GKN,
+        )],
+        &[],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        custom_error(CivicTransferHookError::DestinationNotAssociatedTokenAccount)
+    );
+
+    process_instructions(
+        &mut context,
+        &[execute(
+            &program_id,
+            &source,
+            &mint_address,
+            &associated_destination,
+            &wallet.pubkey(),
+            &TEST_GKN,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn thaw_with_valid_pass() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let wallet = Keypair::new();
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let token_account = Pubkey::new_unique();
+    let token_account_without_pass = Pubkey::new_unique();
+
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        Some(&get_hook_authority_address(&mint_address, &program_id)),
+        None,
+    );
+    for address in [&token_account, &token_account_without_pass] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Frozen,
+            false,
+        );
+    }
+    add_gateway_token(
+        &mut program_test,
+        &token_account,
+        &TEST_GKN,
+        GatewayTokenState::Active,
+    );
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+
+    let error = process_instructions(
+        &mut context,
+        &[thaw_account(
+            &program_id,
+            &token_account_without_pass,
+            &mint_address,
+            &get_gateway_token_address(&token_account_without_pass, &TEST_GKN),
+            None,
+        )],
+        &[],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error, custom_error(CivicTransferHookError::PassMissing));
+    assert_eq!(
+        token_account_state(&mut context, &token_account_without_pass).await,
+        AccountState::Frozen
+    );
+
+    process_instructions(
+        &mut context,
+        &[thaw_account(
+            &program_id,
+            &token_account,
+            &mint_address,
+            &get_gateway_token_address(&token_account, &TEST_GKN),
+            None,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_account_state(&mut context, &token_account).await,
+        AccountState::Initialized
+    );
+}
+
+#[tokio::test]
+async fn freeze_without_valid_pass() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let wallet = Keypair::new();
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let token_account = Pubkey::new_unique();
+    let revoked_token_account = Pubkey::new_unique();
+
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        Some(&get_hook_authority_address(&mint_address, &program_id)),
+        None,
+    );
+    for address in [&token_account, &revoked_token_account] {
+        add_token_account(
+            &mut program_test,
+            address,
+            &mint_address,
+            &wallet.pubkey(),
+            AccountState::Initialized,
+            false,
+        );
+    }
+    add_gateway_token(
+        &mut program_test,
+        &token_account,
+        &TEST_GKN,
+        GatewayTokenState::Active,
+    );
+    add_gateway_token(
+        &mut program_test,
+        &revoked_token_account,
+        &TEST_GKN,
+        GatewayTokenState::Revoked,
+    );
+
+    let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+
+    let error = process_instructions(
+        &mut context,
+        &[freeze_account(
+            &program_id,
+            &token_account,
+            &mint_address,
+            &[TEST_GKN],
+            None,
+        )],
+        &[],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error, custom_error(CivicTransferHookError::PassStillValid));
+    assert_eq!(
+        token_account_state(&mut context, &token_account).await,
+        AccountState::Initialized
+    );
+
+    process_instructions(
+        &mut context,
+        &[freeze_account(
+            &program_id,
+            &revoked_token_account,
+            &mint_address,
+            &[TEST_GKN],
+            None,
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_account_state(&mut context, &revoked_token_account).await,
+        AccountState::Frozen
+    );
+}
+
+#[tokio::test]
+async fn issue_token_pass_for_base_pass() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+    program_test.add_program(
+        "solana_gateway",
+        GATEWAY_PROGRAM_ID,
+        processor!(solana_gateway::processor::process_instruction),
+    );
+
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let other_mint_address = Pubkey::new_unique();
+    let base_pass_network = Pubkey::new_unique();
+    let token_pass_network = Keypair::new();
+    let owner = Keypair::new();
+    let owner_without_base_pass = Keypair::new();
+    let token_account = Pubkey::new_unique();
+    let token_account_without_base_pass = Pubkey::new_unique();
+    let other_token_account = Pubkey::new_unique();
+
+    for mint_address in [&mint_address, &other_mint_address] {
+        add_mint(
+            &mut program_test,
+            mint_address,
+            &mint_authority.pubkey(),
+            None,
+            None,
+        );
+    }
+    add_token_account(
+        &mut program_test,
+        &token_account,
+        &mint_address,
+        &owner.pubkey(),
+        AccountState::Initialized,
+        false,
+    );
+    add_token_account(
+        &mut program_test,
+        &other_token_account,
+        &other_mint_address,
+        &owner.pubkey(),
+        AccountState::Initialized,
         false,
     );
+    add_token_account(
+        &mut program_test,
+        &token_account_without_base_pass,
+        &mint_address,
+        &owner_without_base_pass.pubkey(),
+        AccountState::Initialized,
+        false,
+    );
+    add_gateway_token(
+        &mut program_test,
+        &owner.pubkey(),
+        &base_pass_network,
+        GatewayTokenState::Active,
+    );
+
+    let mut context = program_test.start_with_context().await;
+    for mint_address in [&mint_address, &other_mint_address] {
+        initialize_mint(
+            &mut context,
+            &program_id,
+            mint_address,
+            &mint_authority,
+            &token_pass_network.pubkey(),
+        )
+        .await;
+        apply_config_update(
+            &mut context,
+            &program_id,
+            mint_address,
+            &mint_authority,
+            ConfigUpdate::SetBasePassNetwork(Some(base_pass_network)),
+        )
+        .await;
+    }
+
+    // the network registers the gatekeeper authority PDA of the first mint only
+    let payer = context.payer.pubkey();
+    let gatekeeper_authority =
+        get_gatekeeper_authority_address(&mint_address, &token_pass_network.pubkey(), &program_id);
+    process_instructions(
+        &mut context,
+        &[solana_gateway::instruction::add_gatekeeper(
+            &payer,
+            &gatekeeper_authority,
+            &token_pass_network.pubkey(),
+        )],
+        &[&token_pass_network],
+    )
+    .await
+    .unwrap();
+
+    // another mint accepting the same networks cannot issue passes as that PDA
+    let mut instruction = issue_token_pass(
+        &program_id,
+        &payer,
+        &other_token_account,
+        &other_mint_address,
+        &owner.pubkey(),
+        &base_pass_network,
+        &token_pass_network.pubkey(),
+        None,
+    );
+    let (gatekeeper_account, _) = solana_gateway::state::get_gatekeeper_address_with_seed(
+        &gatekeeper_authority,
+        &token_pass_network.pubkey(),
+    );
+    instruction.accounts[7] = AccountMeta::new_readonly(gatekeeper_authority, false);
+    instruction.accounts[8] = AccountMeta::new_readonly(gatekeeper_account, false);
+    let error = process_instructions(&mut context, &[instruction], &[&owner])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        custom_error(CivicTransferHookError::IncorrectGatekeeperAuthority)
+    );
+
+    let error = process_instructions(
+        &mut context,
+        &[issue_token_pass(
+            &program_id,
+            &payer,
+            &token_account_without_base_pass,
+            &mint_address,
+            &owner_without_base_pass.pubkey(),
+            &base_pass_network,
+            &token_pass_network.pubkey(),
+            None,
+        )],
+        &[&owner_without_base_pass],
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error,
+        custom_error(CivicTransferHookError::BasePassNotActive)
+    );
+
+    process_instructions(
+        &mut context,
+        &[issue_token_pass(
+            &program_id,
+            &payer,
+            &token_account,
+            &mint_address,
+            &owner.pubkey(),
+            &base_pass_network,
+            &token_pass_network.pubkey(),
+            None,
+        )],
+        &[&owner],
+    )
+    .await
+    .unwrap();
+    let gateway_token = context
+        .banks_client
+        .get_account(get_gateway_token_address(
+            &token_account,
+            &token_pass_network.pubkey(),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(gateway_token.owner, GATEWAY_PROGRAM_ID);
+    let gateway_token = try_from_slice_unchecked::<GatewayToken>(&gateway_token.data).unwrap();
+    assert_eq!(gateway_token.owner_wallet, token_account);
+    assert_eq!(gateway_token.state, GatewayTokenState::Active);
+}
+
+#[tokio::test]
+async fn rotate_travel_rule_buffer_of_linked_mint() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = setup(&program_id);
+
+    let mint_address = Pubkey::new_unique();
+    let mint_authority = Keypair::new();
+    let buffer = Keypair::new();
+    add_mint(
+        &mut program_test,
+        &mint_address,
+        &mint_authority.pubkey(),
+        None,
+        None,
+    );
 
-    let extra_account_metas = get_extra_account_metas_address(&mint_address, &program_id);
-    let extra_account_pubkeys = [];
     let mut context = program_test.start_with_context().await;
+    initialize_mint(
+        &mut context,
+        &program_id,
+        &mint_address,
+        &mint_authority,
+        &TEST_GKN,
+    )
+    .await;
+
+    let payer = context.payer.pubkey();
+    let extra_account_metas = get_extra_account_metas_address(&mint_address, &program_id);
+    let shared_policy = get_shared_policy_address(&mint_authority.pubkey(), 0, &program_id);
     let rent = context.banks_client.get_rent().await.unwrap();
-    let rent_lamports =
-        rent.minimum_balance(ExtraAccountMetas::size_of(extra_account_pubkeys.len()).unwrap());
-    let transaction = Transaction::new_signed_with_payer(
+    let buffer_len = TravelRuleBuffer::HEADER_LEN + 2 * TravelRuleRecord::LEN;
+    process_instructions(
+        &mut context,
         &[
-            system_instruction::transfer(
-                &context.payer.pubkey(),
+            system_instruction::transfer(&payer, &shared_policy, rent.minimum_balance(1_000)),
+            create_shared_policy(
+                &program_id,
+                &mint_authority.pubkey(),
+                0,
+                TransferPolicy {
+                    gatekeeper_networks: vec![TEST_GKN],
+                    travel_rule_threshold: Some(1_000),
+                    ..TransferPolicy::default()
+                },
+            ),
+            update_shared_policy(
+                &program_id,
+                &shared_policy,
+                &mint_authority.pubkey(),
+                &[],
+                ConfigUpdate::SetApprovedMints(vec![mint_address]),
+            ),
+            update_config(
+                &program_id,
                 &extra_account_metas,
-                rent_lamports,
+                &mint_address,
+                &mint_authority.pubkey(),
+                &[],
+                ConfigUpdate::LinkSharedPolicy(shared_policy),
+                Some(&shared_policy),
             ),
-            initialize_extra_account_metas(
+            system_instruction::create_account(
+                &payer,
+                &buffer.pubkey(),
+                rent.minimum_balance(buffer_len),
+                buffer_len as u64,
+                &program_id,
+            ),
+            rotate_travel_rule_buffer(
                 &program_id,
                 &extra_account_metas,
                 &mint_address,
-                &mint_authority_pubkey,
-                &extra_account_pubkeys,
+                &mint_authority.pubkey(),
+                &[],
+                &buffer.pubkey(),
+                Some(&shared_policy),
             ),
         ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &mint_authority],
-        context.last_blockhash,
-    );
+        &[&mint_authority, &buffer],
+    )
+    .await
+    .unwrap();
 
-    context
+    // the extra account metas follow the shared policy and the new buffer
+    // without a separate `SyncExtraAccountMetas`
+    let config = context
         .banks_client
-        .process_transaction(transaction)
+        .get_account(get_mint_config_address(&mint_address, &program_id))
         .await
+        .unwrap()
         .unwrap();
-    let transaction = Transaction::new_signed_with_payer(
-        &[execute_with_extra_account_metas(
-            &program_id,
-            &source,
-            &mint_address,
-            &destination,
-            &wallet.pubkey(),
-            &extra_account_metas,
-            &extra_account_pubkeys,
-            0,
-        )],
-        Some(&context.payer.pubkey()),
-        &[&context.payer],
-        context.last_blockhash,
-    );
-    let error = context
+    let mut config = MintConfig::unpack(&config.data).unwrap();
+    assert_eq!(config.travel_rule_buffer, Some(buffer.pubkey()));
+    let shared_policy_account = context
         .banks_client
-        .process_transaction(transaction)
+        .get_account(shared_policy)
         .await
-        .unwrap_err()
+        .unwrap()
         .unwrap();
-    assert_eq!(
-        error,
-        TransactionError::InstructionError(
-            0,
-            InstructionError::Custom(TransferHookError::ProgramCalledOutsideOfTransfer as u32)
-        )
-    );
+    config.policy = SharedPolicy::unpack(&shared_policy_account.data)
+        .unwrap()
+        .policy;
+    let (expected_extra_account_metas, _) = build_extra_account_metas(&config).unwrap();
+    let mut expected_data =
+        vec![0; ExtraAccountMetaList::size_of(expected_extra_account_metas.len()).unwrap()];
+    ExtraAccountMetaList::init::<ExecuteInstruction>(
+        &mut expected_data,
+        &expected_extra_account_metas,
+    )
+    .unwrap();
+    let extra_account_metas_account = context
+        .banks_client
+        .get_account(extra_account_metas)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(extra_account_metas_account.data, expected_data);
 }